@@ -0,0 +1,58 @@
+//! Number of heap allocations formatting list-heavy and deeply nested documents,
+//! counted by a global allocator.
+//! Run with `cargo bench --bench allocations`.
+use std::{alloc::System, fmt::Write};
+
+use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
+
+#[global_allocator]
+static GLOBAL: &StatsAlloc<System> = &INSTRUMENTED_SYSTEM;
+
+/// `items` list items, each with a nested list, a block quote, and a code block.
+fn nested_lists(items: usize) -> String {
+    let mut document = String::new();
+    for item in 0..items {
+        _ = write!(
+            document,
+            "{item}. Item\n\
+             \x20   *  Nested\n\
+             \x20      -  Deeper with *emphasis*\n\n\
+             \x20   >  Quoted\n\n\
+             \x20       indented code\n\n"
+        );
+    }
+    document
+}
+
+/// `sections` block quotes nested 6 levels deep.
+fn nested_quotes(sections: usize) -> String {
+    "> > > > > > Deeply quoted text\n\n".repeat(sections)
+}
+
+fn count(name: &str, config: Config, input: &str) {
+    let formatter = <MarkdownFormatter>::with_config(config);
+    let region = Region::new(GLOBAL);
+    let output = formatter.format(input).unwrap();
+    let stats = region.change();
+    drop(output);
+    let allocations = stats.allocations + stats.reallocations;
+    println!(
+        "{name:<40} {:>8} bytes {allocations:>10} allocations ({:.2} per byte)",
+        input.len(),
+        allocations as f64 / input.len() as f64
+    );
+}
+
+fn main() {
+    for (name, input) in [
+        ("nested lists", nested_lists(2_000)),
+        ("nested quotes", nested_quotes(2_000)),
+    ] {
+        count(name, Config::default(), &input);
+        // Owned, as when read from a configuration file.
+        let mut config = Config::default();
+        config.set("fixed_indentation", "    ").unwrap();
+        count(&format!("{name}, fixed indentation"), config, &input);
+    }
+}
@@ -0,0 +1,72 @@
+//! Formatting throughput on large generated documents,
+//! to check that the time grows linearly with the input size.
+#![allow(missing_docs)] // `criterion_group!` generates an undocumented function.
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+
+/// A document of `sections` sections mixing headings, wrapped paragraphs,
+/// nested lists, block quotes, code blocks, and reference links.
+fn document(sections: usize) -> String {
+    let mut document = String::new();
+    for section in 0..sections {
+        _ = write!(
+            document,
+            "#  Section {section}\n\n\
+             Some *emphasized*   text with a [link][ref{section}] and `code`,\n\
+             wrapped   over   a few lines   so that   paragraphs get reflowed.\n\n\
+             *  Item one\n   *  Nested item with **strong** text\n*  Item two\n\n\
+             >  Quoted   text\n>\n>  More quoted text\n\n\
+             ```rust\nfn main() {{}}\n```\n\n\
+             [ref{section}]: https://example.com/{section}\n\n"
+        );
+    }
+    document
+}
+
+/// A document of `sections` lists nested in block quotes nested in lists,
+/// so that most lines are written after a deep indentation prefix.
+fn nested_document(sections: usize) -> String {
+    let mut document = String::new();
+    for section in 0..sections {
+        _ = write!(
+            document,
+            "*  Section {section}\n\n\
+             \x20  >  Quoted   list:\n\
+             \x20  >\n\
+             \x20  >  1. Item with   text\n\
+             \x20  >     *  Nested\n\
+             \x20  >        >  Deeply   quoted\n\
+             \x20  >        >  text\n\
+             \x20  >\n\
+             \x20  >            indented code\n\n"
+        );
+    }
+    document
+}
+
+fn format(c: &mut Criterion) {
+    let mut group = c.benchmark_group("format");
+    group.sample_size(10);
+    for (name, document) in [
+        ("mixed", document as fn(usize) -> String),
+        ("nested", nested_document),
+    ] {
+        for sections in [1_000, 4_000, 16_000] {
+            let input = document(sections);
+            group.throughput(Throughput::Bytes(input.len() as u64));
+            group.bench_with_input(BenchmarkId::new(name, input.len()), &input, |b, input| {
+                b.iter(|| {
+                    <MarkdownFormatter>::with_config(Config::sichanghe_opinion())
+                        .format(input)
+                        .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, format);
+criterion_main!(benches);
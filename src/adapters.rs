@@ -3,4 +3,7 @@ use super::*;
 mod loose_list;
 mod sequential_blocks;
 
-pub(crate) use {loose_list::LooseListExt, sequential_blocks::SequentialBlockExt};
+pub(crate) use {
+    loose_list::{LooseListAdapter, LooseListExt},
+    sequential_blocks::{SequentialBlockAdapter, SequentialBlockExt},
+};
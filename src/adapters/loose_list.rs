@@ -435,7 +435,7 @@ mod test {
                     output.push(format!("event={event:?} range={range:?}").into())
                 }
                 output.push("".into());
-                std::fs::write(file, &output.join("\n")).unwrap();
+                std::fs::write(file, output.join("\n")).unwrap();
             } else {
                 for ((event, range), line) in events.zip(expected_events.trim().lines()) {
                     assert_eq!(format!("event={event:?} range={range:?}"), line);
@@ -449,14 +449,15 @@ mod test {
             let adapted_events = pulldown_cmark::Parser::new_ext(markdown, options)
                 .into_offset_iter()
                 .all_loose_lists();
+            let config = Config::default();
             let fmt_state = <FormatState<DefaultFormatterCombination, _>>::new(
                 markdown,
-                Config::default(),
+                &config,
                 adapted_events,
                 vec![],
             );
 
-            let output = fmt_state.format().unwrap();
+            let (output, _) = fmt_state.format().unwrap();
 
             // Get the events after formatting
             let pos_events = pulldown_cmark::Parser::new_ext(&output, options).collect::<Vec<_>>();
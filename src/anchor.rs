@@ -0,0 +1,329 @@
+//! Heading anchors, i.e., the fragment identifiers renderers generate for headings.
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+
+/// Generate the anchor GitHub uses for a heading with the given text:
+/// lowercase, drop punctuation, and turn spaces into `-`.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::anchor::github_slug;
+/// assert_eq!(github_slug("Getting Started!"), "getting-started");
+/// assert_eq!(github_slug("`Config::max_width`"), "configmax_width");
+/// ```
+pub fn github_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generate the anchor GitLab uses for a heading with the given text:
+/// like [`github_slug`], but runs of `-` are squeezed into one.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::anchor::gitlab_slug;
+/// assert_eq!(gitlab_slug("Getting -- Started!"), "getting-started");
+/// ```
+pub fn gitlab_slug(text: &str) -> String {
+    let mut slug = github_slug(text);
+    while let Some(index) = slug.find("--") {
+        slug.remove(index);
+    }
+    slug
+}
+
+/// Generate the anchor Pandoc's `auto_identifiers` extension uses for a heading
+/// with the given text: drop punctuation except `_`, `-`, and `.`,
+/// turn spaces into `-`, lowercase, and drop everything before the first letter,
+/// or use `section` if nothing is left.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::anchor::pandoc_slug;
+/// assert_eq!(pandoc_slug("1.2 Getting Started!"), "getting-started");
+/// assert_eq!(pandoc_slug("v1.0 notes"), "v1.0-notes");
+/// assert_eq!(pandoc_slug("2024"), "section");
+/// ```
+pub fn pandoc_slug(text: &str) -> String {
+    let slug = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            '-' | '_' | '.' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .skip_while(|c| !c.is_alphabetic())
+        .collect::<String>();
+    match slug.is_empty() {
+        true => "section".into(),
+        false => slug,
+    }
+}
+
+/// How a renderer generates anchors for headings, e.g., to check `#fragment`
+/// links against the anchors of the platform the document is published on.
+#[derive(Clone, Copy, Debug)]
+pub enum SlugStyle {
+    /// GitHub's, see [`github_slug`].
+    GitHub,
+    /// GitLab's, see [`gitlab_slug`].
+    GitLab,
+    /// Pandoc's, see [`pandoc_slug`].
+    Pandoc,
+    /// Generate anchors with the given function of the heading text.
+    Custom(fn(&str) -> String),
+}
+
+impl SlugStyle {
+    /// Generate the anchor for a heading with the given text,
+    /// before deduplication.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::anchor::SlugStyle;
+    /// assert_eq!(SlugStyle::Pandoc.slug("1 Intro"), "intro");
+    /// assert_eq!(SlugStyle::Custom(|text| text.replace(' ', "_")).slug("A B"), "A_B");
+    /// ```
+    pub fn slug(self, text: &str) -> String {
+        match self {
+            Self::GitHub => github_slug(text),
+            Self::GitLab => gitlab_slug(text),
+            Self::Pandoc => pandoc_slug(text),
+            Self::Custom(slug) => slug(text),
+        }
+    }
+}
+
+/// A heading and the anchor generated for it.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct HeadingAnchor {
+    /// Source range of the heading.
+    pub range: Range<usize>,
+    pub anchor: String,
+}
+
+/// Find the anchors of all headings in the input, in order.
+/// Explicit `{#id}`s are used as is,
+/// and duplicated anchors get a `-1`, `-2`, … suffix.
+pub(crate) fn heading_anchors(input: &str, slug_style: SlugStyle) -> Vec<HeadingAnchor> {
+    let mut anchors = vec![];
+    let mut seen = HashMap::new();
+    let mut heading = None;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { id, .. }) => heading = Some((range, id, String::new())),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, heading_text)) = heading.as_mut() {
+                    heading_text.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((range, id, text)) = heading.take() else {
+                    continue;
+                };
+                let anchor = match id {
+                    Some(id) => id.to_string(),
+                    None => {
+                        let slug = slug_style.slug(&text);
+                        let count = seen.entry(slug.clone()).or_insert(0);
+                        *count += 1;
+                        match *count {
+                            1 => slug,
+                            n => format!("{slug}-{}", n - 1),
+                        }
+                    }
+                };
+                anchors.push(HeadingAnchor { range, anchor });
+            }
+            _ => {}
+        }
+    }
+    anchors
+}
+
+/// Find the `#fragment` destinations of intra-document links,
+/// including those in reference definitions,
+/// returning the source range of each `#fragment` and the fragment.
+pub(crate) fn fragment_links(input: &str) -> Vec<(Range<usize>, String)> {
+    links::link_destinations(input)
+        .into_iter()
+        .filter_map(|(range, dest)| Some((range, dest.strip_prefix('#')?.to_owned())))
+        .collect()
+}
+
+/// Find all anchors links in the input can point to:
+/// heading anchors, and `id` and `name` attributes in HTML.
+pub(crate) fn document_anchors(input: &str, slug_style: SlugStyle) -> HashSet<String> {
+    let mut anchors = heading_anchors(input, slug_style)
+        .into_iter()
+        .map(|heading| heading.anchor)
+        .collect::<HashSet<_>>();
+    for event in Parser::new_ext(input, parser_options()) {
+        if let Event::Html(html) | Event::InlineHtml(html) = event {
+            anchors.extend(html_anchor_ids(&html).map(str::to_owned));
+        }
+    }
+    anchors
+}
+
+/// Find the values of `id` and `name` attributes in an HTML snippet.
+fn html_anchor_ids(html: &str) -> impl Iterator<Item = &str> {
+    html.match_indices(['i', 'n']).filter_map(|(index, _)| {
+        if !html[..index].ends_with(char::is_whitespace) {
+            return None;
+        }
+        let rest = &html[index..];
+        let rest = rest
+            .strip_prefix("id")
+            .or_else(|| rest.strip_prefix("name"))?
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start();
+        let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &rest[1..];
+        Some(&value[..value.find(quote)?])
+    })
+}
+
+/// Compare the heading anchors before and after formatting,
+/// and either rewrite links in `after` that point to changed anchors,
+/// or report those links in `before` as diagnostics.
+pub(crate) fn fix_changed_anchors(
+    before: &str,
+    after: String,
+    rewrite: bool,
+    slug_style: SlugStyle,
+) -> (String, Vec<Diagnostic>) {
+    let old_anchors = heading_anchors(before, slug_style);
+    let new_anchors = heading_anchors(&after, slug_style);
+    if old_anchors.len() != new_anchors.len() {
+        // Headings were added or removed, so we cannot tell which one is which.
+        return (after, vec![]);
+    }
+    let changed = old_anchors
+        .iter()
+        .zip(&new_anchors)
+        .filter(|(old, new)| old.anchor != new.anchor)
+        // Links to anchors that still exist remain valid.
+        .filter(|(old, _)| !new_anchors.iter().any(|new| new.anchor == old.anchor))
+        .map(|(old, new)| (old.anchor.as_str(), new.anchor.as_str()))
+        .collect::<HashMap<_, _>>();
+    if changed.is_empty() {
+        return (after, vec![]);
+    }
+
+    if rewrite {
+        let edits = fragment_links(&after)
+            .into_iter()
+            .filter_map(|(range, fragment)| {
+                let new = changed.get(fragment.as_str())?;
+                Some((range, format!("#{new}")))
+            })
+            .collect();
+        (apply_edits(&after, edits), vec![])
+    } else {
+        let diagnostics = fragment_links(before)
+            .into_iter()
+            .filter_map(|(range, fragment)| {
+                let (old, new) = changed.get_key_value(fragment.as_str())?;
+                Some(Diagnostic {
+                    range,
+                    kind: DiagnosticKind::ChangedAnchor {
+                        old: old.to_string(),
+                        new: new.to_string(),
+                    },
+                })
+            })
+            .collect();
+        (after, diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn duplicated_and_explicit_anchors() {
+        let anchors = heading_anchors("# Foo\n## Foo\n# Foo\n# Bar {#baz}\n", SlugStyle::GitHub)
+            .into_iter()
+            .map(|h| h.anchor)
+            .collect::<Vec<_>>();
+        assert_eq!(anchors, ["foo", "foo-1", "foo-2", "baz"]);
+    }
+
+    #[test]
+    fn anchors_by_slug_style() {
+        let anchors = |slug_style| {
+            heading_anchors("# 1 Intro\n# Intro -- Setup\n# Intro\n", slug_style)
+                .into_iter()
+                .map(|h| h.anchor)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            anchors(SlugStyle::GitHub),
+            ["1-intro", "intro----setup", "intro"]
+        );
+        assert_eq!(
+            anchors(SlugStyle::GitLab),
+            ["1-intro", "intro-setup", "intro"]
+        );
+        assert_eq!(
+            anchors(SlugStyle::Pandoc),
+            ["intro", "intro----setup", "intro-1"]
+        );
+    }
+
+    #[test]
+    fn html_anchors() {
+        let anchors = document_anchors(
+            "<a name=\"install\"></a>\n# Usage\n\nText <span id='inline'>here</span>.\n",
+            SlugStyle::GitHub,
+        );
+        let mut anchors = anchors.into_iter().collect::<Vec<_>>();
+        anchors.sort();
+        assert_eq!(anchors, ["inline", "install", "usage"]);
+    }
+
+    #[test]
+    fn rewrite_changed_anchors() {
+        let before = "# Intro\n\nSee [intro](#intro) and [ref].\n\n[ref]: #intro\n";
+        let after = "# 1 Intro\n\nSee [intro](#intro) and [ref].\n\n[ref]: #intro\n";
+        let (rewritten, diagnostics) =
+            fix_changed_anchors(before, after.into(), true, SlugStyle::GitHub);
+        assert_eq!(
+            rewritten,
+            "# 1 Intro\n\nSee [intro](#1-intro) and [ref].\n\n[ref]: #1-intro\n"
+        );
+        assert!(diagnostics.is_empty());
+
+        let (unchanged, diagnostics) =
+            fix_changed_anchors(before, after.into(), false, SlugStyle::GitHub);
+        assert_eq!(unchanged, after);
+        let kind = DiagnosticKind::ChangedAnchor {
+            old: "intro".into(),
+            new: "1-intro".into(),
+        };
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic {
+                    range: 21..27,
+                    kind: kind.clone()
+                },
+                Diagnostic {
+                    range: 48..54,
+                    kind
+                },
+            ]
+        );
+    }
+}
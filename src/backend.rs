@@ -0,0 +1,116 @@
+//! Parsers producing the events formatting works on.
+use pulldown_cmark::{BrokenLink, OffsetIter};
+
+use super::*;
+use crate::adapters::{LooseListAdapter, SequentialBlockAdapter};
+
+/// Label, destination, title with its quote, and source range of a
+/// link reference definition.
+pub(crate) type ReferenceLinkDefinition = (String, String, Option<(String, char)>, Range<usize>);
+
+/// Source of the events formatting works on,
+/// so that parsers other than pulldown-cmark can be plugged in,
+/// e.g., selected by a feature flag.
+pub(crate) trait ParserBackend {
+    /// Events with their source ranges.
+    type Events<'i>: Iterator<Item = (Event<'i>, Range<usize>)>;
+
+    /// Parse `input` with `options` into events in source order,
+    /// with loose lists marked and sequential blocks ending before the next starts,
+    /// and its link reference definitions in descending order of position.
+    fn parse(input: &str, options: Options) -> (Self::Events<'_>, Vec<ReferenceLinkDefinition>);
+}
+
+/// The parser used to format.
+pub(crate) type Backend = PulldownCmark;
+
+/// [`ParserBackend`] using pulldown-cmark,
+/// with adapters working around its out-of-order events.
+pub(crate) struct PulldownCmark;
+
+type RecoverBrokenLink<'i> = fn(BrokenLink<'i>) -> Option<(CowStr<'i>, CowStr<'i>)>;
+
+/// Callback that will always recover broken links.
+fn recover_broken_link<'i>(broken_link: BrokenLink<'i>) -> Option<(CowStr<'i>, CowStr<'i>)> {
+    tracing::trace!("found boken link: {broken_link:?}");
+    Some(("".into(), "".into()))
+}
+
+impl ParserBackend for PulldownCmark {
+    type Events<'i> =
+        SequentialBlockAdapter<'i, LooseListAdapter<'i, OffsetIter<'i, RecoverBrokenLink<'i>>>>;
+
+    fn parse(input: &str, options: Options) -> (Self::Events<'_>, Vec<ReferenceLinkDefinition>) {
+        let parser =
+            Parser::new_with_broken_link_callback(input, options, Some(recover_broken_link as _));
+
+        // There can't be any characters besides spaces, tabs, or newlines after the title
+        // See https://spec.commonmark.org/0.30/#link-reference-definition for the
+        // definition and https://spec.commonmark.org/0.30/#example-209 as an example.
+        //
+        // It seems that `pulldown_cmark` sometimes parses titles when it shouldn't.
+        // To work around edge cases where a paragraph starting with a quoted string might be
+        // interpreted as a link title we check that only whitespace follows the title
+        let is_false_title = |input: &str, span: Range<usize>| {
+            input[span.end..]
+                .chars()
+                .take_while(|c| *c != '\n')
+                .any(|c| !c.is_whitespace())
+        };
+
+        let reference_links = parser
+            .reference_definitions()
+            .iter()
+            .sorted_by(|(_, link_a), (_, link_b)| {
+                // We want to sort these in descending order based on the ranges
+                // This creates a stack of reference links that we can pop off of.
+                link_b.span.start.cmp(&link_a.span.start)
+            })
+            // TODO: Fix typo.
+            .map(|(link_lable, link_def)| {
+                let (dest, title, span) = (&link_def.dest, &link_def.title, &link_def.span);
+                let full_link = &input[span.clone()];
+                if title.is_some() && is_false_title(input, span.clone()) {
+                    let end = input[span.clone()]
+                        .find(dest.as_ref())
+                        .map(|idx| idx + dest.len())
+                        .unwrap_or(span.end);
+                    return (
+                        link_lable.to_string(),
+                        dest.to_string(),
+                        None,
+                        span.start..end,
+                    );
+                }
+
+                if let Some((url, title)) = links::recover_escaped_link_destination_and_title(
+                    full_link,
+                    link_lable,
+                    title.is_some(),
+                ) {
+                    // Multi-line titles include the block quote markers of continuation lines.
+                    let line_start = input[..span.start].rfind('\n').map_or(0, |i| i + 1);
+                    let quote_depth = input[line_start..span.start].matches('>').count();
+                    let title = title.map(|(title, quote)| {
+                        (links::strip_title_line_prefixes(&title, quote_depth), quote)
+                    });
+                    (link_lable.to_string(), url, title, span.clone())
+                } else {
+                    // Couldn't recover URL from source, just use what we've been given
+                    (
+                        link_lable.to_string(),
+                        dest.to_string(),
+                        title.clone().map(|s| (s.to_string(), '"')),
+                        span.clone(),
+                    )
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let events = parser
+            .into_offset_iter()
+            .all_loose_lists()
+            .all_sequential_blocks();
+        (events, reference_links)
+    }
+}
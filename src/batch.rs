@@ -0,0 +1,608 @@
+//! Format all Markdown files in a directory tree, e.g., for command line tools and CI.
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use super::*;
+
+/// File extensions of the Markdown files batch formatting picks up.
+pub const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Outcome of batch formatting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Per-file results, sorted by path.
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    /// Files that formatting changed, or would change when checking.
+    pub fn changed(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| file.status == FileStatus::Changed)
+    }
+
+    /// Files that could not be read, formatted, or written.
+    pub fn errors(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Error(_)))
+    }
+
+    /// Aggregate statistics, e.g., to measure churn before adopting the formatter.
+    pub fn statistics(&self) -> Statistics {
+        let mut statistics = Statistics {
+            files: self.files.len(),
+            ..Statistics::default()
+        };
+        for file in &self.files {
+            match file.status {
+                FileStatus::Unchanged => {}
+                FileStatus::Changed => statistics.files_changed += 1,
+                FileStatus::Error(_) => statistics.files_with_errors += 1,
+            }
+            statistics.lines_added += file.lines_added;
+            statistics.lines_removed += file.lines_removed;
+            for option in &file.rules_hit {
+                *statistics.rule_hits.entry(option).or_default() += 1;
+            }
+        }
+        statistics
+    }
+
+    /// The report as JSON for CI dashboards,
+    /// with the [`Report::statistics`] under `"statistics"`
+    /// and the changed and failed files under `"files"`.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::batch::*;
+    /// let report = Report {
+    ///     files: vec![FileReport {
+    ///         path: "README.md".into(),
+    ///         status: FileStatus::Changed,
+    ///         lines_added: 1,
+    ///         lines_removed: 1,
+    ///         rules_hit: vec!["max_width"],
+    ///     }],
+    /// };
+    /// assert_eq!(
+    ///     report.to_json(),
+    ///     r#"{"statistics":{"files":1,"files_changed":1,"files_with_errors":0,"lines_added":1,"lines_removed":1,"rule_hits":{"max_width":1}},"files":[{"path":"README.md","status":"changed","lines_added":1,"lines_removed":1,"rules_hit":["max_width"]}]}"#,
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let Statistics {
+            files,
+            files_changed,
+            files_with_errors,
+            lines_added,
+            lines_removed,
+            rule_hits,
+        } = self.statistics();
+        let mut json = format!(
+            r#"{{"statistics":{{"files":{files},"files_changed":{files_changed},"files_with_errors":{files_with_errors},"lines_added":{lines_added},"lines_removed":{lines_removed},"rule_hits":{{"#
+        );
+        for (index, (rule, hits)) in rule_hits.into_iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            push_json_string(&mut json, rule);
+            _ = write!(json, ":{hits}");
+        }
+        json.push_str(r#"}},"files":["#);
+        let files = self
+            .files
+            .iter()
+            .filter(|file| file.status != FileStatus::Unchanged);
+        for (index, file) in files.enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            json.push_str(r#"{"path":"#);
+            push_json_string(&mut json, &file.path.to_string_lossy());
+            match &file.status {
+                FileStatus::Unchanged => unreachable!(),
+                FileStatus::Changed => json.push_str(r#","status":"changed""#),
+                FileStatus::Error(message) => {
+                    json.push_str(r#","status":"error","error":"#);
+                    push_json_string(&mut json, message);
+                }
+            }
+            _ = write!(
+                json,
+                r#","lines_added":{},"lines_removed":{},"rules_hit":["#,
+                file.lines_added, file.lines_removed
+            );
+            for (index, rule) in file.rules_hit.iter().enumerate() {
+                if index > 0 {
+                    json.push(',');
+                }
+                push_json_string(&mut json, rule);
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Aggregate statistics of a [`Report`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Number of files processed.
+    pub files: usize,
+    /// Number of files formatting changed, or would change when checking.
+    pub files_changed: usize,
+    /// Number of files that could not be read, formatted, or written.
+    pub files_with_errors: usize,
+    /// Number of lines formatting added, counting changed lines as added and removed.
+    pub lines_added: usize,
+    /// Number of lines formatting removed.
+    pub lines_removed: usize,
+    /// Number of files each rule changed, see [`FileReport::rules_hit`].
+    pub rule_hits: BTreeMap<&'static str, usize>,
+}
+
+/// Result of batch formatting one file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReport {
+    /// Path of the file, starting with the root given to the batch function.
+    pub path: PathBuf,
+    /// What happened to the file.
+    pub status: FileStatus,
+    /// Number of lines formatting added to the file.
+    pub lines_added: usize,
+    /// Number of lines formatting removed from the file.
+    pub lines_removed: usize,
+    /// The rules that changed the file, sorted, when checking with [`check_tree`]:
+    /// the [`Config`] options set, i.e., not `None`, `false`, or empty,
+    /// that change the output when unset.
+    /// Each is found by formatting the file again without it.
+    pub rules_hit: Vec<&'static str>,
+}
+
+impl FileReport {
+    fn error(path: &Path, error: &dyn std::fmt::Display) -> Self {
+        Self {
+            path: path.into(),
+            status: FileStatus::Error(error.to_string()),
+            lines_added: 0,
+            lines_removed: 0,
+            rules_hit: vec![],
+        }
+    }
+}
+
+/// What happened to a file in batch formatting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file was already formatted.
+    Unchanged,
+    /// Formatting changed the file.
+    Changed,
+    /// The file could not be read, formatted, or written.
+    Error(String),
+}
+
+/// Format the Markdown files under `root` in place, in parallel,
+/// with `config` for every file.
+///
+/// Files with one of the [`MARKDOWN_EXTENSIONS`] are picked up,
+/// except in hidden directories and those [`ignore::is_ignored`].
+/// Errors are reported per file instead of stopping the batch.
+pub fn format_tree(root: impl AsRef<Path>, config: &Config) -> Report {
+    process_tree(root.as_ref(), config, true)
+}
+
+/// Like [`format_tree`], but report which files would change without writing them,
+/// e.g., to fail CI when files are not formatted,
+/// or for a dry run to collect [`Report::statistics`].
+/// Changed files are formatted once more for each option set in `config`
+/// to find the [`FileReport::rules_hit`].
+pub fn check_tree(root: impl AsRef<Path>, config: &Config) -> Report {
+    process_tree(root.as_ref(), config, false)
+}
+
+fn process_tree(root: &Path, config: &Config, write: bool) -> Report {
+    let mut files = vec![];
+    let mut paths = vec![];
+    // The ignore files of the ancestors apply to the whole tree.
+    let ancestors = root.ancestors().skip(1).collect::<Vec<_>>();
+    match ancestors.into_iter().rev().map(ignore::dir_globs).collect() {
+        Ok(mut ignores) => collect_markdown_files(root, &mut ignores, &mut paths, &mut files),
+        Err(error) => files.push(FileReport::error(root, &error)),
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let reports = Mutex::new(files);
+    let n_workers = std::thread::available_parallelism().map_or(1, usize::from);
+    std::thread::scope(|scope| {
+        for _ in 0..n_workers.min(paths.len()) {
+            scope.spawn(|| {
+                while let Some(path) = paths.get(next_index.fetch_add(1, Ordering::Relaxed)) {
+                    let report = process_file(path, config, write);
+                    reports.lock().unwrap().push(report);
+                }
+            });
+        }
+    });
+
+    let mut files = reports.into_inner().unwrap();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Report { files }
+}
+
+/// Push the Markdown files under `dir` not ignored by `ignores`
+/// or the ignore files under `dir` to `paths`,
+/// and directories and entries that cannot be read to `errors`.
+/// `ignores` holds the globs of the ancestors of `dir`,
+/// so each directory's files are read once.
+fn collect_markdown_files(
+    dir: &Path,
+    ignores: &mut Vec<ignore::DirGlobs>,
+    paths: &mut Vec<PathBuf>,
+    errors: &mut Vec<FileReport>,
+) {
+    if ignore::is_ignored_by(ignores, dir) {
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => return errors.push(FileReport::error(dir, &error)),
+    };
+    match ignore::dir_globs(dir) {
+        Ok(globs) => ignores.push(globs),
+        Err(error) => return errors.push(FileReport::error(dir, &error)),
+    }
+    let mut entries = entries
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry.path()),
+            Err(error) => {
+                errors.push(FileReport::error(dir, &error));
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+    for path in entries {
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_dir() {
+            if !is_hidden {
+                collect_markdown_files(&path, ignores, paths, errors);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| MARKDOWN_EXTENSIONS.iter().any(|e| extension == *e))
+            && !ignore::is_ignored_by(ignores, &path)
+        {
+            paths.push(path);
+        }
+    }
+    ignores.pop();
+}
+
+fn process_file(path: &Path, config: &Config, write: bool) -> FileReport {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(error) => return FileReport::error(path, &error),
+    };
+    let formatter = <MarkdownFormatter>::with_config(config.clone());
+    // Keep going with the other files if formatting this one panics.
+    let output = match std::panic::catch_unwind(|| formatter.format(&input)) {
+        Ok(Ok(output)) => output,
+        Ok(Err(_)) => return FileReport::error(path, &"formatting failed"),
+        Err(_) => return FileReport::error(path, &"formatting panicked"),
+    };
+    let (lines_added, lines_removed) = count_changed_lines(&input, &output);
+    let mut rules_hit = vec![];
+    let status = match output == input {
+        true => FileStatus::Unchanged,
+        false if write => {
+            if let Err(error) = fs::write(path, output) {
+                return FileReport::error(path, &error);
+            }
+            FileStatus::Changed
+        }
+        false => {
+            rules_hit = rules_hit_by(&input, &output, config);
+            FileStatus::Changed
+        }
+    };
+    FileReport {
+        path: path.into(),
+        status,
+        lines_added,
+        lines_removed,
+        rules_hit,
+    }
+}
+
+/// Options set in `config` whose absence changes `output`, formatted from `input`.
+fn rules_hit_by(input: &str, output: &str, config: &Config) -> Vec<&'static str> {
+    let mut rules = without_each_option(config)
+        .into_iter()
+        .filter(|(_, without)| {
+            let formatter = <MarkdownFormatter>::with_config(without.clone());
+            // A rule whose absence makes formatting fail or panic is not counted.
+            std::panic::catch_unwind(|| formatter.format(input))
+                .is_ok_and(|result| result.is_ok_and(|rewrite| rewrite != output))
+        })
+        .map(|(option, _)| option)
+        .collect::<Vec<_>>();
+    rules.sort_unstable();
+    rules
+}
+
+/// Whether an option is set, i.e., not its [`Default`] value.
+trait IsSet {
+    fn is_set(&self) -> bool;
+}
+
+impl<T> IsSet for Option<T> {
+    fn is_set(&self) -> bool {
+        self.is_some()
+    }
+}
+
+impl IsSet for bool {
+    fn is_set(&self) -> bool {
+        *self
+    }
+}
+
+impl<T> IsSet for Vec<T> {
+    fn is_set(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+macro_rules! without_each_option {
+    ($($option:ident),* $(,)?) => {
+        /// The name of each option set in `config`, with `config` without it.
+        fn without_each_option(config: &Config) -> Vec<(&'static str, Config)> {
+            // Fail to compile when an option is missing from the list.
+            let Config { $($option: _),* } = config;
+            let mut configs = vec![];
+            $(
+                if config.$option.is_set() {
+                    let mut without = config.clone();
+                    without.$option = Default::default();
+                    configs.push((stringify!($option), without));
+                }
+            )*
+            configs
+        }
+    };
+}
+
+without_each_option!(
+    max_width,
+    paragraph_max_width,
+    code_block_max_width,
+    fixed_zero_padding,
+    fixed_number,
+    fixed_ordered_list_marker,
+    fixed_unordered_list_marker,
+    unordered_list_markers_by_depth,
+    drop_empty_list_items,
+    block_quote_separation,
+    fixed_indentation,
+    fixed_emphasis_marker,
+    fixed_strong_marker,
+    preserve_badge_lines,
+    single_line_badge_rows,
+    blank_lines_around_details,
+    format_details_content,
+    fix_anchor_links,
+    number_headings_from,
+    infer_code_block_language,
+    normalize_code_block_info,
+    convert_html_blocks,
+    flavor,
+    preserve_source,
+    preserve_line_numbers,
+    minimal_diff_tolerance,
+    compact_tables,
+    hard_breaks_in_links_as_spaces,
+    unwrap_paragraphs,
+    collapse_spaces,
+    protected_lines,
+    shortcodes,
+    reference_definitions_placement,
+    reference_definitions_order,
+    blank_lines_between_reference_definitions,
+    single_line_link_titles,
+    explicit_autolinks,
+    list_code_block_separator,
+    fence_code_blocks_after_lists,
+    code_block_buffer_limit,
+    max_wrapped_line_length,
+    own_line_link_width,
+    transforms,
+    continuation_alignment,
+    zero_pad_to_widest_number,
+    html_entities,
+    slug_style,
+    preserve_html_block_whitespace,
+    ascii_only,
+    preserve_suggestion_blocks,
+    tab_width,
+    table_hook,
+    setext_underline,
+    preserve_line_blocks,
+    own_line_comments,
+    bullet_for_task_lists,
+    ordered_list_numbering,
+    heading_style,
+    code_fence_style,
+    code_fence_min_length,
+    align_reference_definitions,
+    nested_list_spacing,
+);
+
+/// Number of lines added and removed from `old` to `new`,
+/// from their longest common subsequence of lines after the common prefix and suffix.
+/// Lines in between are all counted as changed if there are too many to compare.
+fn count_changed_lines(old: &str, new: &str) -> (usize, usize) {
+    const MAX_COMPARISONS: usize = 1 << 24;
+    let (old, new) = (
+        old.lines().collect::<Vec<_>>(),
+        new.lines().collect::<Vec<_>>(),
+    );
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.len().saturating_mul(new.len()) > MAX_COMPARISONS {
+        return (new.len(), old.len());
+    }
+
+    // Row of the dynamic programming table of common subsequence lengths.
+    let mut lengths = vec![0; new.len() + 1];
+    for old_line in old {
+        let mut diagonal = 0;
+        for (index, new_line) in new.iter().enumerate() {
+            let above = lengths[index + 1];
+            lengths[index + 1] = match old_line == new_line {
+                true => diagonal + 1,
+                false => above.max(lengths[index]),
+            };
+            diagonal = above;
+        }
+    }
+    let common = lengths[new.len()];
+    (new.len() - common, old.len() - common)
+}
+
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for char in value.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => _ = write!(json, "\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_trees() {
+        let root = std::env::temp_dir().join(format!("markdown-fmt-batch-{}", std::process::id()));
+        let docs = root.join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::write(root.join("README.md"), "#  Title\n").unwrap();
+        fs::write(docs.join("ok.markdown"), "# Fine\n").unwrap();
+        fs::write(docs.join("notes.txt"), "#  Not Markdown\n").unwrap();
+        fs::write(root.join(".hidden/skip.md"), "#  Hidden\n").unwrap();
+        fs::write(
+            root.join(ignore::IGNORE_FILE_NAME),
+            "CHANGELOG.md\ndocs/old.md\n",
+        )
+        .unwrap();
+        fs::write(root.join("CHANGELOG.md"), "#  Generated\n").unwrap();
+        fs::write(docs.join(ignore::IGNORE_FILE_NAME), "skip.md\n").unwrap();
+        fs::write(docs.join("skip.md"), "#  Skipped\n").unwrap();
+        fs::write(docs.join("old.md"), "#  Old\n").unwrap();
+
+        let expected = Report {
+            files: vec![
+                FileReport {
+                    path: root.join("README.md"),
+                    status: FileStatus::Changed,
+                    lines_added: 1,
+                    lines_removed: 1,
+                    rules_hit: vec![],
+                },
+                FileReport {
+                    path: docs.join("ok.markdown"),
+                    status: FileStatus::Unchanged,
+                    lines_added: 0,
+                    lines_removed: 0,
+                    rules_hit: vec![],
+                },
+            ],
+        };
+        let config = Config::default();
+        assert_eq!(check_tree(&root, &config), expected);
+        // The ignore files above the tree apply too.
+        assert_eq!(check_tree(&docs, &config).files, expected.files[1..]);
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "#  Title\n"
+        );
+        assert_eq!(format_tree(&root, &config), expected);
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "# Title\n"
+        );
+        assert_eq!(check_tree(&root, &config).changed().count(), 0);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn counts_rule_hits() {
+        let root = std::env::temp_dir().join(format!("markdown-fmt-rules-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("list.md"), "* item\n").unwrap();
+        fs::write(root.join("ok.md"), "- item\n").unwrap();
+
+        let config = Config {
+            max_width: Some(80),
+            fixed_unordered_list_marker: Some(crate::UnorderedListMarker::Hyphen),
+            ..Config::default()
+        };
+        let report = check_tree(&root, &config);
+        let rules_hit = report.files.iter().map(|file| &file.rules_hit[..]);
+        assert!(rules_hit.eq([&["fixed_unordered_list_marker"][..], &[]]));
+        assert_eq!(
+            report.statistics().rule_hits,
+            BTreeMap::from([("fixed_unordered_list_marker", 1)])
+        );
+        // Writing does not pay for finding the rules.
+        assert!(format_tree(&root, &config).files[0].rules_hit.is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn counts_changed_lines() {
+        assert_eq!(count_changed_lines("a\nb\nc\n", "a\nb\nc\n"), (0, 0));
+        assert_eq!(
+            count_changed_lines("a\nb\nc\nd\n", "a\nx\nc\nd\ne\n"),
+            (2, 1)
+        );
+        assert_eq!(count_changed_lines("a\nb b\n", "a\nb\nb\n"), (2, 1));
+    }
+
+    #[test]
+    fn escapes_json() {
+        let mut json = String::new();
+        push_json_string(&mut json, "a\"b\\c\nd\u{1}");
+        assert_eq!(json, r#""a\"b\\c\nd\u0001""#);
+    }
+}
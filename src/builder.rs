@@ -6,8 +6,29 @@ use super::*;
 /// HTML block, and paragraph formatting;
 /// default to [`DefaultFormatterCombination`],
 /// and partial customization can easily be done using [`FormatterCombination`].
-#[derive(Clone)]
-pub struct MarkdownFormatter<E>
+///
+/// [`MarkdownFormatter`] and [`Config`] are [`Send`] + [`Sync`]
+/// regardless of `E`, because formatters are only created while formatting.
+/// To format concurrently, e.g., in a server, share one [`Config`]
+/// and create a cheap formatter per input, or clone a formatter:
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+/// let config = std::sync::Arc::new(Config::sichanghe_opinion());
+/// let handles: Vec<_> = ["#  One", "#  Two"]
+///     .into_iter()
+///     .map(|input| {
+///         let config = config.clone();
+///         std::thread::spawn(move || {
+///             <MarkdownFormatter>::with_config((*config).clone()).format(input)
+///         })
+///     })
+///     .collect();
+/// for (handle, expected) in handles.into_iter().zip(["# One", "# Two"]) {
+///     assert_eq!(handle.join().unwrap().unwrap(), expected);
+/// }
+/// ```
+pub struct MarkdownFormatter<E = DefaultFormatterCombination>
 where
     E: ExternalFormatter,
 {
@@ -15,48 +36,83 @@ where
     pub(crate) config: Config,
 }
 
-impl MarkdownFormatter<DefaultFormatterCombination> {
-    /// Create a [`MarkdownFormatter`] with custom [`Config`] and
-    /// default [`ExternalFormatter`].
+impl<E> MarkdownFormatter<E>
+where
+    E: ExternalFormatter,
+{
+    /// Create a [`MarkdownFormatter`] with custom [`Config`].
+    ///
+    /// The [`ExternalFormatter`] is picked by the type,
+    /// defaulting to [`DefaultFormatterCombination`]:
     ///
     /// ```rust
-    /// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
-    /// let formatter = MarkdownFormatter::with_config(Config {
+    /// # use fmtm_ytmimi_markdown_fmt::{
+    ///     Config, DefaultFormatterCombination, MarkdownFormatter,
+    /// };
+    /// let config = Config {
     ///     max_width: Some(80),
     ///     ..Default::default()
-    /// });
+    /// };
+    /// let formatter = <MarkdownFormatter>::with_config(config.clone());
+    /// let formatter =
+    ///     MarkdownFormatter::<DefaultFormatterCombination>::with_config(config);
     /// ```
     pub fn with_config(config: Config) -> Self {
         Self {
+            _external_formatter: Default::default(),
             config,
-            ..Default::default()
         }
     }
-}
 
-impl<E> MarkdownFormatter<E>
-where
-    E: ExternalFormatter,
-{
-    /// Create a [`MarkdownFormatter`] with custom [`Config`] and
-    /// custom [`ExternalFormatter`].
+    /// Create a [`MarkdownFormatter`] with custom [`Config`],
+    /// and report the options of it that [`Config::warnings`] finds ineffective.
     ///
     /// ```rust
-    /// # use fmtm_ytmimi_markdown_fmt::{
-    ///     Config, DefaultFormatterCombination, MarkdownFormatter,
+    /// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+    /// let config = Config {
+    ///     preserve_badge_lines: true,
+    ///     single_line_badge_rows: true,
+    ///     ..Default::default()
     /// };
-    /// let formatter = <MarkdownFormatter<DefaultFormatterCombination>>::with_config(
-    ///     Config {
-    ///         max_width: Some(80),
-    ///         ..Default::default()
-    ///     }
+    /// let (formatter, warnings) = <MarkdownFormatter>::with_checked_config(config);
+    /// assert_eq!(
+    ///     warnings[0].to_string(),
+    ///     "`preserve_badge_lines` has no effect because `single_line_badge_rows` is set",
     /// );
     /// ```
+    pub fn with_checked_config(config: Config) -> (Self, Vec<ConfigWarning>) {
+        let warnings = config.warnings();
+        (Self::with_config(config), warnings)
+    }
+
+    /// Create a [`MarkdownFormatter`] with custom [`Config`] and
+    /// custom [`ExternalFormatter`].
+    #[deprecated(since = "0.0.3", note = "use `MarkdownFormatter::with_config` instead")]
     pub fn with_config_and_external_formatter(config: Config) -> Self {
-        Self {
-            _external_formatter: Default::default(),
-            config,
-        }
+        Self::with_config(config)
+    }
+
+    /// The configuration used when formatting.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Mutable access to the configuration used when formatting.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let mut formatter = <MarkdownFormatter>::default();
+    /// formatter.config_mut().max_width = Some(80);
+    /// assert_eq!(formatter.config().max_width, Some(80));
+    /// ```
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Replace the configuration used when formatting.
+    pub fn set_config(&mut self, config: Config) -> &mut Self {
+        self.config = config;
+        self
     }
 
     /// Configure the max with when rewriting paragraphs.
@@ -72,6 +128,22 @@ where
         self.config = Config::sichanghe_opinion();
         self
     }
+
+    /// Set the configuration to the [`Config::gfm_readme`] preset.
+    pub fn gfm_readme_config(&mut self) -> &mut Self {
+        self.config = Config::gfm_readme();
+        self
+    }
+}
+
+// Not derived to not require `E: Clone`.
+impl<E> Clone for MarkdownFormatter<E>
+where
+    E: ExternalFormatter,
+{
+    fn clone(&self) -> Self {
+        Self::with_config(self.config.clone())
+    }
 }
 
 impl<E> std::fmt::Debug for MarkdownFormatter<E>
@@ -85,11 +157,24 @@ where
     }
 }
 
-impl Default for MarkdownFormatter<DefaultFormatterCombination> {
+impl<E> Default for MarkdownFormatter<E>
+where
+    E: ExternalFormatter,
+{
     fn default() -> Self {
-        MarkdownFormatter {
-            _external_formatter: Default::default(),
-            config: Config::default(),
-        }
+        Self::with_config(Config::default())
     }
 }
+
+// Formatting concurrently with a shared configuration relies on these.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Config>();
+    assert_send_sync::<MarkdownFormatter<DefaultFormatterCombination>>();
+    assert_send_sync::<DefaultFormatterCombination>();
+    assert_send_sync::<PreservingBuffer>();
+    assert_send_sync::<TrimTo4Indent>();
+    assert_send_sync::<Paragraph>();
+    #[cfg(feature = "yaml")]
+    assert_send_sync::<YamlFrontMatter>();
+};
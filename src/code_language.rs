@@ -0,0 +1,117 @@
+//! Guess the language of code blocks, for fences without an info string.
+
+/// Infer the language of `code` from telltale content,
+/// e.g., shebangs, `<?php`, or `fn main()`,
+/// for use as [`Config::infer_code_block_language`](crate::Config::infer_code_block_language).
+/// Return `None` if nothing gives the language away.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::code_language::infer;
+/// assert_eq!(infer("#!/usr/bin/env python3\nprint(1)\n").as_deref(), Some("python"));
+/// assert_eq!(infer("fn main() {\n    println!(\"hi\");\n}\n").as_deref(), Some("rust"));
+/// assert_eq!(infer("Hello, world!\n"), None);
+/// ```
+pub fn infer(code: &str) -> Option<String> {
+    let trimmed = code.trim_start();
+    if let Some(shebang) = trimmed.strip_prefix("#!") {
+        return language_of_interpreter(shebang.lines().next().unwrap_or_default());
+    }
+    let language = if trimmed.starts_with("<?php") {
+        "php"
+    } else if trimmed.starts_with("<?xml") {
+        "xml"
+    } else if starts_with_ignore_ascii_case(trimmed, "<!doctype html")
+        || starts_with_ignore_ascii_case(trimmed, "<html")
+    {
+        "html"
+    } else if code.contains("fn main(") || has_line_starting_with(code, &["use std::", "#[derive("])
+    {
+        "rust"
+    } else if has_line_starting_with(code, &["package main"]) {
+        "go"
+    } else if has_line_starting_with(code, &["#include <"]) {
+        match code.contains("std::") || code.contains("<iostream>") {
+            true => "cpp",
+            false => "c",
+        }
+    } else if code.lines().any(|line| {
+        let line = line.trim_end();
+        (line.starts_with("def ") || line.starts_with("class ")) && line.ends_with(':')
+            || line.starts_with("from ") && line.contains(" import ")
+    }) {
+        "python"
+    } else if (trimmed.starts_with('{') && code.trim_end().ends_with('}')
+        || trimmed.starts_with('[') && code.trim_end().ends_with(']'))
+        && code.contains("\":")
+    {
+        "json"
+    } else {
+        return None;
+    };
+    Some(language.into())
+}
+
+/// The language of the interpreter in a shebang line without the `#!`,
+/// e.g., `python` for `/usr/bin/env python3`.
+fn language_of_interpreter(shebang: &str) -> Option<String> {
+    let mut words = shebang.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match name {
+        "" => return None,
+        "node" | "nodejs" => "javascript",
+        "deno" | "bun" | "ts-node" => "typescript",
+        "pwsh" | "powershell" => "powershell",
+        "rust-script" => "rust",
+        name => name,
+    };
+    Some(language.into())
+}
+
+fn starts_with_ignore_ascii_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+fn has_line_starting_with(code: &str, prefixes: &[&str]) -> bool {
+    code.lines()
+        .any(|line| prefixes.iter().any(|prefix| line.starts_with(prefix)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn infers_from_shebangs() {
+        for (code, language) in [
+            ("#!/bin/bash\necho hi\n", "bash"),
+            ("#!/usr/bin/env -S node --flag\n", "javascript"),
+            ("#!/usr/bin/python3.11\n", "python"),
+            ("#!/usr/bin/env ruby\n", "ruby"),
+        ] {
+            assert_eq!(infer(code).as_deref(), Some(language), "{code}");
+        }
+        assert_eq!(infer("#!\n"), None);
+    }
+
+    #[test]
+    fn infers_from_content() {
+        for (code, language) in [
+            ("<?php echo 1; ?>\n", "php"),
+            ("<!DOCTYPE html>\n<html></html>\n", "html"),
+            ("use std::io;\n", "rust"),
+            ("package main\n\nfunc main() {}\n", "go"),
+            ("#include <stdio.h>\n", "c"),
+            ("#include <iostream>\n", "cpp"),
+            ("def f(x):\n    return x\n", "python"),
+            ("{\n  \"a\": 1\n}\n", "json"),
+        ] {
+            assert_eq!(infer(code).as_deref(), Some(language), "{code}");
+        }
+        assert_eq!(infer("$ cargo build\n"), None);
+    }
+}
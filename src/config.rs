@@ -1,24 +1,516 @@
 use super::*;
 
+mod discover;
+mod warning;
+
+pub use discover::{CONFIG_FILE_NAME, DiscoverError};
+pub use warning::ConfigWarning;
+
 /// Configuration options for the Markdown formatter.
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     /// Maximum line width.
+    /// Headings and tables are never wrapped, so they may exceed it.
     pub max_width: Option<usize>,
+    /// If set, wrap paragraphs at this width instead of `max_width`.
+    pub paragraph_max_width: Option<usize>,
+    /// If set, pass this width to code block formatters instead of `max_width`,
+    /// e.g., to allow longer lines in code than in prose.
+    pub code_block_max_width: Option<usize>,
     /// If set, all ordered lists will have this many leading zeroes.
     pub fixed_zero_padding: Option<usize>,
     /// If set, all ordered lists will begin with this number.
     pub fixed_number: Option<usize>,
     /// If set, all ordered lists will have this marker after the number.
     pub fixed_ordered_list_marker: Option<OrderedListMarker>,
-    /// If set, all unordered lists will begin with this marker.
+    /// If set, all unordered lists will begin with this marker,
+    /// except a list right after another one, which keeps a different marker
+    /// so the two stay separate lists.
     pub fixed_unordered_list_marker: Option<UnorderedListMarker>,
+    /// If set, unordered lists will use these markers by nesting depth,
+    /// cycling back to the first marker for deeper lists,
+    /// e.g., `*` at the top level and `-` for nested lists.
+    /// Takes precedence over `fixed_unordered_list_marker`,
+    /// and likewise does not give a list the marker of the list right before it.
+    pub unordered_list_markers_by_depth: Option<Vec<UnorderedListMarker>>,
+    /// Remove list items without any content, e.g., `-` lines,
+    /// and lists with only such items.
+    /// Otherwise, empty list items are kept as a bare marker.
+    pub drop_empty_list_items: bool,
+    /// If set, merge block quotes separated by blank lines,
+    /// or split them at blank `>` lines.
+    /// Otherwise, both are kept as is.
+    /// GitHub alerts, e.g., `> [!NOTE]`, are neither merged nor split.
+    pub block_quote_separation: Option<BlockQuoteSeparation>,
     /// If set, all lists will have this many indentation per level.
     pub fixed_indentation: Option<Cow<'static, str>>,
-    /// If set, all emphasis spans will use this marker.
+    /// If set, all emphasis spans will use this marker,
+    /// except those that would no longer be emphasis with it,
+    /// e.g., `foo*bar*baz` keeps `*` since `_` inside words is not emphasis.
     pub fixed_emphasis_marker: Option<&'static str>,
-    /// If set, all strong spans will use this marker.
+    /// If set, all strong spans will use this marker, with the same exception.
     pub fixed_strong_marker: Option<&'static str>,
+    /// Never reflow paragraphs consisting only of images and image links,
+    /// e.g., rows of badges at the top of a README.
+    pub preserve_badge_lines: bool,
+    /// Join the lines of paragraphs consisting only of images and image links,
+    /// e.g., rows of badges, into one line regardless of the maximum width.
+    /// Takes precedence over `preserve_badge_lines`.
+    pub single_line_badge_rows: bool,
+    /// Ensure `<details>` and `</details>` HTML blocks outside of lists are
+    /// preceded by a blank line, so GitHub renders the Markdown around them.
+    /// HTML blocks always end at a blank line, so one follows them already.
+    pub blank_lines_around_details: bool,
+    /// Format the Markdown inside `<details>` HTML blocks that GitHub would
+    /// otherwise render as plain HTML because no blank line follows `<summary>`.
+    /// The output inserts the missing blank lines around the inner Markdown.
+    pub format_details_content: bool,
+    /// Rewrite `#fragment` links to headings whose anchors change during
+    /// formatting, instead of reporting them as [`Diagnostic`]s.
+    pub fix_anchor_links: bool,
+    /// If set, number headings of this level (1 to 6) and deeper hierarchically,
+    /// e.g., `## 2.1 Title` when numbering from level 1.
+    /// Existing numbers with as many components as the heading's depth are
+    /// replaced, so a leading year in a section heading is taken as a number.
+    /// Headings above this level restart the numbering;
+    /// headings in block quotes and lists, and headings with no text
+    /// besides their attributes, are left alone.
+    pub number_headings_from: Option<usize>,
+    /// If set, give fenced code blocks without an info string the language
+    /// this function returns for their content, e.g.,
+    /// [`code_language::infer`](crate::code_language::infer).
+    pub infer_code_block_language: Option<fn(&str) -> Option<String>>,
+    /// Lowercase the language of fenced code block info strings,
+    /// e.g., ```` ```Rust ```` to ```` ```rust ````,
+    /// which some syntax highlighters match case-sensitively,
+    /// and drop the whitespace between the fence and the info string.
+    pub normalize_code_block_info: bool,
+    /// Convert HTML blocks made only of paragraphs, lists, tables, links, images,
+    /// and basic inline elements like `<b>` and `<i>` to Markdown,
+    /// e.g., to clean up documents pasted from rich text editors.
+    /// Other HTML blocks are preserved.
+    pub convert_html_blocks: bool,
+    /// If set, downgrade constructs this Markdown flavor does not support
+    /// to equivalents it does.
+    /// Footnote definitions only contain the blocks indented after them
+    /// in flavors with [`Flavor::supports_footnote_blocks`].
+    pub flavor: Option<Flavor>,
+    /// Keep the input as is except for trailing whitespace,
+    /// which is trimmed unless it is content or a hard break,
+    /// e.g., in code blocks and code spans.
+    /// All other options are ignored.
+    /// See [`Config::preserve`].
+    pub preserve_source: bool,
+    /// Avoid adding or removing lines, so line-based review comments and
+    /// anchors still point at the same content: paragraphs are not reflowed,
+    /// leading and trailing blank lines are kept,
+    /// and options that add or remove lines are ignored,
+    /// i.e., `blank_lines_around_details`, `format_details_content`,
+    /// `convert_html_blocks`, and the blank lines of [`Flavor::Pandoc`].
+    pub preserve_line_numbers: bool,
+    /// Minimal-diff mode, to reduce churn when the configuration changes slightly.
+    /// Treat the input as the previous formatted output and keep its line breaks
+    /// in paragraphs, only re-wrapping lines longer than the maximum width
+    /// plus this many columns.
+    /// Has no effect without a maximum width for paragraphs.
+    pub minimal_diff_tolerance: Option<usize>,
+    /// Do not pad table cells to align columns,
+    /// and keep the delimiter row as typed, e.g., `|---|:-:|`,
+    /// so editing a cell does not change the other rows.
+    pub compact_tables: bool,
+    /// Turn hard breaks directly in link and image text into soft breaks,
+    /// i.e., spaces like other line breaks there, as renderers disagree on them.
+    /// Hard breaks elsewhere, including in emphasis, are kept.
+    pub hard_breaks_in_links_as_spaces: bool,
+    /// Join the lines of each paragraph into one long line regardless of
+    /// the maximum width, e.g., for translation or diff-tracking workflows.
+    /// Hard breaks are kept.
+    pub unwrap_paragraphs: bool,
+    /// Collapse runs of spaces between words in paragraphs to one space,
+    /// even if paragraphs are not wrapped.
+    /// Code spans, math, HTML, and `shortcodes` are left as is.
+    pub collapse_spaces: bool,
+    /// If set, paragraph lines this regular expression matches are never
+    /// merged with other lines or wrapped, e.g., `^\\s*%%` for Anki cloze
+    /// markers or custom directives.
+    /// Lines are matched without the indentation or markers of their containers.
+    pub protected_lines: Option<Regex>,
+    /// If set, paragraph text this regular expression matches is kept as is,
+    /// never wrapped across lines, escaped, or reformatted inside,
+    /// e.g., `\{\{[<%].*?[%>]\}\}` for Hugo shortcodes like `{{< figure >}}`.
+    pub shortcodes: Option<Regex>,
+    /// If set, where to put link reference definitions.
+    /// Otherwise, they stay where they are, except that those at the end of
+    /// the document are sorted and separated as the next options configure.
+    pub reference_definitions_placement: Option<ReferenceDefinitionPlacement>,
+    /// If set, sort the link reference definitions at the end of the document.
+    /// Otherwise, they stay in source order.
+    pub reference_definitions_order: Option<ReferenceDefinitionOrder>,
+    /// If set, separate the link reference definitions at the end of the document
+    /// by this many blank lines.
+    /// Otherwise, keep the blank lines between them in the source,
+    /// or none if they are sorted.
+    pub blank_lines_between_reference_definitions: Option<usize>,
+    /// Join the lines of link titles spanning multiple lines in reference
+    /// definitions with spaces.
+    /// Otherwise, each title line is kept, re-indented for its container.
+    pub single_line_link_titles: bool,
+    /// Upgrade the bare URLs and email addresses that GFM links automatically,
+    /// e.g., `https://example.com` and `foo@example.com`,
+    /// to explicit autolinks, e.g., `<https://example.com>`.
+    /// `www.` links, which have no scheme,
+    /// become inline links to `http://` URLs instead.
+    pub explicit_autolinks: bool,
+    /// If set, separate lists from the indented code blocks right after them
+    /// with an HTML comment of this text, or `<!-- -->` if empty.
+    /// Otherwise, they are separated by comments suggesting a fenced code block.
+    pub list_code_block_separator: Option<Cow<'static, str>>,
+    /// Write indented code blocks right after lists as fenced code blocks,
+    /// which need no separator comment.
+    /// Takes precedence over `list_code_block_separator`.
+    pub fence_code_blocks_after_lists: bool,
+    /// If set, code blocks longer than this many bytes are written to
+    /// the output line by line as they are instead of being buffered for
+    /// the external formatter,
+    /// and no buffer but the output pre-allocates more than this many bytes,
+    /// e.g., to format huge log dumps without doubling the memory they take.
+    /// The output pre-allocates the size of the input.
+    pub code_block_buffer_limit: Option<usize>,
+    /// If set, paragraphs with a source line longer than this many bytes are
+    /// not wrapped, e.g., minified content pasted into Markdown,
+    /// and [`MarkdownFormatter::format_with_diagnostics`] reports them as
+    /// [`DiagnosticKind::LineTooLongToWrap`].
+    pub max_wrapped_line_length: Option<usize>,
+    /// If set, inline links in paragraphs at least this many columns wide,
+    /// measured with their line breaks as spaces,
+    /// are moved to their own line, as some style guides require for long URLs.
+    /// Links in paragraphs kept as is, e.g., badge rows, stay where they are,
+    /// and so do links right after text or whose moving would leave a line
+    /// starting a block, e.g., `*` a list item.
+    pub own_line_link_width: Option<usize>,
+    /// Opt-in [`Transform`]s of the document content to apply while formatting,
+    /// in order, e.g., sorting table rows.
+    pub transforms: Vec<Transform>,
+    /// If set, where to align the continuation lines of the first paragraph
+    /// of list items, e.g., under the marker instead of the text after long
+    /// markers like `100.`.
+    /// Otherwise, they align under the text.
+    pub continuation_alignment: Option<ContinuationAlignment>,
+    /// Zero-pad the numbers of each ordered list to its widest number,
+    /// e.g., `08.`, `09.`, `10.`, on top of `fixed_zero_padding`.
+    pub zero_pad_to_widest_number: bool,
+    /// If set, how to write HTML entities, e.g., `&mdash;`, in text.
+    /// Otherwise, they are preserved verbatim.
+    pub html_entities: Option<HtmlEntities>,
+    /// If set, how the renderer generates heading anchors,
+    /// for `fix_anchor_links` and the anchor diagnostics.
+    /// Otherwise, anchors are generated like GitHub does.
+    pub slug_style: Option<SlugStyle>,
+    /// Write HTML blocks exactly as they are at the current indentation,
+    /// keeping the leading whitespace of every line, e.g., in `<pre>`,
+    /// and the whitespace of whitespace-only lines between their paragraphs.
+    /// Otherwise, the HTML block formatter gets the block,
+    /// and whitespace-only lines become empty lines.
+    pub preserve_html_block_whitespace: bool,
+    /// Only write ASCII characters that are not in the input,
+    /// for toolchains that cannot handle other encodings:
+    /// `html_entities` only decodes entities standing for ASCII characters,
+    /// `convert_html_blocks` keeps HTML blocks whose conversion is not ASCII,
+    /// and `replace_terms` writes non-ASCII replacements as entities.
+    /// Set `html_entities` to `Encode` to also encode the input's characters.
+    pub ascii_only: bool,
+    /// Write top-level fenced code blocks with the `suggestion` info string,
+    /// i.e., GitHub review suggestions, exactly as they are,
+    /// including their fences and the blank lines before them,
+    /// so review comments can be formatted without changing the suggested code.
+    pub preserve_suggestion_blocks: bool,
+    /// If set, expand the tabs indenting lines, including those after
+    /// block quote and list markers, to spaces at stops every this many columns
+    /// before formatting.
+    /// Tabs in code blocks, HTML blocks, and metadata blocks are kept.
+    /// Use 4, CommonMark's tab stop, to keep the document's structure.
+    pub tab_width: Option<usize>,
+    /// If set, this function gets each table after `transforms`,
+    /// and can change its cells and alignments before it is written,
+    /// e.g., to truncate or linkify cells.
+    pub table_hook: Option<fn(&mut Table)>,
+    /// If set, how long to write the `===` and `---` underlines
+    /// of setext headings.
+    /// Otherwise, they are kept as is.
+    pub setext_underline: Option<SetextUnderline>,
+    /// Keep paragraphs whose lines all end with hard breaks, e.g.,
+    /// poetry and addresses, as they are: their lines are not wrapped,
+    /// and lines indented past the first line keep that indentation.
+    pub preserve_line_blocks: bool,
+    /// HTML comments that must stay on their own line, e.g., `<!--more-->`,
+    /// which blog engines use to mark the end of a post's excerpt.
+    /// Such comments inside paragraphs are moved to their own line,
+    /// so they are never wrapped into the text around them.
+    pub own_line_comments: Vec<String>,
+    /// If set, unordered lists with task list items, e.g., `- [ ] todo`,
+    /// use this marker, as some renderers only recognize `-` task lists.
+    /// The whole list gets it, so it is not split where other items
+    /// are not tasks.
+    /// Takes precedence over the other unordered list marker options.
+    pub bullet_for_task_lists: Option<UnorderedListMarker>,
+    /// If set, how to number the items of ordered lists.
+    /// Otherwise, all items get `fixed_number` if it is set.
+    pub ordered_list_numbering: Option<OrderedListNumbering>,
+    /// If set, which syntax to write headings with.
+    /// Otherwise, headings keep their syntax.
+    pub heading_style: Option<HeadingStyle>,
+    /// If set, write the fences of fenced code blocks with this character.
+    /// Backtick fences cannot have info strings with backticks,
+    /// so those blocks keep their fences.
+    pub code_fence_style: Option<CodeFenceStyle>,
+    /// If set, write code fences this long, or longer if the code has
+    /// as long a run of the fence character, instead of as long as
+    /// in the source.
+    pub code_fence_min_length: Option<usize>,
+    /// If set, align the destinations of reference link definitions
+    /// on consecutive lines in a column by padding after their `]:`,
+    /// unless the `[label]:` is wider than this many columns.
+    pub align_reference_definitions: Option<usize>,
+    /// If set, whether a blank line separates a list item's paragraph from
+    /// the list nested right after it.
+    /// Otherwise, the source spacing is kept.
+    pub nested_list_spacing: Option<NestedListSpacing>,
+}
+
+/// Spacing between a list item's paragraph and its nested list.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NestedListSpacing {
+    /// Keep the spacing of each nested list.
+    Preserve,
+    /// Always a blank line, which makes the outer list loose when rendered.
+    BlankLine,
+    /// Never a blank line.
+    /// Nested lists that cannot interrupt a paragraph keep theirs,
+    /// e.g., ordered lists not starting at `1`.
+    NoBlankLine,
+}
+
+/// Markdown flavor the formatted output is rendered with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flavor {
+    /// Strict [CommonMark](https://spec.commonmark.org/):
+    /// strikethrough becomes `<del>` HTML and task list markers plain `[ ]` text.
+    CommonMark,
+    /// [GitHub Flavored Markdown](https://github.github.com/gfm/).
+    /// Nothing is downgraded.
+    Gfm,
+    /// [Pandoc's Markdown](https://pandoc.org/MANUAL.html#pandocs-markdown):
+    /// lists, headings, and block quotes get the blank line before them that
+    /// Pandoc requires.
+    Pandoc,
+    /// [MkDocs](https://www.mkdocs.org/)' Python-Markdown without extensions:
+    /// like `CommonMark`, and list item content is indented by at least 4 spaces.
+    MkDocs,
+}
+
+/// Where to put link reference definitions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReferenceDefinitionPlacement {
+    /// Keep each definition exactly where it is, with the blank lines around it,
+    /// and never sort or re-space them.
+    Preserve,
+    /// Move all definitions to the end of the document,
+    /// in source order unless sorted.
+    End,
+}
+
+/// Order of the link reference definitions at the end of the document.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReferenceDefinitionOrder {
+    /// Alphabetically by label, ignoring case.
+    Label,
+    /// By the first link using each definition,
+    /// followed by unused definitions in source order.
+    FirstUse,
+}
+
+/// How to separate the blocks of adjacent block quotes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockQuoteSeparation {
+    /// Merge block quotes separated only by blank lines into one,
+    /// with blank `>` lines between their blocks.
+    /// Block quotes that would meet at nested block quotes stay separate.
+    Merge,
+    /// Split block quotes at the blank `>` lines between their blocks
+    /// into separate block quotes, with blank lines between them.
+    /// Directly nested block quotes are split along with their block quote.
+    Split,
+}
+
+/// Where continuation lines of a list item's first paragraph start.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContinuationAlignment {
+    /// Under the list marker, as lazy continuation lines.
+    /// Blocks after the first paragraph still align under the text,
+    /// because they would otherwise end the list.
+    Marker,
+    /// Under the text after the list marker.
+    Text,
+}
+
+/// How long to write the underlines of setext headings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SetextUnderline {
+    /// As wide as the widest line of the heading, including its attributes.
+    HeadingWidth,
+    /// This many `=` or `-`, at least one.
+    Fixed(usize),
+}
+
+/// Character to write code fences with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CodeFenceStyle {
+    /// ```` ``` ````.
+    Backtick,
+    /// `~~~`.
+    Tilde,
+}
+
+/// Which syntax to write headings with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeadingStyle {
+    /// Keep the syntax of each heading.
+    Preserve,
+    /// `# Heading`.
+    /// Setext headings of several lines or ending with `#` are kept,
+    /// as ATX headings are one line and the `#` would be taken for
+    /// a closing sequence.
+    Atx,
+    /// `Heading` underlined by `===` or `---` for levels 1 and 2,
+    /// as wide as the heading unless `setext_underline` is set.
+    /// Other levels, empty headings, headings whose text would be taken
+    /// for another block, e.g., `# - item`, and headings right after
+    /// a line that the text would join, e.g., a paragraph's, stay ATX headings.
+    SetextWhenPossible,
+}
+
+/// How to number the items of ordered lists.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrderedListNumbering {
+    /// Keep the number of each item, ignoring `fixed_number`.
+    Preserve,
+    /// Number every item `1`.
+    AllOnes,
+    /// Number the items counting up from the first item's number,
+    /// or from `fixed_number` if it is set, e.g., `1.`, `2.`, `3.`.
+    /// Nested lists are counted on their own.
+    Incrementing,
+}
+
+/// How to write HTML entities and non-ASCII characters in text.
+/// Code spans, code blocks, and HTML are never changed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HtmlEntities {
+    /// Keep entities and characters exactly as they are.
+    Preserve,
+    /// Decode entities to the Unicode characters they stand for, e.g.,
+    /// `&mdash;` to `—`, except those standing for ASCII punctuation or
+    /// whitespace, e.g., `&lt;` or `&#10;`, which Markdown could interpret.
+    Decode,
+    /// Encode non-ASCII characters as entities, e.g., `—` as `&mdash;`,
+    /// using numeric references for characters without a common name.
+    /// Characters after a backslash, next to `*` or `_`, or in link labels
+    /// are kept, as encoding them would change what the Markdown means.
+    Encode,
+}
+
+impl HtmlEntities {
+    /// Entity `source` text, parsed as `parsed`, as written under this policy.
+    pub(crate) fn rewrite_entity<'t>(self, source: &'t str, parsed: &'t str) -> &'t str {
+        let is_entity = source.starts_with('&') && source.ends_with(';') && source != parsed;
+        let is_significant = |c: char| c.is_ascii_punctuation() || c.is_ascii_whitespace();
+        match self {
+            Self::Decode if is_entity && !parsed.contains(is_significant) => parsed,
+            _ => source,
+        }
+    }
+
+    /// `text` with its non-ASCII characters encoded if this policy encodes them,
+    /// where `before` and `after` are the characters written around `text`.
+    /// Characters after a backslash are kept, as it would escape the `&`,
+    /// and so are those next to `*` or `_`, which decide whether they are
+    /// emphasis delimiters.
+    pub(crate) fn encode<'t>(
+        self,
+        text: Cow<'t, str>,
+        before: Option<char>,
+        after: Option<char>,
+    ) -> Cow<'t, str> {
+        if self != Self::Encode || text.is_ascii() {
+            return text;
+        }
+        let mut encoded = String::with_capacity(text.len());
+        let mut previous = before;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let next = chars.peek().copied().or(after);
+            let kept = c.is_ascii()
+                || previous == Some('\\')
+                || [previous, next]
+                    .iter()
+                    .any(|around| matches!(around, Some('*' | '_')));
+            match ENTITY_NAMES.iter().find(|(named, _)| *named == c) {
+                _ if kept => encoded.push(c),
+                Some((_, name)) => _ = write!(encoded, "&{name};"),
+                None => _ = write!(encoded, "&#x{:X};", c as u32),
+            }
+            previous = Some(c);
+        }
+        encoded.into()
+    }
+}
+
+/// Names of the common non-ASCII characters' entities.
+const ENTITY_NAMES: [(char, &str); 20] = [
+    ('\u{a0}', "nbsp"),
+    ('©', "copy"),
+    ('®', "reg"),
+    ('°', "deg"),
+    ('±', "plusmn"),
+    ('·', "middot"),
+    ('«', "laquo"),
+    ('»', "raquo"),
+    ('×', "times"),
+    ('÷', "divide"),
+    ('–', "ndash"),
+    ('—', "mdash"),
+    ('‘', "lsquo"),
+    ('’', "rsquo"),
+    ('“', "ldquo"),
+    ('”', "rdquo"),
+    ('•', "bull"),
+    ('…', "hellip"),
+    ('€', "euro"),
+    ('™', "trade"),
+];
+
+impl Flavor {
+    /// Check if the flavor renders `~~strikethrough~~`.
+    pub fn supports_strikethrough(self) -> bool {
+        matches!(self, Self::Gfm | Self::Pandoc)
+    }
+
+    /// Check if the flavor renders `- [ ]` task list markers as checkboxes.
+    pub fn supports_task_lists(self) -> bool {
+        matches!(self, Self::Gfm | Self::Pandoc)
+    }
+
+    /// Check if the flavor takes lines starting with `:` right after a table
+    /// as its caption rather than rows.
+    pub fn supports_table_captions(self) -> bool {
+        matches!(self, Self::Pandoc)
+    }
+
+    /// Check if footnote definitions in the flavor continue with the blocks
+    /// indented by 4 spaces after them, like GitHub's footnotes.
+    pub fn supports_footnote_blocks(self) -> bool {
+        matches!(self, Self::Gfm | Self::Pandoc)
+    }
 }
 
 impl Config {
@@ -26,29 +518,202 @@ impl Config {
     pub fn sichanghe_opinion() -> Self {
         Self {
             max_width: Some(80),
+            paragraph_max_width: None,
+            code_block_max_width: None,
             fixed_zero_padding: Some(0),
             fixed_number: Some(1),
             fixed_ordered_list_marker: Some(OrderedListMarker::Period),
             fixed_unordered_list_marker: Some(UnorderedListMarker::Hyphen),
+            unordered_list_markers_by_depth: None,
+            drop_empty_list_items: false,
+            block_quote_separation: None,
             fixed_indentation: Some("    ".into()),
             fixed_emphasis_marker: Some("*"),
             fixed_strong_marker: Some("**"),
+            preserve_badge_lines: false,
+            single_line_badge_rows: false,
+            blank_lines_around_details: false,
+            format_details_content: false,
+            fix_anchor_links: false,
+            number_headings_from: None,
+            infer_code_block_language: None,
+            normalize_code_block_info: false,
+            convert_html_blocks: false,
+            flavor: None,
+            preserve_source: false,
+            preserve_line_numbers: false,
+            minimal_diff_tolerance: None,
+            compact_tables: false,
+            hard_breaks_in_links_as_spaces: false,
+            unwrap_paragraphs: false,
+            collapse_spaces: false,
+            protected_lines: None,
+            shortcodes: None,
+            reference_definitions_placement: None,
+            reference_definitions_order: None,
+            blank_lines_between_reference_definitions: None,
+            single_line_link_titles: false,
+            explicit_autolinks: false,
+            list_code_block_separator: None,
+            fence_code_blocks_after_lists: false,
+            code_block_buffer_limit: None,
+            max_wrapped_line_length: None,
+            own_line_link_width: None,
+            transforms: vec![],
+            continuation_alignment: None,
+            zero_pad_to_widest_number: false,
+            html_entities: None,
+            slug_style: None,
+            preserve_html_block_whitespace: false,
+            ascii_only: false,
+            preserve_suggestion_blocks: false,
+            tab_width: None,
+            table_hook: None,
+            setext_underline: None,
+            preserve_line_blocks: false,
+            own_line_comments: vec![],
+            bullet_for_task_lists: None,
+            ordered_list_numbering: None,
+            heading_style: None,
+            code_fence_style: None,
+            code_fence_min_length: None,
+            align_reference_definitions: None,
+            nested_list_spacing: None,
         }
     }
 
+    /// Preset for GitHub READMEs,
+    /// which keeps the constructs GitHub renders specially intact.
+    pub fn gfm_readme() -> Self {
+        Self {
+            preserve_badge_lines: true,
+            blank_lines_around_details: true,
+            ..Self::default()
+        }
+    }
+
+    /// Profile that never changes what the Markdown means and only trims
+    /// trailing whitespace, i.e., [`Config::preserve_source`],
+    /// as a safe start for adopting the formatter.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+    /// let input = "#  Title \n\n*  item  \n   continued\n\n```\ncode  \n```\n";
+    /// let rewrite = <MarkdownFormatter>::with_config(Config::preserve())
+    ///     .format(input)
+    ///     .unwrap();
+    /// assert_eq!(rewrite, "#  Title\n\n*  item  \n   continued\n\n```\ncode  \n```\n");
+    /// ```
+    pub fn preserve() -> Self {
+        Self {
+            preserve_source: true,
+            ..Self::default()
+        }
+    }
+
+    /// The maximum line width for content of the given `context`.
+    pub fn max_width_for(&self, context: FormattingContext) -> Option<usize> {
+        let max_width = match context {
+            FormattingContext::Paragraph if self.preserve_line_numbers => return None,
+            FormattingContext::Paragraph if self.unwrap_paragraphs => return Some(usize::MAX),
+            FormattingContext::Paragraph => self.paragraph_max_width,
+            FormattingContext::CodeBlock => self.code_block_max_width,
+            _ => None,
+        };
+        max_width.or(self.max_width)
+    }
+
+    /// Options to parse Markdown formatted with this configuration,
+    /// with GitHub's footnotes if the flavor has them.
+    pub(crate) fn parser_options(&self) -> Options {
+        let mut options = parser_options();
+        if self.flavor.is_some_and(Flavor::supports_footnote_blocks) {
+            options.remove(Options::ENABLE_OLD_FOOTNOTES);
+            options.insert(Options::ENABLE_FOOTNOTES);
+        }
+        options
+    }
+
+    /// This configuration prepared for formatting,
+    /// see [`Config::without_line_changes`] and [`Config::with_ascii_replacements`],
+    /// only cloned if preparing changes it.
+    pub(crate) fn prepared(&self) -> Cow<'_, Self> {
+        match self.preserve_line_numbers || self.ascii_only {
+            true => Cow::Owned(
+                self.clone()
+                    .without_line_changes()
+                    .with_ascii_replacements(),
+            ),
+            false => Cow::Borrowed(self),
+        }
+    }
+
+    /// This configuration without the options that add or remove lines
+    /// if `preserve_line_numbers` is set.
+    pub(crate) fn without_line_changes(self) -> Self {
+        if !self.preserve_line_numbers {
+            return self;
+        }
+        Self {
+            blank_lines_around_details: false,
+            format_details_content: false,
+            convert_html_blocks: false,
+            own_line_link_width: None,
+            own_line_comments: vec![],
+            nested_list_spacing: None,
+            ..self
+        }
+    }
+
+    /// This configuration with the replacement terms of `transforms`
+    /// encoded as entities if `ascii_only` is set.
+    pub(crate) fn with_ascii_replacements(mut self) -> Self {
+        if !self.ascii_only {
+            return self;
+        }
+        for transform in &mut self.transforms {
+            if let Transform::ReplaceTerms { terms } = transform {
+                for (_, replacement) in terms {
+                    let encoded =
+                        HtmlEntities::Encode.encode(replacement.as_str().into(), None, None);
+                    *replacement = encoded.into_owned();
+                }
+            }
+        }
+        self
+    }
+
     /// Parse a list marker from string with this configuration.
     pub fn list_marker(&self, source: &str) -> Result<ListMarker, ParseListMarkerError> {
+        self.list_marker_at_depth(source, 0)
+    }
+
+    /// Parse a list marker from string with this configuration,
+    /// for a list nested in `depth` other lists.
+    pub fn list_marker_at_depth(
+        &self,
+        source: &str,
+        depth: usize,
+    ) -> Result<ListMarker, ParseListMarkerError> {
         Ok(match ListMarker::from_str(source)? {
             ListMarker::Ordered {
                 zero_padding,
                 number,
                 marker,
             } => {
-                let zero_padding = match self.fixed_zero_padding {
-                    Some(fixed_zero_padding) => fixed_zero_padding,
-                    None => zero_padding,
+                let fixed_number = match self.ordered_list_numbering {
+                    Some(OrderedListNumbering::Preserve) => None,
+                    Some(OrderedListNumbering::AllOnes) => Some(1),
+                    Some(OrderedListNumbering::Incrementing) | None => self.fixed_number,
+                };
+                let zero_padding = match (self.fixed_zero_padding, fixed_number) {
+                    (Some(fixed_zero_padding), _) => fixed_zero_padding,
+                    // Keep the width of the number, e.g., `010.` as `001.`.
+                    (None, Some(fixed_number)) => (zero_padding + list::number_width(number))
+                        .saturating_sub(list::number_width(fixed_number)),
+                    (None, None) => zero_padding,
                 };
-                let number = match self.fixed_number {
+                let number = match fixed_number {
                     Some(fixed_number) => fixed_number,
                     None => number,
                 };
@@ -62,22 +727,284 @@ impl Config {
                     marker,
                 }
             }
-            marker @ ListMarker::Unordered(_) => match &self.fixed_unordered_list_marker {
-                Some(fixed_marker) => ListMarker::Unordered(fixed_marker.clone()),
-                None => marker,
+            marker @ ListMarker::Unordered(_) => match (
+                &self.unordered_list_markers_by_depth,
+                &self.fixed_unordered_list_marker,
+            ) {
+                (Some(markers), _) if !markers.is_empty() => {
+                    ListMarker::Unordered(markers[depth % markers.len()].clone())
+                }
+                (_, Some(fixed_marker)) => ListMarker::Unordered(fixed_marker.clone()),
+                _ => marker,
             },
         })
     }
 
-    /// Internal setter for config options. Used for testing
-    #[cfg(test)]
-    pub(crate) fn set(&mut self, field: &str, value: &str) {
-        match field {
-            "max_width" => {
-                let value = value.parse::<usize>().unwrap();
-                self.max_width = Some(value)
+    /// Set the option `field` from its string representation `value`,
+    /// e.g., `"80"` for `max_width`, or `"*,-"` for `unordered_list_markers_by_depth`.
+    /// Enum values are their variant names, e.g., `"Gfm"` for `flavor`.
+    /// `transforms` are separated by `;`, each parsed as a [`Transform`].
+    /// On error, return a message naming the unknown option or invalid value.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::Config;
+    /// let mut config = Config::default();
+    /// config.set("max_width", "80").unwrap();
+    /// assert_eq!(config.max_width, Some(80));
+    /// assert!(config.set("max_width", "eighty").is_err());
+    /// ```
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(value: &str) -> Option<T> {
+            value.parse().ok()
+        }
+        fn marker<T: TryFrom<char>>(value: &str) -> Option<T> {
+            let mut chars = value.trim().chars();
+            match (chars.next(), chars.next()) {
+                (Some(marker), None) => marker.try_into().ok(),
+                _ => None,
+            }
+        }
+
+        // `Some(false)` for unknown options, `None` for invalid values.
+        let mut set = || -> Option<bool> {
+            match field {
+                "max_width" => self.max_width = Some(parse(value)?),
+                "paragraph_max_width" => self.paragraph_max_width = Some(parse(value)?),
+                "code_block_max_width" => self.code_block_max_width = Some(parse(value)?),
+                "fixed_zero_padding" => self.fixed_zero_padding = Some(parse(value)?),
+                "zero_pad_to_widest_number" => self.zero_pad_to_widest_number = parse(value)?,
+                "ascii_only" => self.ascii_only = parse(value)?,
+                "preserve_line_blocks" => self.preserve_line_blocks = parse(value)?,
+                "preserve_suggestion_blocks" => self.preserve_suggestion_blocks = parse(value)?,
+                "preserve_html_block_whitespace" => {
+                    self.preserve_html_block_whitespace = parse(value)?
+                }
+                "fixed_number" => self.fixed_number = Some(parse(value)?),
+                "tab_width" => self.tab_width = Some(parse(value)?),
+                "fixed_ordered_list_marker" => {
+                    self.fixed_ordered_list_marker = Some(marker(value)?)
+                }
+                "fixed_unordered_list_marker" => {
+                    self.fixed_unordered_list_marker = Some(marker(value)?)
+                }
+                "drop_empty_list_items" => self.drop_empty_list_items = parse(value)?,
+                "bullet_for_task_lists" => self.bullet_for_task_lists = Some(marker(value)?),
+                "unordered_list_markers_by_depth" => {
+                    let markers = value.split(',').map(marker).collect::<Option<_>>()?;
+                    self.unordered_list_markers_by_depth = Some(markers)
+                }
+                "fixed_indentation" => self.fixed_indentation = Some(value.to_owned().into()),
+                "fixed_emphasis_marker" => {
+                    self.fixed_emphasis_marker = Some(match value {
+                        "*" => "*",
+                        "_" => "_",
+                        _ => return None,
+                    })
+                }
+                "fixed_strong_marker" => {
+                    self.fixed_strong_marker = Some(match value {
+                        "**" => "**",
+                        "__" => "__",
+                        _ => return None,
+                    })
+                }
+                "preserve_badge_lines" => self.preserve_badge_lines = parse(value)?,
+                "single_line_badge_rows" => self.single_line_badge_rows = parse(value)?,
+                "blank_lines_around_details" => self.blank_lines_around_details = parse(value)?,
+                "format_details_content" => self.format_details_content = parse(value)?,
+                "fix_anchor_links" => self.fix_anchor_links = parse(value)?,
+                "number_headings_from" => self.number_headings_from = Some(parse(value)?),
+                "infer_code_block_language" => {
+                    self.infer_code_block_language = match value {
+                        "builtin" => Some(code_language::infer),
+                        _ => return None,
+                    }
+                }
+                "convert_html_blocks" => self.convert_html_blocks = parse(value)?,
+                "flavor" => {
+                    self.flavor = Some(match value {
+                        "CommonMark" => Flavor::CommonMark,
+                        "Gfm" => Flavor::Gfm,
+                        "Pandoc" => Flavor::Pandoc,
+                        "MkDocs" => Flavor::MkDocs,
+                        _ => return None,
+                    })
+                }
+                "preserve_source" => self.preserve_source = parse(value)?,
+                "preserve_line_numbers" => self.preserve_line_numbers = parse(value)?,
+                "minimal_diff_tolerance" => self.minimal_diff_tolerance = Some(parse(value)?),
+                "compact_tables" => self.compact_tables = parse(value)?,
+                "unwrap_paragraphs" => self.unwrap_paragraphs = parse(value)?,
+                "collapse_spaces" => self.collapse_spaces = parse(value)?,
+                "protected_lines" => self.protected_lines = Some(Regex::new(value).ok()?),
+                "shortcodes" => self.shortcodes = Some(Regex::new(value).ok()?),
+                "own_line_comments" => {
+                    let comments = value.split(',').map(str::trim);
+                    let comments = comments.filter(|comment| !comment.is_empty());
+                    self.own_line_comments = comments.map(str::to_owned).collect()
+                }
+                "block_quote_separation" => {
+                    self.block_quote_separation = Some(match value {
+                        "Merge" => BlockQuoteSeparation::Merge,
+                        "Split" => BlockQuoteSeparation::Split,
+                        _ => return None,
+                    })
+                }
+                "reference_definitions_placement" => {
+                    self.reference_definitions_placement = Some(match value {
+                        "Preserve" => ReferenceDefinitionPlacement::Preserve,
+                        "End" => ReferenceDefinitionPlacement::End,
+                        _ => return None,
+                    })
+                }
+                "continuation_alignment" => {
+                    self.continuation_alignment = Some(match value {
+                        "Marker" => ContinuationAlignment::Marker,
+                        "Text" => ContinuationAlignment::Text,
+                        _ => return None,
+                    })
+                }
+                "code_fence_style" => {
+                    self.code_fence_style = Some(match value {
+                        "Backtick" => CodeFenceStyle::Backtick,
+                        "Tilde" => CodeFenceStyle::Tilde,
+                        _ => return None,
+                    })
+                }
+                "code_fence_min_length" => self.code_fence_min_length = Some(parse(value)?),
+                "align_reference_definitions" => {
+                    self.align_reference_definitions = Some(parse(value)?)
+                }
+                "nested_list_spacing" => {
+                    self.nested_list_spacing = Some(match value {
+                        "Preserve" => NestedListSpacing::Preserve,
+                        "BlankLine" => NestedListSpacing::BlankLine,
+                        "NoBlankLine" => NestedListSpacing::NoBlankLine,
+                        _ => return None,
+                    })
+                }
+                "heading_style" => {
+                    self.heading_style = Some(match value {
+                        "Preserve" => HeadingStyle::Preserve,
+                        "Atx" => HeadingStyle::Atx,
+                        "SetextWhenPossible" => HeadingStyle::SetextWhenPossible,
+                        _ => return None,
+                    })
+                }
+                "ordered_list_numbering" => {
+                    self.ordered_list_numbering = Some(match value {
+                        "Preserve" => OrderedListNumbering::Preserve,
+                        "AllOnes" => OrderedListNumbering::AllOnes,
+                        "Incrementing" => OrderedListNumbering::Incrementing,
+                        _ => return None,
+                    })
+                }
+                "html_entities" => {
+                    self.html_entities = Some(match value {
+                        "Preserve" => HtmlEntities::Preserve,
+                        "Decode" => HtmlEntities::Decode,
+                        "Encode" => HtmlEntities::Encode,
+                        _ => return None,
+                    })
+                }
+                "setext_underline" => {
+                    self.setext_underline = Some(match value {
+                        "HeadingWidth" => SetextUnderline::HeadingWidth,
+                        _ => SetextUnderline::Fixed(parse(value)?),
+                    })
+                }
+                "slug_style" => {
+                    self.slug_style = Some(match value {
+                        "GitHub" => SlugStyle::GitHub,
+                        "GitLab" => SlugStyle::GitLab,
+                        "Pandoc" => SlugStyle::Pandoc,
+                        _ => return None,
+                    })
+                }
+                "reference_definitions_order" => {
+                    self.reference_definitions_order = Some(match value {
+                        "Label" => ReferenceDefinitionOrder::Label,
+                        "FirstUse" => ReferenceDefinitionOrder::FirstUse,
+                        _ => return None,
+                    })
+                }
+                "single_line_link_titles" => self.single_line_link_titles = parse(value)?,
+                "explicit_autolinks" => self.explicit_autolinks = parse(value)?,
+                "normalize_code_block_info" => self.normalize_code_block_info = parse(value)?,
+                "list_code_block_separator" => {
+                    self.list_code_block_separator = Some(value.to_owned().into())
+                }
+                "fence_code_blocks_after_lists" => {
+                    self.fence_code_blocks_after_lists = parse(value)?
+                }
+                "blank_lines_between_reference_definitions" => {
+                    self.blank_lines_between_reference_definitions = Some(parse(value)?)
+                }
+                "hard_breaks_in_links_as_spaces" => {
+                    self.hard_breaks_in_links_as_spaces = parse(value)?
+                }
+                "code_block_buffer_limit" => self.code_block_buffer_limit = Some(parse(value)?),
+                "max_wrapped_line_length" => self.max_wrapped_line_length = Some(parse(value)?),
+                "own_line_link_width" => self.own_line_link_width = Some(parse(value)?),
+                "transforms" => {
+                    let transforms = value.split(';').map(|t| t.trim().parse().ok());
+                    self.transforms = transforms.collect::<Option<_>>()?
+                }
+                _ => return Some(false),
+            }
+            Some(true)
+        };
+        match set() {
+            Some(true) => Ok(()),
+            Some(false) => Err(format!("unknown configuration `{field}`")),
+            None => Err(format!("invalid value `{value}` for `{field}`")),
+        }
+    }
+}
+
+/// Parse rustfmt-style `key=value,key=value` strings,
+/// e.g., from a command line `--config` flag,
+/// on top of [`Config::default`].
+/// Values are as in [`Config::set`] and may contain commas,
+/// e.g., `unordered_list_markers_by_depth=*,-`,
+/// but a comma followed by `key=` starts the next option.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::Config;
+/// let config: Config = "max_width=100,unordered_list_markers_by_depth=*,-"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(config.max_width, Some(100));
+/// assert_eq!(config.unordered_list_markers_by_depth.unwrap().len(), 2);
+/// assert_eq!(
+///     "max_width=100,bullet=-".parse::<Config>().unwrap_err(),
+///     "unknown configuration `bullet`",
+/// );
+/// ```
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options: Vec<(&str, String)> = vec![];
+        for part in s.split(',').filter(|part| !part.trim().is_empty()) {
+            let key_value = part.split_once('=').filter(|(key, _)| {
+                let key = key.trim();
+                !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase() || b == b'_')
+            });
+            match (key_value, options.last_mut()) {
+                (Some((key, value)), _) => options.push((key.trim(), value.trim().into())),
+                (None, Some((_, value))) => {
+                    value.push(',');
+                    value.push_str(part.trim());
+                }
+                (None, None) => return Err(format!("expected `key=value`, found `{part}`")),
             }
-            _ => panic!("unknown configuration {field}"),
         }
+
+        let mut config = Self::default();
+        for (key, value) in options {
+            config.set(key, &value)?;
+        }
+        Ok(config)
     }
 }
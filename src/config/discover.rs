@@ -0,0 +1,331 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::*;
+use crate::ignore::{IGNORE_FILE_NAME, glob_matches, ignore_file_globs};
+
+/// File name of the configuration files [`Config::discover`] looks for.
+pub const CONFIG_FILE_NAME: &str = ".markdown-fmt.toml";
+
+impl Config {
+    /// Find the configuration for the Markdown file or directory at `path`
+    /// from the [`CONFIG_FILE_NAME`] files in it and its ancestor directories,
+    /// like `rustfmt.toml` discovery.
+    /// Options in nearer files override those in farther ones,
+    /// on top of [`Config::default`].
+    /// Return `None` if `path` is ignored,
+    /// i.e., matched by the `ignore` globs of a configuration file,
+    /// or by an [`IGNORE_FILE_NAME`] file in the same directories.
+    ///
+    /// Configuration files set options by name, e.g.:
+    ///
+    /// ```toml
+    /// max_width = 100
+    /// flavor = "Gfm"
+    /// unordered_list_markers_by_depth = ["*", "-"]
+    /// transforms = ["normalize_bullets", "replace_terms:e-mail->email,web site->website"]
+    /// # Globs relative to this file's directory, `**` matching any directories.
+    /// ignore = ["CHANGELOG.md", "generated/**"]
+    /// ```
+    ///
+    /// Only this flat subset of TOML is supported: no tables or multi-line values.
+    /// Ancestors are those of `path` as given,
+    /// so relative paths are not searched above the current directory.
+    pub fn discover(path: impl AsRef<Path>) -> Result<Option<Self>, DiscoverError> {
+        let path = path.as_ref();
+        let skip_file = usize::from(!path.is_dir());
+        let dirs = path.ancestors().skip(skip_file).collect::<Vec<_>>();
+
+        let mut config = Self::default();
+        for dir in dirs.into_iter().rev() {
+            let ignore = config.merge_dir(dir)?;
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            if ignore.iter().any(|glob| glob_matches(glob, relative)) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// Set the options in the configuration file in `dir`, if any,
+    /// and return the `ignore` globs of it and the ignore file in `dir`.
+    pub(crate) fn merge_dir(&mut self, dir: &Path) -> Result<Vec<String>, DiscoverError> {
+        let mut ignore = vec![];
+        let config_file = dir.join(CONFIG_FILE_NAME);
+        if config_file.is_file() {
+            let source = read(&config_file)?;
+            let globs = self.merge_config_file(&source).map_err(|(line, message)| {
+                DiscoverError::Parse {
+                    path: config_file,
+                    line,
+                    message,
+                }
+            })?;
+            ignore.extend(globs);
+        }
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        if ignore_file.is_file() {
+            ignore.extend(ignore_file_globs(&read(&ignore_file)?).map(Into::into));
+        }
+        Ok(ignore)
+    }
+
+    /// Set the options in configuration file `source`,
+    /// and return its `ignore` globs.
+    /// On error, return the 1-based line number and the problem.
+    fn merge_config_file(&mut self, source: &str) -> Result<Vec<String>, (usize, String)> {
+        let mut ignore = vec![];
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                let message = match line.starts_with('[') {
+                    true => "tables are not supported".into(),
+                    false => format!("expected `key = value`, found `{line}`"),
+                };
+                return Err((line_number, message));
+            };
+            let key = key.trim();
+            let values = parse_value(value.trim())
+                .ok_or_else(|| (line_number, format!("invalid value for `{key}`")))?;
+            // Array items joined the way `Config::set` splits them.
+            let separator = match key {
+                "ignore" => {
+                    ignore.extend(values);
+                    continue;
+                }
+                // Transforms use `,` in their own arguments.
+                "transforms" => ";",
+                _ => ",",
+            };
+            self.set(key, &values.join(separator))
+                .map_err(|message| (line_number, message))?;
+        }
+        Ok(ignore)
+    }
+}
+
+/// Error reading configuration or ignore files,
+/// e.g., in [`Config::discover`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DiscoverError {
+    /// A configuration or ignore file could not be read.
+    Io {
+        /// Path of the file.
+        path: PathBuf,
+        /// The underlying error.
+        error: std::io::Error,
+    },
+    /// A configuration file has an invalid line.
+    Parse {
+        /// Path of the configuration file.
+        path: PathBuf,
+        /// 1-based line number.
+        line: usize,
+        /// What is wrong with the line.
+        message: String,
+    },
+}
+
+impl std::fmt::Display for DiscoverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "cannot read `{}`: {error}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for DiscoverError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, DiscoverError> {
+    fs::read_to_string(path).map_err(|error| DiscoverError::Io {
+        path: path.into(),
+        error,
+    })
+}
+
+/// `line` without any `#` comment outside of strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, char) in line.char_indices() {
+        match (quote, char) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(char),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Parse a TOML string, integer, boolean, or one-line array of them
+/// into their string representations.
+fn parse_value(value: &str) -> Option<Vec<String>> {
+    match value.strip_prefix('[') {
+        Some(items) => {
+            let items = items.strip_suffix(']')?.trim();
+            let items = items.strip_suffix(',').unwrap_or(items);
+            if items.trim().is_empty() {
+                return Some(vec![]);
+            }
+            split_array_items(items)?
+                .into_iter()
+                .map(|item| parse_scalar(item.trim()))
+                .collect()
+        }
+        None => Some(vec![parse_scalar(value)?]),
+    }
+}
+
+fn split_array_items(items: &str) -> Option<Vec<&str>> {
+    let mut result = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut item_start = 0;
+    for (index, char) in items.char_indices() {
+        match (quote, char) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(char),
+            (None, ',') => {
+                result.push(&items[item_start..index]);
+                item_start = index + 1;
+            }
+            (None, '[' | ']') => return None,
+            _ => {}
+        }
+        escaped = false;
+    }
+    result.push(&items[item_start..]);
+    Some(result)
+}
+
+fn parse_scalar(value: &str) -> Option<String> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        return literal.strip_suffix('\'').map(Into::into);
+    }
+    let Some(basic) = value.strip_prefix('"') else {
+        let is_bare = value == "true"
+            || value == "false"
+            || !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit() || b == b'_');
+        return is_bare.then(|| value.replace('_', ""));
+    };
+    let mut result = String::with_capacity(basic.len());
+    let mut chars = basic.strip_suffix('"')?.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+        result.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            c @ ('"' | '\\') => c,
+            _ => return None,
+        });
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_config_files() {
+        let mut config = Config::default();
+        let source = r#"
+            max_width = 1_00 # Wide.
+            fixed_indentation = "  "
+            unordered_list_markers_by_depth = ['*', "-",]
+            ignore = ["a#b.md"]
+        "#;
+        assert_eq!(config.merge_config_file(source).unwrap(), ["a#b.md"]);
+        assert_eq!(config.max_width, Some(100));
+        assert_eq!(config.fixed_indentation.as_deref(), Some("  "));
+        assert_eq!(
+            config.unordered_list_markers_by_depth,
+            Some(vec![
+                UnorderedListMarker::Asterisk,
+                UnorderedListMarker::Hyphen
+            ])
+        );
+
+        for (source, line) in [("\n[table]", 2), ("max_width = \"x\"", 1), ("width = 1", 1)] {
+            assert_eq!(
+                config.merge_config_file(source).unwrap_err().0,
+                line,
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn discovers_nested_configs() {
+        let root =
+            std::env::temp_dir().join(format!("markdown-fmt-discover-{}", std::process::id()));
+        let nested = root.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(CONFIG_FILE_NAME),
+            "max_width = 80\ncompact_tables = true\nignore = [\"nested/skip.md\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            nested.join(CONFIG_FILE_NAME),
+            "max_width = 100\ntransforms = [\"normalize_bullets\", \"replace_terms:a->b,c->d\"]\n",
+        )
+        .unwrap();
+        fs::write(nested.join(IGNORE_FILE_NAME), "# Vendored.\nvendor/\n").unwrap();
+
+        let config = Config::discover(nested.join("doc.md")).unwrap().unwrap();
+        assert_eq!(config.max_width, Some(100));
+        assert!(config.compact_tables);
+        assert_eq!(
+            config.transforms,
+            [
+                Transform::NormalizeBullets,
+                Transform::ReplaceTerms {
+                    terms: vec![("a".into(), "b".into()), ("c".into(), "d".into())]
+                },
+            ]
+        );
+        assert!(Config::discover(nested.join("skip.md")).unwrap().is_none());
+        assert!(
+            Config::discover(nested.join("vendor/lib/doc.md"))
+                .unwrap()
+                .is_none()
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
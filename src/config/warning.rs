@@ -0,0 +1,218 @@
+use super::*;
+
+impl Config {
+    /// Find options that contradict each other or cannot take effect,
+    /// which would otherwise silently produce surprising output.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::{Config, ConfigWarning};
+    /// let config = Config {
+    ///     paragraph_max_width: Some(80),
+    ///     unwrap_paragraphs: true,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(
+    ///     config.warnings(),
+    ///     [ConfigWarning::Overridden {
+    ///         option: "paragraph_max_width",
+    ///         by: "unwrap_paragraphs",
+    ///     }],
+    /// );
+    /// assert!(Config::sichanghe_opinion().warnings().is_empty());
+    /// ```
+    pub fn warnings(&self) -> Vec<ConfigWarning> {
+        let preserve_references =
+            self.reference_definitions_placement == Some(ReferenceDefinitionPlacement::Preserve);
+        // Python-Markdown only nests content indented by 4 spaces.
+        let short_indentation = self
+            .fixed_indentation
+            .as_ref()
+            .is_some_and(|indentation| indentation.len() < 4);
+        let has_markers_by_depth = self
+            .unordered_list_markers_by_depth
+            .as_ref()
+            .is_some_and(|markers| !markers.is_empty());
+
+        // (option is set, option, overriding option is set, overriding option)
+        let overrides = [
+            (
+                self.paragraph_max_width.is_some(),
+                "paragraph_max_width",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.paragraph_max_width.is_some(),
+                "paragraph_max_width",
+                self.unwrap_paragraphs,
+                "unwrap_paragraphs",
+            ),
+            (
+                self.unwrap_paragraphs,
+                "unwrap_paragraphs",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.minimal_diff_tolerance.is_some(),
+                "minimal_diff_tolerance",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.minimal_diff_tolerance.is_some(),
+                "minimal_diff_tolerance",
+                self.unwrap_paragraphs,
+                "unwrap_paragraphs",
+            ),
+            (
+                self.blank_lines_around_details,
+                "blank_lines_around_details",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.format_details_content,
+                "format_details_content",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.convert_html_blocks,
+                "convert_html_blocks",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.own_line_link_width.is_some(),
+                "own_line_link_width",
+                self.preserve_line_numbers,
+                "preserve_line_numbers",
+            ),
+            (
+                self.own_line_link_width.is_some(),
+                "own_line_link_width",
+                self.unwrap_paragraphs,
+                "unwrap_paragraphs",
+            ),
+            (
+                self.fixed_unordered_list_marker.is_some(),
+                "fixed_unordered_list_marker",
+                has_markers_by_depth,
+                "unordered_list_markers_by_depth",
+            ),
+            (
+                self.preserve_badge_lines,
+                "preserve_badge_lines",
+                self.single_line_badge_rows,
+                "single_line_badge_rows",
+            ),
+            (
+                self.list_code_block_separator.is_some(),
+                "list_code_block_separator",
+                self.fence_code_blocks_after_lists,
+                "fence_code_blocks_after_lists",
+            ),
+            (
+                self.reference_definitions_order.is_some(),
+                "reference_definitions_order",
+                preserve_references,
+                "reference_definitions_placement",
+            ),
+            (
+                self.blank_lines_between_reference_definitions.is_some(),
+                "blank_lines_between_reference_definitions",
+                preserve_references,
+                "reference_definitions_placement",
+            ),
+            (
+                short_indentation,
+                "fixed_indentation",
+                self.flavor == Some(Flavor::MkDocs),
+                "flavor",
+            ),
+        ];
+        let mut warnings = overrides
+            .into_iter()
+            .filter(|(is_set, _, is_overridden, _)| *is_set && *is_overridden)
+            .map(|(_, option, _, by)| ConfigWarning::Overridden { option, by })
+            .collect::<Vec<_>>();
+
+        let paragraph_width = self.max_width.or(self.paragraph_max_width);
+        if self.minimal_diff_tolerance.is_some() && paragraph_width.is_none() {
+            warnings.push(ConfigWarning::Requires {
+                option: "minimal_diff_tolerance",
+                requires: "max_width",
+            });
+        }
+        if self.max_wrapped_line_length.is_some() && paragraph_width.is_none() {
+            warnings.push(ConfigWarning::Requires {
+                option: "max_wrapped_line_length",
+                requires: "max_width",
+            });
+        }
+        if self
+            .number_headings_from
+            .is_some_and(|level| !(1..=6).contains(&level))
+        {
+            warnings.push(ConfigWarning::InvalidValue {
+                option: "number_headings_from",
+                reason: "heading levels are 1 to 6",
+            });
+        }
+        if self
+            .fixed_indentation
+            .as_ref()
+            .is_some_and(|indentation| !indentation.chars().all(|c| c == ' ' || c == '\t'))
+        {
+            warnings.push(ConfigWarning::InvalidValue {
+                option: "fixed_indentation",
+                reason: "indentation other than spaces and tabs breaks lists",
+            });
+        }
+        warnings
+    }
+}
+
+/// Problem with a [`Config`] that [`Config::warnings`] finds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigWarning {
+    /// `option` is set but has no effect because `by` takes precedence.
+    Overridden {
+        /// Name of the ignored option.
+        option: &'static str,
+        /// Name of the option taking precedence.
+        by: &'static str,
+    },
+    /// `option` is set but has no effect without `requires`.
+    Requires {
+        /// Name of the ignored option.
+        option: &'static str,
+        /// Name of the option it needs.
+        requires: &'static str,
+    },
+    /// The value of `option` cannot work as intended.
+    InvalidValue {
+        /// Name of the option.
+        option: &'static str,
+        /// Why the value does not work.
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overridden { option, by } => {
+                write!(f, "`{option}` has no effect because `{by}` is set")
+            }
+            Self::Requires { option, requires } => {
+                write!(f, "`{option}` has no effect without `{requires}`")
+            }
+            Self::InvalidValue { option, reason } => {
+                write!(f, "invalid value for `{option}`: {reason}")
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+/// Dump the event stream the formatter works on for `input`, one event per line,
+/// e.g., to attach to bug reports or to assert on in tests.
+///
+/// Events come from the parser the formatter uses, with the parser options of
+/// the default configuration, broken link recovery,
+/// and its adapters for loose lists and sequential blocks.
+/// Each line has the event indented by its nesting depth,
+/// its source byte range, and the source text in that range.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::debug_events;
+/// let dump = debug_events("- *a*\n");
+/// assert_eq!(
+///     dump,
+///     r#"Start(List(None)) 0..6 "- *a*\n"
+///   Start(Item) 0..6 "- *a*\n"
+///     Start(Emphasis) 2..5 "*a*"
+///       Text(Borrowed("a")) 3..4 "a"
+///     End(Emphasis) 2..5 "*a*"
+///   End(Item) 0..6 "- *a*\n"
+/// End(List(false)) 0..6 "- *a*\n"
+/// "#
+/// );
+/// ```
+pub fn debug_events(input: &str) -> String {
+    let (events, _) = Backend::parse(input, parser_options());
+    let mut dump = String::new();
+    let mut depth = 0_usize;
+    for (event, range) in events {
+        if matches!(event, Event::End(_)) {
+            depth = depth.saturating_sub(1);
+        }
+        let indent = "  ".repeat(depth);
+        let source = &input[range.clone()];
+        _ = writeln!(dump, "{indent}{event:?} {range:?} {source:?}");
+        if matches!(event, Event::Start(_)) {
+            depth += 1;
+        }
+    }
+    dump
+}
@@ -0,0 +1,81 @@
+//! Helpers to format only the parts of a document that changed,
+//! e.g., for "format on commit" workflows.
+use super::*;
+
+/// Parse the new-file line ranges changed in unified `diff` output,
+/// e.g., from `git diff --unified=0`,
+/// for use with [`MarkdownFormatter::format_changed_lines`].
+///
+/// Each hunk header `@@ -a,b +c,d @@` becomes the 1-based range `c..c + d`.
+/// Hunks that only delete lines become empty ranges.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::diff::changed_lines;
+/// let diff = "\
+/// --- a/README.md
+/// +++ b/README.md
+/// @@ -3 +3,2 @@ Intro
+/// -old
+/// +new
+/// +lines
+/// @@ -10,2 +11,0 @@
+/// -gone
+/// -too
+/// ";
+/// assert_eq!(changed_lines(diff), vec![3..5, 11..11]);
+/// ```
+pub fn changed_lines(diff: &str) -> Vec<Range<usize>> {
+    diff.lines()
+        .filter_map(|line| {
+            let header = line.strip_prefix("@@ -")?;
+            let (_, new) = header.split_once(" +")?;
+            let new = new.split(' ').next()?;
+            let (start, len) = match new.split_once(',') {
+                Some((start, len)) => (start.parse::<usize>().ok()?, len.parse().ok()?),
+                None => (new.parse().ok()?, 1),
+            };
+            Some(start..start + len)
+        })
+        .collect()
+}
+
+/// Byte ranges of the top-level blocks in `input`, starting at the beginning of
+/// their first line and excluding trailing whitespace.
+/// Link reference definitions are not included.
+pub(crate) fn top_level_blocks(input: &str) -> Vec<Range<usize>> {
+    let mut blocks = vec![];
+    let mut depth = 0_usize;
+    let mut block_start = 0;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        let start = match event {
+            Event::Start(_) => {
+                depth += 1;
+                if depth > 1 {
+                    continue;
+                }
+                block_start = range.start;
+                continue;
+            }
+            Event::End(_) => {
+                depth -= 1;
+                if depth > 0 {
+                    continue;
+                }
+                block_start
+            }
+            _ if depth == 0 => range.start,
+            _ => continue,
+        };
+        let start = input[..start].rfind('\n').map_or(0, |index| index + 1);
+        let end = start + input[start..range.end].trim_end().len();
+        blocks.push(start..end);
+    }
+    blocks
+}
+
+/// Whether the 1-based `changed` line range touches lines `first..=last`.
+/// Empty ranges touch the line they start at.
+pub(crate) fn touches(changed: &Range<usize>, first: usize, last: usize) -> bool {
+    let end = changed.end.max(changed.start + 1);
+    changed.start <= last && end > first
+}
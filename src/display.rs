@@ -0,0 +1,34 @@
+//! A [`Display`](std::fmt::Display) adapter to format Markdown in `format!` and `write!`.
+use super::*;
+
+/// Markdown input and the [`Config`] to format it with,
+/// formatted when displayed, e.g., by `format!` or `write!`.
+///
+/// This is a convenience, not a streaming formatter: every display formats the
+/// whole input into a new `String` with the borrowed configuration,
+/// and then writes that to the target in one piece.
+/// The whole document is needed before writing any of it
+/// because, e.g., reference definitions can move to its end.
+/// Format with [`MarkdownFormatter`] instead to keep the output.
+/// If formatting fails, e.g., because an external formatter does,
+/// the input is written as is,
+/// since failing would make `format!` and `to_string` panic.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, Formatted};
+/// let config = Config::default();
+/// let page = format!("<!-- generated -->\n{}", Formatted("#  Title", &config));
+/// assert_eq!(page, "<!-- generated -->\n# Title");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Formatted<'a>(pub &'a str, pub &'a Config);
+
+impl std::fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(input, config) = *self;
+        match <MarkdownFormatter>::format_borrowed(config, input) {
+            Ok(output) => f.write_str(&output),
+            Err(_) => f.write_str(input),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Helpers to format the Markdown in Rust doc comments,
+//! e.g., for rustfmt integration.
+use super::*;
+
+/// Decoration of a Rust doc comment,
+/// for use with [`MarkdownFormatter::format_doc_comment`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DocCommentStyle {
+    /// `///` lines, documenting the item after them.
+    OuterLine,
+    /// `//!` lines, documenting the item containing them.
+    InnerLine,
+    /// A `/** */` block, documenting the item after it.
+    OuterBlock,
+    /// A `/*! */` block, documenting the item containing it.
+    InnerBlock,
+}
+
+impl DocCommentStyle {
+    fn opener(self) -> &'static str {
+        match self {
+            Self::OuterLine => "///",
+            Self::InnerLine => "//!",
+            Self::OuterBlock => "/**",
+            Self::InnerBlock => "/*!",
+        }
+    }
+
+    fn is_block(self) -> bool {
+        matches!(self, Self::OuterBlock | Self::InnerBlock)
+    }
+}
+
+/// A doc comment split into its Markdown and decoration.
+pub(crate) struct DocComment<'a> {
+    pub(crate) markdown: String,
+    style: DocCommentStyle,
+    /// Whitespace before the comment opener.
+    indent: &'a str,
+    /// Whitespace before the lines of a block comment without leading `*`.
+    block_indent: Option<&'a str>,
+    /// Block comment on one line, e.g., `/** Docs. */`.
+    single_line: bool,
+    trailing_newline: bool,
+}
+
+impl<'a> DocComment<'a> {
+    /// Strip the decoration of `comment`,
+    /// or return `None` if it is not a doc comment of `style`.
+    pub(crate) fn parse(comment: &'a str, style: DocCommentStyle) -> Option<Self> {
+        let first_line = comment.lines().next()?;
+        let indent = &first_line[..first_line.len() - first_line.trim_start().len()];
+        let mut doc_comment = Self {
+            markdown: String::new(),
+            style,
+            indent,
+            block_indent: None,
+            single_line: false,
+            trailing_newline: comment.ends_with('\n'),
+        };
+        let lines = match style.is_block() {
+            true => doc_comment.block_lines(comment)?,
+            false => comment
+                .lines()
+                .map(|line| {
+                    let rest = line.trim_start().strip_prefix(style.opener())?;
+                    // `////` starts a regular comment.
+                    match rest.starts_with('/') {
+                        true => None,
+                        false => Some(rest.strip_prefix(' ').unwrap_or(rest)),
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?,
+        };
+        doc_comment.markdown = lines.join("\n");
+        Some(doc_comment)
+    }
+
+    fn block_lines(&mut self, comment: &'a str) -> Option<Vec<&'a str>> {
+        let body = comment
+            .trim()
+            .strip_prefix(self.style.opener())?
+            .strip_suffix("*/")?;
+        self.single_line = !body.contains('\n');
+        let mut lines = body.lines().collect::<Vec<_>>();
+        let starred = lines
+            .iter()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .all(|line| line.trim_start().starts_with('*'));
+        let indent_len = |line: &str| line.len() - line.trim_start().len();
+        let block_indent = lines
+            .iter()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .min_by_key(|line| indent_len(line))
+            .map(|line| &line[..indent_len(line)]);
+        if !starred {
+            self.block_indent = Some(block_indent.unwrap_or(self.indent));
+        }
+
+        for (index, line) in lines.iter_mut().enumerate() {
+            *line = match (index, starred) {
+                (0, _) => line.trim(),
+                (_, _) if line.trim().is_empty() => "",
+                (_, true) => {
+                    let rest = &line.trim_start()[1..];
+                    rest.strip_prefix(' ').unwrap_or(rest).trim_end()
+                }
+                (_, false) => line[block_indent.map_or(0, str::len)..].trim_end(),
+            };
+        }
+        let first = lines.iter().position(|line| !line.is_empty())?;
+        let last = lines.iter().rposition(|line| !line.is_empty())?;
+        Some(lines.drain(first..=last).collect())
+    }
+
+    /// Width the decoration takes before each line of Markdown.
+    pub(crate) fn decoration_width(&self) -> usize {
+        match (self.style.is_block(), self.block_indent) {
+            (false, _) => self.indent.len() + self.style.opener().len() + 1,
+            (true, None) => self.indent.len() + " * ".len(),
+            (true, Some(block_indent)) => block_indent.len(),
+        }
+    }
+
+    /// Decorate formatted `markdown` like this doc comment.
+    pub(crate) fn decorate(&self, markdown: &str) -> String {
+        let markdown = markdown.trim_end();
+        let Self { indent, .. } = self;
+        let opener = self.style.opener();
+        let mut result = String::with_capacity(markdown.len() * 2);
+        let write_lines = |result: &mut String, prefix: &str, separator: &str| {
+            for line in markdown.lines() {
+                match line.is_empty() {
+                    true => result.push_str(prefix.trim_end()),
+                    false => {
+                        result.push_str(prefix);
+                        result.push_str(separator);
+                        result.push_str(line);
+                    }
+                }
+                result.push('\n');
+            }
+        };
+        match (self.style.is_block(), self.block_indent) {
+            (false, _) => {
+                write_lines(&mut result, &format!("{indent}{opener}"), " ");
+                result.pop();
+            }
+            _ if self.single_line && !markdown.contains('\n') => {
+                _ = write!(result, "{indent}{opener} {markdown} */");
+            }
+            (true, None) => {
+                result.push_str(indent);
+                result.push_str(opener);
+                result.push('\n');
+                write_lines(&mut result, &format!("{indent} *"), " ");
+                _ = write!(result, "{indent} */");
+            }
+            (true, Some(block_indent)) => {
+                result.push_str(indent);
+                result.push_str(opener);
+                result.push('\n');
+                write_lines(&mut result, block_indent, "");
+                _ = write!(result, "{indent}*/");
+            }
+        }
+        if self.trailing_newline {
+            result.push('\n');
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_block_doc_comments() {
+        let format = |comment, style| {
+            <MarkdownFormatter>::default()
+                .format_doc_comment(comment, style)
+                .unwrap()
+        };
+        let starred = "    /**\n     * #  Title\n     *\n     * *  item\n     */\n";
+        assert_eq!(
+            format(starred, DocCommentStyle::OuterBlock),
+            "    /**\n     * # Title\n     *\n     * * item\n     */\n"
+        );
+        let unstarred = "/*!\n  Crate   docs.\n\n      indented code\n*/";
+        assert_eq!(
+            format(unstarred, DocCommentStyle::InnerBlock),
+            "/*!\n  Crate   docs.\n\n      indented code\n*/"
+        );
+        assert_eq!(
+            format("/**  *Docs*  */", DocCommentStyle::OuterBlock),
+            "/** *Docs* */"
+        );
+        assert!(
+            <MarkdownFormatter>::default()
+                .format_doc_comment("//// Not docs.", DocCommentStyle::OuterLine)
+                .is_err()
+        );
+    }
+}
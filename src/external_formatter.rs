@@ -2,12 +2,17 @@ use super::*;
 
 mod default;
 mod fn_based;
+#[cfg(feature = "yaml")]
+mod yaml;
 
 pub use {
     default::{DefaultFormatterCombination, Paragraph, PreservingBuffer, TrimTo4Indent},
     fn_based::{FnFormatter, FormatterFn},
 };
 
+#[cfg(feature = "yaml")]
+pub use yaml::YamlFrontMatter;
+
 /// A formatter buffer we write non-Markdown string into.
 pub trait ExternalFormatter: Write {
     /// Make a new instance based on the given [`BufferType`], maximum width,
@@ -22,6 +27,25 @@ pub trait ExternalFormatter: Write {
 
     /// Consume Self and return the formatted buffer.
     fn into_buffer(self) -> String;
+
+    /// Called right after [`ExternalFormatter::new`] with the width of
+    /// the indentation each line of the formatted buffer will be prefixed with,
+    /// e.g., for list items and block quotes.
+    /// The maximum width given to `new` already excludes this indentation.
+    fn set_indentation(&mut self, _indent: usize) {}
+
+    /// Called right after [`ExternalFormatter::new`] in minimal-diff mode,
+    /// i.e., with [`Config::minimal_diff_tolerance`](crate::Config::minimal_diff_tolerance),
+    /// with how many columns past the maximum width existing lines may run.
+    /// Formatters that wrap text should then keep the line breaks of the input
+    /// and only re-wrap lines longer than that.
+    fn set_line_break_tolerance(&mut self, _tolerance: usize) {}
+
+    /// Called right after [`ExternalFormatter::new`] for paragraphs
+    /// if [`Config::shortcodes`](crate::Config::shortcodes) is set,
+    /// with the pattern of the spans that must stay on one line.
+    /// Formatters that wrap text should never break lines inside them.
+    fn set_unbreakable_spans(&mut self, _pattern: &Regex) {}
 }
 
 /// Type of the string being written to a [`ExternalFormatter`].
@@ -40,6 +64,11 @@ pub enum BufferType<'a> {
     HtmlBlock,
     /// String in a paragraph.
     Paragraph,
+    /// Metadata block, e.g., YAML front matter.
+    MetadataBlock {
+        /// Whether the block is delimited by `---` (YAML) or `+++` (TOML).
+        kind: MetadataBlockKind,
+    },
 }
 
 impl<'a> BufferType<'a> {
@@ -50,6 +79,7 @@ impl<'a> BufferType<'a> {
             Self::DisplayMath => FormattingContext::DisplayMath,
             Self::HtmlBlock => FormattingContext::HtmlBlock,
             Self::Paragraph => FormattingContext::Paragraph,
+            Self::MetadataBlock { .. } => FormattingContext::MetadataBlock,
         }
     }
 }
@@ -65,14 +95,18 @@ pub enum FormattingContext {
     HtmlBlock,
     /// A paragraph.
     Paragraph,
+    /// A metadata block.
+    MetadataBlock,
 }
 
 /// A convenience combination of
 /// external formatters implementing [`ExternalFormatter`],
 /// using one [`ExternalFormatter`] for each of code block (`C`),
-/// display math (`D`), HTML block (`H`), and paragraph (`P`) formatting.
+/// display math (`D`), HTML block (`H`), paragraph (`P`),
+/// and metadata block (`M`) formatting.
+/// Metadata blocks are preserved as is by default.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub enum FormatterCombination<C, D, H, P> {
+pub enum FormatterCombination<C, D, H, P, M = PreservingBuffer> {
     /// Inner code block formatter.
     CodeBlock(C),
     /// Inner display math formatter.
@@ -81,14 +115,17 @@ pub enum FormatterCombination<C, D, H, P> {
     HtmlBlock(H),
     /// Inner paragraph formatter.
     Paragraph(P),
+    /// Inner metadata block formatter.
+    MetadataBlock(M),
 }
 
-impl<C, D, H, P> Write for FormatterCombination<C, D, H, P>
+impl<C, D, H, P, M> Write for FormatterCombination<C, D, H, P, M>
 where
     C: Write,
     D: Write,
     H: Write,
     P: Write,
+    M: Write,
 {
     fn write_str(&mut self, s: &str) -> std::fmt::Result {
         match self {
@@ -96,16 +133,18 @@ where
             Self::DisplayMath(d) => d.write_str(s),
             Self::HtmlBlock(h) => h.write_str(s),
             Self::Paragraph(p) => p.write_str(s),
+            Self::MetadataBlock(m) => m.write_str(s),
         }
     }
 }
 
-impl<C, D, H, P> ExternalFormatter for FormatterCombination<C, D, H, P>
+impl<C, D, H, P, M> ExternalFormatter for FormatterCombination<C, D, H, P, M>
 where
     C: ExternalFormatter,
     D: ExternalFormatter,
     H: ExternalFormatter,
     P: ExternalFormatter,
+    M: ExternalFormatter,
 {
     fn new(buffer_type: BufferType, max_width: Option<usize>, capacity: usize) -> Self {
         match buffer_type {
@@ -115,6 +154,9 @@ where
             BufferType::DisplayMath => Self::DisplayMath(D::new(buffer_type, max_width, capacity)),
             BufferType::HtmlBlock => Self::HtmlBlock(H::new(buffer_type, max_width, capacity)),
             BufferType::Paragraph => Self::Paragraph(P::new(buffer_type, max_width, capacity)),
+            BufferType::MetadataBlock { .. } => {
+                Self::MetadataBlock(M::new(buffer_type, max_width, capacity))
+            }
         }
     }
 
@@ -124,6 +166,7 @@ where
             Self::DisplayMath(d) => d.is_empty(),
             Self::HtmlBlock(h) => h.is_empty(),
             Self::Paragraph(p) => p.is_empty(),
+            Self::MetadataBlock(m) => m.is_empty(),
         }
     }
 
@@ -133,6 +176,7 @@ where
             Self::DisplayMath(d) => d.context(),
             Self::HtmlBlock(h) => h.context(),
             Self::Paragraph(p) => p.context(),
+            Self::MetadataBlock(m) => m.context(),
         }
     }
 
@@ -142,6 +186,37 @@ where
             Self::DisplayMath(d) => d.into_buffer(),
             Self::HtmlBlock(h) => h.into_buffer(),
             Self::Paragraph(p) => p.into_buffer(),
+            Self::MetadataBlock(m) => m.into_buffer(),
+        }
+    }
+
+    fn set_indentation(&mut self, indent: usize) {
+        match self {
+            Self::CodeBlock(c) => c.set_indentation(indent),
+            Self::DisplayMath(d) => d.set_indentation(indent),
+            Self::HtmlBlock(h) => h.set_indentation(indent),
+            Self::Paragraph(p) => p.set_indentation(indent),
+            Self::MetadataBlock(m) => m.set_indentation(indent),
+        }
+    }
+
+    fn set_line_break_tolerance(&mut self, tolerance: usize) {
+        match self {
+            Self::CodeBlock(c) => c.set_line_break_tolerance(tolerance),
+            Self::DisplayMath(d) => d.set_line_break_tolerance(tolerance),
+            Self::HtmlBlock(h) => h.set_line_break_tolerance(tolerance),
+            Self::Paragraph(p) => p.set_line_break_tolerance(tolerance),
+            Self::MetadataBlock(m) => m.set_line_break_tolerance(tolerance),
+        }
+    }
+
+    fn set_unbreakable_spans(&mut self, pattern: &Regex) {
+        match self {
+            Self::CodeBlock(c) => c.set_unbreakable_spans(pattern),
+            Self::DisplayMath(d) => d.set_unbreakable_spans(pattern),
+            Self::HtmlBlock(h) => h.set_unbreakable_spans(pattern),
+            Self::Paragraph(p) => p.set_unbreakable_spans(pattern),
+            Self::MetadataBlock(m) => m.set_unbreakable_spans(pattern),
         }
     }
 }
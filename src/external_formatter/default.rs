@@ -1,3 +1,5 @@
+use textwrap::core::Word;
+
 use super::*;
 
 /// A default [`ExternalFormatter`].
@@ -48,22 +50,20 @@ const MARKDOWN_HARD_BREAK: &str = "  \n";
 pub struct Paragraph {
     buffer: String,
     max_width: Option<usize>,
+    /// Columns past `max_width` existing lines may run in minimal-diff mode.
+    line_break_tolerance: Option<usize>,
+    /// Pattern of the spans never to break lines in.
+    unbreakable_spans: Option<Regex>,
 }
 
 impl Write for Paragraph {
     fn write_str(&mut self, s: &str) -> std::fmt::Result {
-        let is_hard_break = |s: &str| -> bool {
-            // Hard breaks can have any amount of leading whitesace followed by a newline
-            s.strip_prefix("  ")
-                .is_some_and(|maybe_hard_break| maybe_hard_break.trim_start_matches(' ').eq("\n"))
-        };
-
         if self.max_width.is_some() && is_hard_break(s) {
             self.buffer.push_str(MARKDOWN_HARD_BREAK);
             return Ok(());
         }
 
-        if self.max_width.is_some() && s.trim().is_empty() {
+        if self.max_width.is_some() && self.line_break_tolerance.is_none() && s.trim().is_empty() {
             // If the user configured the max_width then push a space so we can reflow text
             self.buffer.push(' ');
         } else {
@@ -80,6 +80,8 @@ impl ExternalFormatter for Paragraph {
         Self {
             max_width,
             buffer: String::with_capacity(capacity),
+            line_break_tolerance: None,
+            unbreakable_spans: None,
         }
     }
 
@@ -99,6 +101,34 @@ impl ExternalFormatter for Paragraph {
             return rewrite_buffer;
         };
 
+        match &self.unbreakable_spans {
+            Some(pattern) => {
+                let (protected, replaced) = protect_spans(&rewrite_buffer, pattern);
+                restore_spans(&self.wrap(protected, max_width), &replaced)
+            }
+            None => self.wrap(rewrite_buffer, max_width),
+        }
+    }
+
+    fn set_line_break_tolerance(&mut self, tolerance: usize) {
+        self.line_break_tolerance = Some(tolerance);
+    }
+
+    fn set_unbreakable_spans(&mut self, pattern: &Regex) {
+        self.unbreakable_spans = Some(pattern.clone());
+    }
+}
+
+impl Paragraph {
+    fn wrap(&self, rewrite_buffer: String, max_width: usize) -> String {
+        if let Some(tolerance) = self.line_break_tolerance {
+            return rewrap_long_lines(
+                &rewrite_buffer,
+                max_width,
+                max_width.saturating_add(tolerance),
+            );
+        }
+
         let all_lines_with_max_width = rewrite_buffer.lines().all(|l| l.len() <= max_width);
 
         if all_lines_with_max_width {
@@ -108,10 +138,7 @@ impl ExternalFormatter for Paragraph {
 
         let mut output_buffer = String::with_capacity(rewrite_buffer.capacity());
 
-        let wrap_options = TextWrapOptions::new(max_width)
-            .break_words(false)
-            .word_separator(textwrap::WordSeparator::AsciiSpace)
-            .wrap_algorithm(textwrap::WrapAlgorithm::FirstFit);
+        let wrap_options = wrap_options(max_width);
 
         let mut split_on_hard_breaks = rewrite_buffer.split(MARKDOWN_HARD_BREAK).peekable();
 
@@ -128,6 +155,178 @@ impl ExternalFormatter for Paragraph {
     }
 }
 
+/// Check if `s` is a hard break of trailing whitespace,
+/// which is normalized to [`MARKDOWN_HARD_BREAK`] when wrapping.
+/// Hard breaks are at least two spaces or tabs followed by a line ending,
+/// which may be `\r\n`.
+fn is_hard_break(s: &str) -> bool {
+    let Some(whitespace) = s.strip_suffix('\n') else {
+        return false;
+    };
+    let whitespace = whitespace.strip_suffix('\r').unwrap_or(whitespace);
+    whitespace.len() >= 2 && whitespace.bytes().all(|b| matches!(b, b' ' | b'\t'))
+}
+
+/// Stand-in for the spaces in unbreakable spans while wrapping,
+/// which neither `split_words` nor `textwrap` break lines at.
+const UNBREAKABLE_SPACE: char = '\u{A0}';
+
+/// Replace the spaces in the spans of `text` that `pattern` matches
+/// with [`UNBREAKABLE_SPACE`],
+/// returning whether each [`UNBREAKABLE_SPACE`] in the result replaced a space.
+fn protect_spans(text: &str, pattern: &Regex) -> (String, Vec<bool>) {
+    let mut protected = String::with_capacity(text.len());
+    let mut replaced = vec![];
+    let mut written = 0;
+    let mut push = |protected: &mut String, part: &str, in_span: bool| {
+        for char in part.chars() {
+            match char {
+                ' ' if in_span => {
+                    protected.push(UNBREAKABLE_SPACE);
+                    replaced.push(true);
+                }
+                UNBREAKABLE_SPACE => {
+                    protected.push(char);
+                    replaced.push(false);
+                }
+                _ => protected.push(char),
+            }
+        }
+    };
+    for span in pattern.find_iter(text) {
+        push(&mut protected, &text[written..span.start()], false);
+        push(&mut protected, span.as_str(), true);
+        written = span.end();
+    }
+    push(&mut protected, &text[written..], false);
+    (protected, replaced)
+}
+
+/// Undo [`protect_spans`] on `text` given which [`UNBREAKABLE_SPACE`]s `replaced` spaces.
+fn restore_spans(text: &str, replaced: &[bool]) -> String {
+    let mut replaced = replaced.iter();
+    text.chars()
+        .map(|char| match char {
+            UNBREAKABLE_SPACE if replaced.next() == Some(&true) => ' ',
+            _ => char,
+        })
+        .collect()
+}
+
+fn wrap_options(max_width: usize) -> TextWrapOptions<'static> {
+    TextWrapOptions::new(max_width)
+        .break_words(false)
+        .word_separator(textwrap::WordSeparator::Custom(split_words))
+        .word_splitter(textwrap::WordSplitter::Custom(hyphen_split_points))
+        .wrap_algorithm(textwrap::WrapAlgorithm::FirstFit)
+}
+
+/// Keep the line breaks of `text`, only re-wrapping lines longer than `limit`
+/// to `max_width`.
+fn rewrap_long_lines(text: &str, max_width: usize, limit: usize) -> String {
+    let mut output_buffer = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches('\n');
+        if content.trim_end_matches(' ').len() <= limit {
+            output_buffer.push_str(line);
+            continue;
+        }
+        let hard_break = match content.ends_with("  ") {
+            true => MARKDOWN_HARD_BREAK,
+            false => &line[content.len()..],
+        };
+        output_buffer.push_str(&textwrap::fill(content, wrap_options(max_width)));
+        output_buffer.push_str(hard_break);
+    }
+    output_buffer
+}
+
+/// Split a line into words at ASCII spaces, like [`textwrap::WordSeparator::AsciiSpace`],
+/// but never inside spans that must stay on one line:
+/// - Attribute groups attached to images and links, e.g., `![alt](img.png){width=100}`.
+/// - Inline math, e.g., `$a + b$`, which some renderers no longer recognize once broken.
+/// - Images and links around images, e.g., badges like `[![build status](b.svg)](ci)`.
+/// - Literal backslashes and the words after them, e.g., `C:\ drive`,
+///   as a backslash at the end of a line would be a hard break.
+fn split_words(line: &str) -> Box<dyn Iterator<Item = Word<'_>> + '_> {
+    let mut words = vec![];
+    let mut word_start = 0;
+    let mut protected_until = None;
+    let mut image_end = 0;
+    let mut char_indices = line.char_indices().peekable();
+    while let Some((index, char)) = char_indices.next() {
+        match protected_until {
+            Some(closer) if char == closer && !line[..index].ends_with('\\') => {
+                protected_until = None
+            }
+            Some(_) => {}
+            None if index < image_end => {}
+            None if ["![", "[!["].iter().any(|s| line[index..].starts_with(s))
+                && !line[..index].ends_with('\\') =>
+            {
+                image_end = image_span_len(&line[index..]).map_or(0, |len| index + len);
+            }
+            None if char == '{' && line[..index].ends_with([')', ']']) => {
+                protected_until = Some('}');
+            }
+            None if char == '$' && !line[..index].ends_with('\\') => protected_until = Some('$'),
+            None if char == ' ' && !ends_with_literal_backslash(&line[..index]) => {
+                while let Some((_, ' ')) = char_indices.peek() {
+                    char_indices.next();
+                }
+                let word_end = char_indices.peek().map_or(line.len(), |(index, _)| *index);
+                words.push(Word::from(&line[word_start..word_end]));
+                word_start = word_end;
+            }
+            None => {}
+        }
+    }
+    if word_start < line.len() {
+        words.push(Word::from(&line[word_start..]));
+    }
+    Box::new(words.into_iter())
+}
+
+/// Check if `text` ends with a backslash that escapes nothing before it,
+/// i.e., an odd number of backslashes.
+fn ends_with_literal_backslash(text: &str) -> bool {
+    (text.len() - text.trim_end_matches('\\').len()) % 2 == 1
+}
+
+/// Split words at hyphens like [`textwrap::WordSplitter::HyphenSplitter`],
+/// except for links and images, whose destinations a line break would cut.
+fn hyphen_split_points(word: &str) -> Vec<usize> {
+    match word.contains("](") {
+        true => vec![],
+        false => textwrap::WordSplitter::HyphenSplitter.split_points(word),
+    }
+}
+
+/// The length of the image, or link around images, that `text` starts with,
+/// i.e., up to where its brackets and parentheses are balanced
+/// and no destination or label follows,
+/// or `None` if they never balance.
+fn image_span_len(text: &str) -> Option<usize> {
+    let mut depth = 0_usize;
+    let mut escaped = false;
+    for (index, char) in text.char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' | '(' => depth += 1,
+            ']' | ')' => {
+                depth = depth.checked_sub(1)?;
+                let rest = &text[index + 1..];
+                if depth == 0 && !rest.starts_with(['(', '[']) {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
 /// A buffer that trims each line's leading spaces down to a multiple of 4.
 pub struct TrimTo4Indent {
     buffer: String,
@@ -176,3 +375,27 @@ impl ExternalFormatter for TrimTo4Indent {
         self.buffer
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hard_breaks_with_tabs_and_carriage_returns() {
+        for hard_break in ["  \n", "    \n", "  \r\n", " \t\n", "\t\t\r\n"] {
+            assert!(is_hard_break(hard_break), "{hard_break:?}");
+        }
+        for not_hard_break in ["\n", "\r\n", " \n", "\t\n", "  ", "\\\n", "a \n"] {
+            assert!(!is_hard_break(not_hard_break), "{not_hard_break:?}");
+        }
+    }
+
+    #[test]
+    fn normalize_hard_breaks_when_wrapping() {
+        let mut paragraph = Paragraph::new(BufferType::Paragraph, Some(80), 0);
+        for s in ["one", " \t\n", "two", "\r\n", "three", "  \r\n", "four"] {
+            paragraph.write_str(s).unwrap();
+        }
+        assert_eq!(paragraph.into_buffer(), "one  \ntwo three  \nfour");
+    }
+}
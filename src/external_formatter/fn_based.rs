@@ -1,13 +1,16 @@
 use super::*;
 
-/// A formatting function `F` that takes the buffer type, optional maximum width,
-/// and the string to format, and returns the formatted string.
+/// A formatting function `F` that takes the buffer type, optional available width,
+/// indentation width, and the string to format, and returns the formatted string.
 pub trait FormatterFn: Default {
     /// Format the input string based on the configuration.
+    /// `available_width` is the maximum width minus the `indent` the formatted
+    /// lines will be prefixed with, e.g., inside list items and block quotes.
     fn format(
         &mut self,
         buffer_type: BufferType,
-        max_width: Option<usize>,
+        available_width: Option<usize>,
+        indent: usize,
         input: String,
     ) -> String;
 }
@@ -22,6 +25,7 @@ where
     buffer: String,
     buffer_type: BufferType<'static>,
     max_width: Option<usize>,
+    indent: usize,
     formatter_fn: F,
 }
 
@@ -47,11 +51,13 @@ where
             BufferType::DisplayMath => BufferType::DisplayMath,
             BufferType::HtmlBlock => BufferType::HtmlBlock,
             BufferType::Paragraph => BufferType::Paragraph,
+            BufferType::MetadataBlock { kind } => BufferType::MetadataBlock { kind },
         };
         Self {
             buffer: String::with_capacity(capacity),
             buffer_type,
             max_width,
+            indent: 0,
             formatter_fn: F::default(),
         }
     }
@@ -66,6 +72,82 @@ where
 
     fn into_buffer(mut self) -> String {
         self.formatter_fn
-            .format(self.buffer_type, self.max_width, self.buffer)
+            .format(self.buffer_type, self.max_width, self.indent, self.buffer)
+    }
+
+    fn set_indentation(&mut self, indent: usize) {
+        self.indent = indent;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::{Config, MarkdownFormatter, PreservingBuffer, TrimTo4Indent};
+
+    /// Replace each code block with its available width and indentation.
+    #[derive(Default)]
+    struct ReportWidths;
+
+    impl FormatterFn for ReportWidths {
+        fn format(
+            &mut self,
+            _buffer_type: BufferType,
+            available_width: Option<usize>,
+            indent: usize,
+            _input: String,
+        ) -> String {
+            format!("{available_width:?} {indent}\n")
+        }
+    }
+
+    /// Separate the lines of each HTML block with blank lines.
+    #[derive(Default)]
+    struct DoubleSpace;
+
+    impl FormatterFn for DoubleSpace {
+        fn format(
+            &mut self,
+            _buffer_type: BufferType,
+            _available_width: Option<usize>,
+            _indent: usize,
+            input: String,
+        ) -> String {
+            input.lines().join("\n\n") + "\n"
+        }
+    }
+
+    #[test]
+    fn keeps_html_blocks_that_would_not_parse_as_one() {
+        type Formatter = FormatterCombination<
+            PreservingBuffer,
+            TrimTo4Indent,
+            FnFormatter<DoubleSpace>,
+            PreservingBuffer,
+        >;
+        let input = "<div>\n# Not a heading\n</div>\n\n<!--\ncomment\n-->\n";
+        let expected = "<div>\n# Not a heading\n</div>\n\n<!--\n\ncomment\n\n-->\n";
+        let output = MarkdownFormatter::<Formatter>::with_config(Config::sichanghe_opinion())
+            .format(input)
+            .unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn passes_available_width_and_indent() {
+        type Formatter = FormatterCombination<
+            FnFormatter<ReportWidths>,
+            TrimTo4Indent,
+            TrimTo4Indent,
+            PreservingBuffer,
+        >;
+        let input = "```\ncode\n```\n\n> - ```\n>   code\n>   ```\n";
+        let expected = "```\nSome(80) 0\n```\n\n> - ```\n>   Some(76) 4\n>   ```\n";
+        let output = MarkdownFormatter::<Formatter>::with_config(Config::sichanghe_opinion())
+            .format(input)
+            .unwrap();
+        assert_eq!(output, expected);
     }
 }
@@ -0,0 +1,296 @@
+use super::*;
+
+/// A metadata block formatter for YAML front matter.
+/// Re-indents nested YAML with two spaces per level,
+/// collapses extra spaces after `key:` and `-`,
+/// and, if `SORT_KEYS` is `true`, sorts top-level keys alphabetically.
+///
+/// Comments move together with the key below them.
+/// Blank lines between top-level keys are dropped when sorting.
+/// Block scalars (`|` and `>`) are shifted as a whole so their content is kept.
+/// TOML front matter, and YAML this formatter does not understand,
+/// e.g., block scalars with explicit indentation indicators or top-level sequences,
+/// are preserved as is.
+///
+/// Use it as the `M` in a [`FormatterCombination`]:
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::*;
+/// type Formatter = FormatterCombination<
+///     PreservingBuffer,
+///     TrimTo4Indent,
+///     TrimTo4Indent,
+///     Paragraph,
+///     YamlFrontMatter<true>,
+/// >;
+/// let input = "---\ntitle:   Hi\nauthor:\n    name: Me\n---\n# Hi\n";
+/// let output = MarkdownFormatter::<Formatter>::with_config(
+///     Config::default(),
+/// )
+/// .format(input)
+/// .unwrap();
+/// assert_eq!(output, "---\nauthor:\n  name: Me\ntitle: Hi\n---\n# Hi\n");
+/// ```
+pub struct YamlFrontMatter<const SORT_KEYS: bool = false> {
+    buffer: String,
+    context: FormattingContext,
+    is_yaml: bool,
+}
+
+impl<const SORT_KEYS: bool> Write for YamlFrontMatter<SORT_KEYS> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.buffer.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const SORT_KEYS: bool> ExternalFormatter for YamlFrontMatter<SORT_KEYS> {
+    fn new(buffer_type: BufferType, _max_width: Option<usize>, capacity: usize) -> Self {
+        tracing::trace!(?buffer_type, capacity, "YamlFrontMatter::new");
+        Self {
+            buffer: String::with_capacity(capacity),
+            context: buffer_type.to_formatting_context(),
+            is_yaml: matches!(
+                buffer_type,
+                BufferType::MetadataBlock {
+                    kind: MetadataBlockKind::YamlStyle
+                }
+            ),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    fn context(&self) -> FormattingContext {
+        self.context
+    }
+
+    fn into_buffer(self) -> String {
+        if !self.is_yaml {
+            return self.buffer;
+        }
+        let Some(mut output) = normalize_indentation(&self.buffer) else {
+            return self.buffer;
+        };
+        if SORT_KEYS {
+            if let Some(sorted) = sort_top_level_keys(&output) {
+                output = sorted;
+            }
+        }
+        if self.buffer.ends_with('\n') {
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// A block scalar being re-indented as a whole.
+struct BlockScalar {
+    /// Source indentation of the line introducing the block scalar.
+    parent_indent: usize,
+    /// Indentation to give the least indented content line.
+    target_indent: usize,
+    /// Source indentation of the first content line.
+    content_indent: Option<usize>,
+}
+
+/// Re-indent `yaml` with two spaces per nesting level, without trailing newline.
+/// Return `None` if the indentation cannot be safely rewritten.
+fn normalize_indentation(yaml: &str) -> Option<String> {
+    let mut output = String::with_capacity(yaml.len());
+    let mut levels: Vec<usize> = vec![];
+    let mut block_scalar: Option<BlockScalar> = None;
+    for (index, line) in yaml.lines().enumerate() {
+        if index > 0 {
+            output.push('\n');
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if line[indent..].starts_with('\t') {
+            return None;
+        }
+        if let Some(scalar) = &mut block_scalar {
+            if indent > scalar.parent_indent {
+                let content_indent = *scalar.content_indent.get_or_insert(indent);
+                let extra = indent.checked_sub(content_indent)?;
+                push_indented(&mut output, scalar.target_indent + extra, &line[indent..]);
+                continue;
+            }
+            block_scalar = None;
+        }
+        let content = &line[indent..];
+        if content.starts_with('#') {
+            // Comments do not affect the structure.
+            output.push_str(line);
+            continue;
+        }
+        while levels.last().is_some_and(|level| *level > indent) {
+            levels.pop();
+        }
+        if levels.last() != Some(&indent) {
+            levels.push(indent);
+        }
+        let target_indent = (levels.len() - 1) * 2;
+        let (n_dashes, content) = normalize_spacing(content);
+        push_indented(&mut output, target_indent, &content);
+        match block_scalar_indicator(&content) {
+            Some(indicator) if indicator.contains(|c: char| c.is_ascii_digit()) => return None,
+            Some(_) => {
+                block_scalar = Some(BlockScalar {
+                    parent_indent: indent,
+                    target_indent: target_indent + 2 * n_dashes + 2,
+                    content_indent: None,
+                })
+            }
+            None => {}
+        }
+    }
+    Some(output)
+}
+
+fn push_indented(output: &mut String, indent: usize, content: &str) {
+    output.extend(std::iter::repeat(' ').take(indent));
+    output.push_str(content);
+}
+
+/// Collapse the spaces after leading sequence dashes and after a plain `key:`.
+/// Return the number of leading sequence dashes and the rewritten content.
+fn normalize_spacing(content: &str) -> (usize, String) {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    let mut n_dashes = 0;
+    while let Some(after_dash) = rest.strip_prefix("- ") {
+        output.push_str("- ");
+        rest = after_dash.trim_start_matches(' ');
+        n_dashes += 1;
+    }
+    match rest.split_once(": ") {
+        Some((key, value)) if is_plain_key(key) => {
+            output.push_str(key);
+            output.push_str(": ");
+            output.push_str(value.trim_start_matches(' '));
+        }
+        _ => output.push_str(rest),
+    }
+    (n_dashes, output)
+}
+
+fn is_plain_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with([' ', '-'])
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' '))
+}
+
+/// The block scalar indicator ending `content`, e.g., `|`, `>-`, or `|2`.
+fn block_scalar_indicator(content: &str) -> Option<&str> {
+    let content = match content.split_once(" #") {
+        Some((content, _comment)) => content,
+        None => content,
+    }
+    .trim_end();
+    let (before, indicator) = content.rsplit_once(' ').unwrap_or(("", content));
+    let is_indicator = indicator.starts_with(['|', '>'])
+        && indicator[1..]
+            .chars()
+            .all(|c| c == '+' || c == '-' || c.is_ascii_digit());
+    let is_value = before.is_empty() || before.ends_with(':') || before.ends_with('-');
+    (is_indicator && is_value).then_some(indicator)
+}
+
+/// The top-level key `line` introduces, if it is a top-level mapping entry.
+fn top_level_key(line: &str) -> Option<&str> {
+    if line.starts_with([
+        ' ', '-', '?', '[', '{', '#', '&', '*', '!', '|', '>', '%', '.',
+    ]) {
+        return None;
+    }
+    let key = match line.split_once(": ") {
+        Some((key, _)) => key,
+        None => line.strip_suffix(':')?,
+    };
+    (!key.is_empty()).then_some(key)
+}
+
+/// Stable-sort the top-level entries of the normalized `yaml` by key.
+/// Return `None` if `yaml` is not a top-level mapping.
+fn sort_top_level_keys(yaml: &str) -> Option<String> {
+    let mut entries: Vec<(&str, Vec<&str>)> = vec![];
+    // Blank and top-level comment lines not yet known to belong to an entry.
+    let mut pending: Vec<&str> = vec![];
+    for line in yaml.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            pending.push(line);
+        } else if line.starts_with(' ') {
+            let (_, lines) = entries.last_mut()?;
+            lines.append(&mut pending);
+            lines.push(line);
+        } else {
+            let key = top_level_key(line)?;
+            let mut lines: Vec<&str> = pending.drain(..).filter(|l| !l.is_empty()).collect();
+            lines.push(line);
+            entries.push((key, lines));
+        }
+    }
+    entries.sort_by_key(|(key, _)| *key);
+    let trailing_comments = pending.into_iter().filter(|l| !l.is_empty());
+    let lines = entries
+        .into_iter()
+        .flat_map(|(_, lines)| lines)
+        .chain(trailing_comments);
+    Some(lines.collect::<Vec<_>>().join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn format<const SORT_KEYS: bool>(yaml: &str) -> String {
+        let kind = MetadataBlockKind::YamlStyle;
+        let mut formatter =
+            YamlFrontMatter::<SORT_KEYS>::new(BufferType::MetadataBlock { kind }, None, 0);
+        formatter.write_str(yaml).unwrap();
+        formatter.into_buffer()
+    }
+
+    #[test]
+    fn normalizes_indentation_and_spacing() {
+        let yaml = "title:    Hello  \nauthor:\n    name:  Me\n    links:\n        -   a\n        -   b\ntags:\n- x\n-   key:  1\n    other: 2\n";
+        let expected = "title: Hello\nauthor:\n  name: Me\n  links:\n    - a\n    - b\ntags:\n- x\n- key: 1\n  other: 2\n";
+        assert_eq!(format::<false>(yaml), expected);
+    }
+
+    #[test]
+    fn shifts_block_scalars_as_a_whole() {
+        let yaml = "a:\n    b: |\n        line\n\n            indented\n    c: >-\n      folded\n";
+        let expected = "a:\n  b: |\n    line\n\n        indented\n  c: >-\n    folded\n";
+        assert_eq!(format::<false>(yaml), expected);
+        let yaml = "a: |2\n   x\n";
+        assert_eq!(format::<false>(yaml), yaml);
+    }
+
+    #[test]
+    fn sorts_top_level_keys() {
+        let yaml = "title: Hi\n\n# The author.\nauthor:\n  name: Me\n\n  # Contact.\n  email: a@b.c\ndate: 2024-01-01\n";
+        let expected = "# The author.\nauthor:\n  name: Me\n\n  # Contact.\n  email: a@b.c\ndate: 2024-01-01\ntitle: Hi\n";
+        assert_eq!(format::<true>(yaml), expected);
+        let yaml = "- b\n- a\n";
+        assert_eq!(format::<true>(yaml), yaml);
+    }
+
+    #[test]
+    fn preserves_toml() {
+        let kind = MetadataBlockKind::PlusesStyle;
+        let mut formatter =
+            YamlFrontMatter::<true>::new(BufferType::MetadataBlock { kind }, None, 0);
+        let toml = "b  =  1\na = 2\n";
+        formatter.write_str(toml).unwrap();
+        assert_eq!(formatter.into_buffer(), toml);
+    }
+}
@@ -0,0 +1,192 @@
+//! Deduplication of the top-level footnote definitions of a document,
+//! e.g., after merging documents or copying text with its footnotes.
+use super::*;
+
+/// A top-level footnote definition.
+struct FootnoteDefinition {
+    /// Whole lines of the definition, including the last newline.
+    range: Range<usize>,
+    /// The label as written, to rewrite references with.
+    label: String,
+    /// Normalized label for matching.
+    key: String,
+    /// The content after the label, with the indentation of each line removed.
+    body: String,
+}
+
+/// The content of the footnote definition `text` after its `[^label]:`,
+/// with each line trimmed so that differently indented copies match.
+fn definition_body(text: &str) -> String {
+    let body = text.split_once("]:").map_or(text, |(_, body)| body);
+    body.lines().map(str::trim).join("\n").trim().to_owned()
+}
+
+/// The top-level footnote definitions of `input`, and the label and range
+/// of each footnote reference.
+fn footnotes(input: &str) -> (Vec<FootnoteDefinition>, Vec<(String, Range<usize>)>) {
+    let mut definitions = vec![];
+    let mut references = vec![];
+    let mut depth = 0_usize;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        match &event {
+            Event::Start(Tag::FootnoteDefinition(label)) if depth == 0 => {
+                // The parser includes the blank lines after it.
+                let end = range.start + input[range.clone()].trim_end().len();
+                let range = sections::whole_lines(input, range.start..end);
+                definitions.push(FootnoteDefinition {
+                    body: definition_body(&input[range.clone()]),
+                    range,
+                    label: label.to_string(),
+                    key: links::normalize_label(label),
+                });
+            }
+            Event::FootnoteReference(label) => {
+                references.push((links::normalize_label(label), range));
+            }
+            _ => {}
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    (definitions, references)
+}
+
+/// Later definitions of a label already defined with a different body.
+fn conflicts(definitions: &[FootnoteDefinition]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for (index, definition) in definitions.iter().enumerate() {
+        let earlier = &definitions[..index];
+        let defined = earlier.iter().any(|other| other.key == definition.key);
+        let same_body = earlier
+            .iter()
+            .any(|other| other.key == definition.key && other.body == definition.body);
+        if defined && !same_body {
+            diagnostics.push(Diagnostic {
+                range: definition.range.clone(),
+                kind: DiagnosticKind::ConflictingFootnote {
+                    label: definition.label.clone(),
+                },
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Report the footnote definitions of `input` whose label is already defined
+/// with a different body, see [`lint::conflicting_footnotes`].
+pub(crate) fn conflicting_footnotes(input: &str) -> Vec<Diagnostic> {
+    conflicts(&footnotes(input).0)
+}
+
+/// Remove the top-level footnote definitions of `input` repeating an earlier
+/// definition of the same label with the same body.
+/// If `unify_bodies`, also remove definitions with the same body as an earlier
+/// one under another label, pointing their references to the earlier label.
+/// Labels defined with different bodies are never unified,
+/// and are reported like [`conflicting_footnotes`].
+pub(crate) fn dedup_footnotes(input: &str, unify_bodies: bool) -> (String, Vec<Diagnostic>) {
+    let (definitions, references) = footnotes(input);
+    let diagnostics = conflicts(&definitions);
+    let conflicting = |key: &str| {
+        definitions
+            .iter()
+            .filter(|definition| definition.key == key)
+            .map(|definition| &definition.body)
+            .unique()
+            .count()
+            > 1
+    };
+
+    let mut removed: Vec<Range<usize>> = vec![];
+    // Labels of removed definitions and the labels to refer to instead.
+    let mut renamed: Vec<(&str, &str)> = vec![];
+    for (index, definition) in definitions.iter().enumerate() {
+        let earlier = &definitions[..index];
+        let duplicate = earlier
+            .iter()
+            .any(|other| other.key == definition.key && other.body == definition.body);
+        if !duplicate {
+            if !unify_bodies || conflicting(&definition.key) {
+                continue;
+            }
+            let Some(other) = earlier.iter().find(|other| {
+                other.key != definition.key
+                    && other.body == definition.body
+                    && !conflicting(&other.key)
+            }) else {
+                continue;
+            };
+            renamed.push((&definition.key, &other.label));
+        }
+        // Also remove the blank lines after it.
+        let rest = &input[definition.range.end..];
+        let blank = &rest[..rest.len() - rest.trim_start_matches(['\n', '\r', ' ']).len()];
+        let blank_lines = blank.rfind('\n').map_or(0, |index| index + 1);
+        removed.push(definition.range.start..definition.range.end + blank_lines);
+    }
+
+    let mut edits = removed
+        .iter()
+        .map(|range| (range.clone(), String::new()))
+        .collect_vec();
+    for (key, range) in references {
+        // References in removed definitions go with them.
+        if removed.iter().any(|removed| removed.contains(&range.start)) {
+            continue;
+        }
+        if let Some((_, label)) = renamed.iter().find(|(renamed, _)| *renamed == key) {
+            edits.push((range, format!("[^{label}]")));
+        }
+    }
+    (apply_edits(input, edits), diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dedup_keeps_conflicting_labels() {
+        let input = "\
+Text[^a][^b][^c][^d].
+
+[^a]: Same.
+
+[^b]: Conflicts.
+
+[^c]:
+    Same.
+
+[^b]: Conflicting[^d].
+
+[^d]: Conflicts.
+";
+        let (output, diagnostics) = dedup_footnotes(input, true);
+        assert_eq!(
+            output,
+            "\
+Text[^a][^b][^a][^d].
+
+[^a]: Same.
+
+[^b]: Conflicts.
+
+[^b]: Conflicting[^d].
+
+[^d]: Conflicts.
+"
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| &input[diagnostic.range.clone()])
+                .collect_vec(),
+            ["[^b]: Conflicting[^d].\n"]
+        );
+        let (output, _) = dedup_footnotes(input, false);
+        assert_eq!(output, input);
+    }
+}
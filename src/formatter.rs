@@ -1,5 +1,3 @@
-use pulldown_cmark::MetadataBlockKind;
-
 use super::*;
 
 mod formatting_states;
@@ -14,86 +12,395 @@ where
     ///
     /// ```rust
     /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
-    /// let formatter = MarkdownFormatter::default();
+    /// let formatter = <MarkdownFormatter>::default();
     /// let input = "   #  Header! ";
     /// let rewrite = formatter.format(input).unwrap();
     /// assert_eq!(rewrite, String::from("# Header!"));
     /// ```
     pub fn format(self, input: &str) -> Result<String, std::fmt::Error> {
-        // callback that will always revcover broken links
-        let mut callback = |broken_link| {
-            tracing::trace!("found boken link: {broken_link:?}");
-            Some(("".into(), "".into()))
-        };
+        if self.config.fix_anchor_links {
+            self.format_with_diagnostics(input)
+                .map(|(output, _)| output)
+        } else {
+            self.rewrite(input).map(|(output, _)| output)
+        }
+    }
 
-        let mut options = Options::all();
-        options.remove(Options::ENABLE_SMART_PUNCTUATION);
-
-        let parser = Parser::new_with_broken_link_callback(input, options, Some(&mut callback));
-
-        // There can't be any characters besides spaces, tabs, or newlines after the title
-        // See https://spec.commonmark.org/0.30/#link-reference-definition for the
-        // definition and https://spec.commonmark.org/0.30/#example-209 as an example.
-        //
-        // It seems that `pulldown_cmark` sometimes parses titles when it shouldn't.
-        // To work around edge cases where a paragraph starting with a quoted string might be
-        // interpreted as a link title we check that only whitespace follows the title
-        let is_false_title = |input: &str, span: Range<usize>| {
-            input[span.end..]
-                .chars()
-                .take_while(|c| *c != '\n')
-                .any(|c| !c.is_whitespace())
-        };
+    /// Format Markdown input and report problems found along the way,
+    /// e.g., links broken because formatting changed a heading's anchor.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let formatter = <MarkdownFormatter>::default();
+    /// let (rewrite, diagnostics) = formatter.format_with_diagnostics("#  Header!").unwrap();
+    /// assert_eq!(rewrite, String::from("# Header!"));
+    /// assert!(diagnostics.is_empty());
+    /// ```
+    pub fn format_with_diagnostics(
+        self,
+        input: &str,
+    ) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        let fix_anchor_links = self.config.fix_anchor_links;
+        let slug_style = self.config.slug_style.unwrap_or(SlugStyle::GitHub);
+        let (output, mut diagnostics) = self.rewrite(input)?;
+        let (output, anchor_diagnostics) =
+            anchor::fix_changed_anchors(input, output, fix_anchor_links, slug_style);
+        diagnostics.extend(anchor_diagnostics);
+        Ok((output, diagnostics))
+    }
 
-        let reference_links = parser
-            .reference_definitions()
-            .iter()
-            .sorted_by(|(_, link_a), (_, link_b)| {
-                // We want to sort these in descending order based on the ranges
-                // This creates a stack of reference links that we can pop off of.
-                link_b.span.start.cmp(&link_a.span.start)
+    /// Format Markdown input, showing the text of each paragraph to `inspector`
+    /// once it is formatted.
+    /// See [`TextInspector`] for an example.
+    pub fn format_with_inspector(
+        self,
+        input: &str,
+        inspector: &mut dyn TextInspector,
+    ) -> Result<String, std::fmt::Error> {
+        let fix_anchor_links = self.config.fix_anchor_links;
+        let slug_style = self.config.slug_style.unwrap_or(SlugStyle::GitHub);
+        let (output, _) = self.rewrite_inspected(input, Some(inspector))?;
+        Ok(match fix_anchor_links {
+            true => anchor::fix_changed_anchors(input, output, true, slug_style).0,
+            false => output,
+        })
+    }
+
+    /// Format each document of `docs` lazily, e.g., the many small comments
+    /// a service formats.
+    /// The documents share this formatter's configuration, prepared once,
+    /// instead of setting up a formatter per document.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::{FormatError, MarkdownFormatter};
+    /// let comments = ["#  One", "*  two", "Three  "];
+    /// let rewrites = <MarkdownFormatter>::default()
+    ///     .format_many(comments)
+    ///     .collect::<Result<Vec<_>, FormatError>>()
+    ///     .unwrap();
+    /// assert_eq!(rewrites, ["# One", "* two", "Three"]);
+    /// ```
+    pub fn format_many<'a>(
+        self,
+        docs: impl IntoIterator<Item = &'a str> + 'a,
+    ) -> impl Iterator<Item = Result<String, FormatError>> + 'a
+    where
+        E: 'a,
+    {
+        let fix_anchor_links = self.config.fix_anchor_links;
+        let slug_style = self.config.slug_style.unwrap_or(SlugStyle::GitHub);
+        let config = self.config.without_line_changes().with_ascii_replacements();
+        docs.into_iter().enumerate().map(move |(index, doc)| {
+            let (output, _) =
+                Self::rewrite_prepared(&config, doc, None).map_err(|_| FormatError { index })?;
+            Ok(match fix_anchor_links {
+                true => anchor::fix_changed_anchors(doc, output, true, slug_style).0,
+                false => output,
             })
-            // TODO: Fix typo.
-            .map(|(link_lable, link_def)| {
-                let (dest, title, span) = (&link_def.dest, &link_def.title, &link_def.span);
-                let full_link = &input[span.clone()];
-                if title.is_some() && is_false_title(input, span.clone()) {
-                    let end = input[span.clone()]
-                        .find(dest.as_ref())
-                        .map(|idx| idx + dest.len())
-                        .unwrap_or(span.end);
-                    return (
-                        link_lable.to_string(),
-                        dest.to_string(),
-                        None,
-                        span.start..end,
-                    );
-                }
-
-                if let Some((url, title)) = links::recover_escaped_link_destination_and_title(
-                    full_link,
-                    link_lable,
-                    title.is_some(),
-                ) {
-                    (link_lable.to_string(), url, title, span.clone())
-                } else {
-                    // Couldn't recover URL from source, just use what we've been given
-                    (
-                        link_lable.to_string(),
-                        dest.to_string(),
-                        title.clone().map(|s| (s.to_string(), '"')),
-                        span.clone(),
-                    )
-                }
+        })
+    }
+
+    /// Format only the top-level blocks of `input` that touch `changed_lines`,
+    /// leaving the rest of the document as is.
+    ///
+    /// `changed_lines` are 1-based line ranges with exclusive ends,
+    /// e.g., from [`diff::changed_lines`].
+    /// Empty ranges touch the line they start at.
+    /// Adjacent changed blocks are formatted together,
+    /// so the blank lines between them are normalized too.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let input = "#  Untouched\n\n*  changed\n*  list\n\n#  Untouched\n";
+    /// let rewrite = <MarkdownFormatter>::default()
+    ///     .format_changed_lines(input, &[4..5])
+    ///     .unwrap();
+    /// assert_eq!(rewrite, "#  Untouched\n\n* changed\n* list\n\n#  Untouched\n");
+    /// ```
+    pub fn format_changed_lines(
+        self,
+        input: &str,
+        changed_lines: &[Range<usize>],
+    ) -> Result<String, std::fmt::Error> {
+        let line_of = |offset: usize| input[..offset].matches('\n').count() + 1;
+        let blocks = diff::top_level_blocks(input);
+        let is_changed = |block: &Range<usize>| {
+            let (first, last) = (line_of(block.start), line_of(block.end));
+            changed_lines
+                .iter()
+                .any(|changed| diff::touches(changed, first, last))
+        };
+
+        let mut spans: Vec<Range<usize>> = vec![];
+        let mut previous_changed = false;
+        for block in blocks {
+            let changed = is_changed(&block);
+            match spans.last_mut() {
+                Some(span) if changed && previous_changed => span.end = block.end,
+                _ if changed => spans.push(block),
+                _ => {}
+            }
+            previous_changed = changed;
+        }
+
+        let edits = spans
+            .into_iter()
+            .map(|span| {
+                let formatted = self.clone().format(&input[span.clone()])?;
+                Ok((span, formatted))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<_, _>>()?;
+        Ok(apply_edits(input, edits))
+    }
+
+    /// Rearrange the top-level sections of `input` whose heading titles are in
+    /// `order` into that order, and format the result,
+    /// e.g., to enforce a fixed section order in READMEs.
+    ///
+    /// Sections are the headings outside of containers at the shallowest level
+    /// with a title in `order`, matched ignoring case,
+    /// with the blocks up to the next heading at the same or a shallower level.
+    /// Sections not in `order` keep their positions,
+    /// and sections separated by a shallower heading are rearranged separately.
+    /// Link reference and footnote definitions in a section move
+    /// to the first section using them.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let input = "# Tool\n\n## License\n\nMIT\n\n## Usage\n\nSee [docs].\n\n## Install\n\n\
+    ///              Run it.\n\n[docs]: https://example.com\n";
+    /// let rewrite = <MarkdownFormatter>::default()
+    ///     .reorder_sections(input, &["Install", "Usage", "License"])
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     rewrite,
+    ///     "# Tool\n\n## Install\n\nRun it.\n\n## Usage\n\nSee [docs].\n\n\
+    ///      [docs]: https://example.com\n\n## License\n\nMIT\n"
+    /// );
+    /// ```
+    pub fn reorder_sections(self, input: &str, order: &[&str]) -> Result<String, std::fmt::Error> {
+        self.format(&sections::reorder_sections(input, order))
+    }
+
+    /// Insert a stub section for each `(title, stub)` in `templates` whose title
+    /// has no top-level heading in `input`, and format the result,
+    /// e.g., to add the sections a repository requires in READMEs.
+    ///
+    /// Each stub section goes after the section of the previous title in
+    /// `templates`, or before that of the next title, or at the end,
+    /// at the level of the existing sections, see [`lint::missing_sections`].
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let input = "# Tool\n\n## Usage\n\nRun it.\n";
+    /// let rewrite = <MarkdownFormatter>::default()
+    ///     .insert_missing_sections(input, &[("Install", "TODO"), ("Usage", ""), ("License", "MIT")])
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     rewrite,
+    ///     "# Tool\n\n## Install\n\nTODO\n\n## Usage\n\nRun it.\n\n## License\n\nMIT\n"
+    /// );
+    /// ```
+    pub fn insert_missing_sections(
+        self,
+        input: &str,
+        templates: &[(&str, &str)],
+    ) -> Result<String, std::fmt::Error> {
+        self.format(&sections::insert_missing_sections(input, templates))
+    }
+
+    /// Remove the top-level footnote definitions repeating an earlier definition
+    /// of the same label with the same body, and format the result,
+    /// e.g., after merging documents.
+    /// If `unify_bodies`, footnotes with the same body as an earlier one
+    /// under another label are also removed, and their references point to
+    /// the earlier label instead.
+    /// Labels defined with different bodies are kept and reported,
+    /// see [`lint::conflicting_footnotes`].
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let input = "A[^a], B[^b].\n\n[^a]: Same.\n\n[^a]: Same.\n\n[^b]: Same.\n";
+    /// let (rewrite, diagnostics) = <MarkdownFormatter>::default()
+    ///     .dedup_footnotes(input, true)
+    ///     .unwrap();
+    /// assert_eq!(rewrite, "A[^a], B[^a].\n\n[^a]: Same.\n");
+    /// assert!(diagnostics.is_empty());
+    /// ```
+    pub fn dedup_footnotes(
+        self,
+        input: &str,
+        unify_bodies: bool,
+    ) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        let (deduplicated, diagnostics) = footnotes::dedup_footnotes(input, unify_bodies);
+        Ok((self.format(&deduplicated)?, diagnostics))
+    }
 
-        let iter = parser
-            .into_offset_iter()
-            .all_loose_lists()
-            .all_sequential_blocks();
+    /// Format `text` as inline Markdown to insert into a larger document,
+    /// e.g., a table cell, normalizing its emphasis markers, escapes,
+    /// and links like in paragraphs, but without wrapping it.
+    /// Its lines are joined by spaces,
+    /// and the start of what would otherwise begin a block,
+    /// e.g., a heading or a list item, is escaped,
+    /// while leading HTML is kept as inline HTML.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter};
+    /// let formatter = <MarkdownFormatter>::with_config(Config::sichanghe_opinion());
+    /// let rewrite = formatter.clone().format_inline("See  _the_\n[docs]( https://example.com )").unwrap();
+    /// assert_eq!(rewrite, "See  *the* [docs](https://example.com)");
+    /// assert_eq!(formatter.format_inline("1. not a list").unwrap(), "1\\. not a list");
+    /// ```
+    pub fn format_inline(self, text: &str) -> Result<String, std::fmt::Error> {
+        let mut line = text.lines().map(str::trim).join(" ").trim().to_owned();
+        // Link reference definitions have no events.
+        let first_event = Parser::new_ext(&line, parser_options()).next();
+        // Leading HTML, e.g., `<span>`, is inline HTML in a larger document.
+        let inline = matches!(
+            first_event,
+            Some(Event::Start(Tag::Paragraph | Tag::HtmlBlock))
+        );
+        if !line.is_empty() && !inline {
+            // Escape the block marker, e.g., `#`, `-`, or the `.` of `1.`.
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if line[digits..].starts_with(|c: char| c.is_ascii_punctuation()) {
+                line.insert(digits, '\\');
+            }
+        }
+        let config = Config {
+            max_width: None,
+            paragraph_max_width: None,
+            ..self.config
+        };
+        let formatted = Self::with_config(config).format(&line)?;
+        Ok(formatted.trim_end().to_owned())
+    }
+
+    /// Format the Markdown in Rust doc `comment` decorated in `style`,
+    /// keeping the decoration and indentation.
+    /// The maximum widths include the decoration.
+    /// Return an error if `comment` is not a doc comment of `style`.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter, doc_comment::DocCommentStyle};
+    /// let comment = "    /// Format the input,\n    /// or fail.\n    ///\n    /// +  Fast\n";
+    /// let rewrite = <MarkdownFormatter>::with_config(Config {
+    ///     max_width: Some(30),
+    ///     ..Default::default()
+    /// })
+    /// .format_doc_comment(comment, DocCommentStyle::OuterLine)
+    /// .unwrap();
+    /// assert_eq!(rewrite, "    /// Format the input, or\n    /// fail.\n    ///\n    /// + Fast\n");
+    /// ```
+    pub fn format_doc_comment(
+        self,
+        comment: &str,
+        style: DocCommentStyle,
+    ) -> Result<String, std::fmt::Error> {
+        let doc_comment = DocComment::parse(comment, style).ok_or(std::fmt::Error)?;
+        let decoration_width = doc_comment.decoration_width();
+        let mut config = self.config;
+        for width in [
+            &mut config.max_width,
+            &mut config.paragraph_max_width,
+            &mut config.code_block_max_width,
+        ] {
+            *width = width.map(|width| width.saturating_sub(decoration_width));
+        }
+        let markdown = Self::with_config(config).format(&doc_comment.markdown)?;
+        Ok(doc_comment.decorate(&markdown))
+    }
 
-        let fmt_state = <FormatState<E, _>>::new(input, self.config, iter, reference_links);
+    /// Format the Markdown cells of Jupyter `notebook` JSON,
+    /// e.g., the content of an `.ipynb` file,
+    /// preserving all other bytes, including the JSON layout.
+    /// Return an error if `notebook` is not valid notebook JSON.
+    ///
+    /// ```rust
+    /// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+    /// let notebook = r##"{"cells": [{"cell_type": "markdown", "source": ["#  Title"]}]}"##;
+    /// let rewrite = <MarkdownFormatter>::default().format_notebook(notebook).unwrap();
+    /// assert_eq!(rewrite, r##"{"cells": [{"cell_type": "markdown", "source": ["# Title"]}]}"##);
+    /// ```
+    #[cfg(feature = "notebook")]
+    pub fn format_notebook(self, notebook: &str) -> Result<String, std::fmt::Error> {
+        let sources = notebook::markdown_sources(notebook).ok_or(std::fmt::Error)?;
+        let mut edits = vec![];
+        for (range, text) in sources {
+            let formatted = self.clone().format(&text)?;
+            if formatted != text {
+                edits.push((
+                    range.clone(),
+                    notebook::source_json(&formatted, &notebook[range]),
+                ));
+            }
+        }
+        Ok(apply_edits(notebook, edits))
+    }
+
+    /// Format `input` like [`MarkdownFormatter::format`] with a borrowed `config`,
+    /// which is only cloned if preparing it for formatting changes it.
+    pub(crate) fn format_borrowed(config: &Config, input: &str) -> Result<String, std::fmt::Error> {
+        let slug_style = config.slug_style.unwrap_or(SlugStyle::GitHub);
+        let (output, _) = Self::rewrite_prepared(&config.prepared(), input, None)?;
+        Ok(match config.fix_anchor_links {
+            true => anchor::fix_changed_anchors(input, output, true, slug_style).0,
+            false => output,
+        })
+    }
+
+    fn rewrite(self, input: &str) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        self.rewrite_inspected(input, None)
+    }
+
+    fn rewrite_inspected(
+        self,
+        input: &str,
+        inspector: Option<&mut dyn TextInspector>,
+    ) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        let config = self.config.without_line_changes().with_ascii_replacements();
+        Self::rewrite_prepared(&config, input, inspector)
+    }
+
+    /// Format `input` with `config` already prepared for [`FormatState`].
+    fn rewrite_prepared(
+        config: &Config,
+        input: &str,
+        inspector: Option<&mut dyn TextInspector>,
+    ) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        if config.preserve_source {
+            return Ok((utils::trim_trailing_whitespace(input), vec![]));
+        }
+        let mut source = Cow::Borrowed(input);
+        if let Some(tab_width) = config.tab_width {
+            if let Some(expanded) = utils::expand_indentation_tabs(&source, tab_width) {
+                source = expanded.into();
+            }
+        }
+        for transform in &config.transforms {
+            if let Some(transformed) = transform.apply_to_source(&source) {
+                source = transformed.into();
+            }
+        }
+        let (events, reference_links) = Backend::parse(&source, config.parser_options());
+        let mut fmt_state = <FormatState<E, _>>::new(&source, config, events, reference_links);
+        // Shorten the inspector's lifetime to that of the transformed source.
+        fmt_state.text_inspector = inspector.map(|inspector| inspector as &mut dyn TextInspector);
         fmt_state.format()
     }
 }
+
+/// Error formatting one of the documents of [`MarkdownFormatter::format_many`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatError {
+    /// Index of the document among the documents formatted.
+    pub index: usize,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot format document {}", self.index)
+    }
+}
+
+impl std::error::Error for FormatError {}
@@ -1,11 +1,12 @@
 use super::*;
 
+mod details;
 mod format;
 mod helpers;
+mod indentation;
 
 pub(crate) use helpers::*;
-
-type ReferenceLinkDefinition = (String, String, Option<(String, char)>, Range<usize>);
+use indentation::{Indentation, intern};
 
 pub(crate) struct FormatState<'i, E, I>
 where
@@ -20,15 +21,19 @@ where
     rewrite_buffer: String,
     /// Handles code block, HTML block, and paragraph formatting.
     external_formatter: Option<E>,
-    /// Stack that keeps track of nested list markers.
-    /// Unordered list markers are one of `*`, `+`, or `-`,
-    /// while ordered lists markers start with 0-9 digits followed by a `.` or `)`.
-    // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-    // list_markers: Vec<ListMarker>,
+    /// Stack that keeps track of the number of the last item written
+    /// in each open list, if it is ordered.
+    list_numbers: Vec<Option<usize>>,
+    /// Stack of the marker to write the items of each open list with, if it is unordered.
+    unordered_list_markers: Vec<Option<UnorderedListMarker>>,
+    /// Depth in `nested_context`, rewrite buffer length after it, and marker of
+    /// the last unordered list written, so a list right after it uses another marker.
+    last_unordered_list: Option<(usize, usize, UnorderedListMarker)>,
     /// Stack that keeps track of indentation.
-    indentation: Vec<Cow<'static, str>>,
+    indentation: Indentation,
     /// Stack that keeps track of whether we're formatting inside of another element.
-    nested_context: Vec<Tag<'i>>,
+    /// Inline so that documents nesting only a few levels deep never allocate it.
+    nested_context: SmallVec<[Tag<'i>; 8]>,
     /// A set of reference link definitions that will be output after formatting.
     /// Reference style links contain 3 parts:
     /// 1. Text to display
@@ -38,17 +43,85 @@ where
     /// [title]: link "optional title"
     /// ```
     reference_links: Vec<ReferenceLinkDefinition>,
+    /// Reference link definitions moved to the end of the document, in source order.
+    moved_reference_links: Vec<ReferenceLinkDefinition>,
+    /// Source ranges of bare URLs and email addresses to write as explicit links,
+    /// with the destination of `www.` links.
+    bare_autolinks: Vec<(std::ops::Range<usize>, Option<String>)>,
     /// keep track of the current setext header.
     /// ```markdown
     /// Header
     /// ======
     /// ```
     setext_header: Option<&'i str>,
-    /// Store the fragment identifier and classes from the header start tag.
-    header_id_and_classes: Option<(Option<CowStr<'i>>, Vec<CowStr<'i>>)>,
+    /// Where the text of the current setext heading starts in the rewrite buffer.
+    setext_heading_start: usize,
+    /// The current setext heading is written for an ATX heading
+    /// because of `heading_style`.
+    setext_heading_from_atx: bool,
+    /// Store the fragment identifier, classes, and attributes from the header start tag.
+    heading_attributes: Option<HeadingAttributes<'i>>,
+    /// Hierarchical number of the last numbered heading, e.g., `[2, 1]` for `2.1`.
+    heading_numbers: Vec<usize>,
+    /// A heading number was just written, so the next event should replace
+    /// the heading's existing number and separate the text from the number.
+    after_heading_number: bool,
+    /// Position in the rewrite buffer right after the opening fence of
+    /// the current code block if it has no info string to infer.
+    bare_fence_info_position: Option<usize>,
+    /// `Some` if the current code block is longer than `code_block_buffer_limit`,
+    /// so its text is written straight to the rewrite buffer,
+    /// with whether the newline after the last line written is still pending.
+    code_block_written_through: Option<bool>,
+    /// The fence to write the current or next indented code block with,
+    /// if it follows a list and `fence_code_blocks_after_lists` is set.
+    indented_code_block_fence: Option<String>,
+    /// The fence to close the current fenced code block with,
+    /// if `code_fence_style` or `code_fence_min_length` changes it.
+    code_fence: Option<String>,
+    /// For each open list, the rewrite buffer length before it
+    /// until it has an item that is not dropped by `drop_empty_list_items`.
+    list_starts_without_items: Vec<Option<usize>>,
+    /// Most newlines before the empty list items dropped since the last kept item.
+    dropped_item_newlines: usize,
+    /// For each open list item if `drop_empty_list_items` is set.
+    item_starts: Vec<ItemStart>,
+    /// Source ranges of the text `shortcodes` matches.
+    shortcodes: Vec<std::ops::Range<usize>>,
+    /// Source range of the last shortcode written as is.
+    written_shortcode: std::ops::Range<usize>,
+    /// Source offset of the paragraph being formatted.
+    paragraph_start: usize,
+    /// The paragraph line being formatted matches `protected_lines`.
+    in_protected_line: bool,
+    /// The paragraph being formatted is kept as is, e.g., a badge row,
+    /// so its links stay where they are.
+    in_kept_paragraph: bool,
+    /// Source column of the lines of the paragraph being formatted
+    /// that are not indented past its first line,
+    /// if it is a line block kept because of `preserve_line_blocks`.
+    line_block_column: Option<usize>,
+    /// A link moved to its own line has ended, but its line goes on until
+    /// the next whitespace, e.g., to keep punctuation after the link.
+    ending_own_line_link: bool,
+    /// Indentation of the current list item's text, put aside while
+    /// its first paragraph's continuation lines align under the marker.
+    item_text_indentation: Option<Cow<'static, str>>,
+    /// Source offsets of the ordered list items with the width to zero-pad
+    /// their numbers to, if `zero_pad_to_widest_number`.
+    widest_list_numbers: Vec<(usize, usize)>,
+    /// Source offsets of the items of task lists, if `bullet_for_task_lists`.
+    task_list_items: Vec<usize>,
+    /// The text written to the current HTML block's formatter, to fall back to
+    /// if the formatted block would not parse as one HTML block.
+    html_block_text: String,
+    /// The list item paragraph just written is followed by a nested list.
+    list_after_item_paragraph: bool,
     /// next Start event should push indentation
     needs_indent: bool,
     table_state: Option<TableState<'i>>,
+    /// Transforms of the [`transform::MARKER`] comment before the next table.
+    table_transforms: Vec<Transform>,
     last_position: usize,
     trim_link_or_image_start: bool,
     /// Force write into rewrite buffer.
@@ -56,7 +129,13 @@ where
     // out-of-order problem.
     force_rewrite_buffer: bool,
     /// Format configurations
-    config: Config,
+    config: &'i Config,
+    /// Stack of `tracing` spans of the blocks being formatted.
+    block_spans: Vec<tracing::Span>,
+    /// Problems found while formatting, e.g., paragraphs too long to wrap.
+    diagnostics: Vec<Diagnostic>,
+    /// Shown the text of each paragraph once formatted.
+    pub(crate) text_inspector: Option<&'i mut dyn TextInspector>,
 }
 
 /// Depnding on the formatting context there are a few different buffers where we might want to
@@ -67,6 +146,9 @@ where
     E: ExternalFormatter,
 {
     fn write_str(&mut self, text: &str) -> std::fmt::Result {
+        if !self.force_rewrite_buffer && self.in_html_block() {
+            self.html_block_text.push_str(text);
+        }
         if let Some(writer) = self.current_buffer() {
             tracing::trace!(text, "write_str");
             writer.write_str(text)?
@@ -75,6 +157,9 @@ where
     }
 
     fn write_fmt(&mut self, args: std::fmt::Arguments<'_>) -> std::fmt::Result {
+        if !self.force_rewrite_buffer && self.in_html_block() {
+            return self.write_str(&args.to_string());
+        }
         if let Some(writer) = self.current_buffer() {
             writer.write_fmt(args)?
         }
@@ -89,7 +174,7 @@ where
 {
     pub(crate) fn new(
         input: &'i str,
-        config: Config,
+        config: &'i Config,
         iter: I,
         reference_links: Vec<ReferenceLinkDefinition>,
     ) -> Self {
@@ -97,36 +182,114 @@ where
             input,
             last_was_softbreak: false,
             events: iter.peekable(),
-            rewrite_buffer: String::with_capacity(input.len() * 2),
+            rewrite_buffer: String::with_capacity(match config.code_block_buffer_limit {
+                Some(_) => input.len(),
+                None => input.len() * 2,
+            }),
             external_formatter: None,
-            // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-            // list_markers: vec![],
-            indentation: vec![],
-            nested_context: vec![],
+            list_numbers: vec![],
+            unordered_list_markers: vec![],
+            last_unordered_list: None,
+            indentation: Indentation::default(),
+            nested_context: SmallVec::new(),
             reference_links,
+            moved_reference_links: vec![],
+            bare_autolinks: match config.explicit_autolinks {
+                true => links::bare_autolinks(input, config.parser_options()),
+                false => vec![],
+            },
             setext_header: None,
-            header_id_and_classes: None,
+            setext_heading_start: 0,
+            setext_heading_from_atx: false,
+            heading_attributes: None,
+            heading_numbers: vec![],
+            after_heading_number: false,
+            bare_fence_info_position: None,
+            code_block_written_through: None,
+            indented_code_block_fence: None,
+            code_fence: None,
+            list_starts_without_items: vec![],
+            dropped_item_newlines: 0,
+            item_starts: vec![],
+            shortcodes: match &config.shortcodes {
+                Some(pattern) => pattern.find_iter(input).map(|m| m.range()).collect(),
+                None => vec![],
+            },
+            written_shortcode: 0..0,
+            paragraph_start: 0,
+            in_protected_line: false,
+            in_kept_paragraph: false,
+            line_block_column: None,
+            ending_own_line_link: false,
+            item_text_indentation: None,
+            widest_list_numbers: match config.zero_pad_to_widest_number {
+                true => list::widest_list_numbers(input, config),
+                false => vec![],
+            },
+            task_list_items: match config.bullet_for_task_lists {
+                Some(_) => list::task_list_items(input, config.parser_options()),
+                None => vec![],
+            },
+            html_block_text: String::new(),
+            list_after_item_paragraph: false,
             needs_indent: false,
             table_state: None,
+            table_transforms: vec![],
             last_position: 0,
             trim_link_or_image_start: false,
             force_rewrite_buffer: false,
             config,
+            block_spans: vec![],
+            diagnostics: vec![],
+            text_inspector: None,
         }
     }
 
     /// The main entry point for markdown formatting.
-    pub fn format(mut self) -> Result<String, std::fmt::Error> {
-        while let Some((event, range)) = self.events.next() {
-            self.format_one_event(event, range)?;
+    pub fn format(mut self) -> Result<(String, Vec<Diagnostic>), std::fmt::Error> {
+        loop {
+            self.split_block_quote()?;
+            let Some((event, range)) = self.events.next() else {
+                break;
+            };
+            if self.merges_block_quote(&event, &range) {
+                // Skip the end of this block quote and the start of the next.
+                self.events.next();
+                continue;
+            }
+            let mut span = self
+                .block_spans
+                .last()
+                .cloned()
+                .unwrap_or_else(tracing::Span::none);
+            if let Some(kind) = block_start_kind(&event) {
+                span = span.in_scope(|| {
+                    tracing::debug_span!("block", kind, ?range, depth = self.block_spans.len())
+                });
+                self.block_spans.push(span.clone());
+            }
+            let is_block_end = is_block_end(&event);
+            span.in_scope(|| self.format_one_event(event, range))?;
+            if is_block_end {
+                self.block_spans.pop();
+            }
         }
         debug_assert!(self.nested_context.is_empty());
-        let trailing_newline = self.input.ends_with('\n');
+        let input = self.input;
+        let (leading_newlines, trailing_newlines) = match self.config.preserve_line_numbers {
+            true => (
+                input[..input.len() - input.trim_start().len()]
+                    .matches('\n')
+                    .count(),
+                input[input.trim_end().len()..].matches('\n').count(),
+            ),
+            false => (0, usize::from(input.ends_with('\n'))),
+        };
+        let diagnostics = std::mem::take(&mut self.diagnostics);
         self.rewrite_final_reference_links().map(|mut output| {
-            if trailing_newline {
-                output.push('\n');
-            }
-            output
+            output.insert_str(0, &"\n".repeat(leading_newlines));
+            output.push_str(&"\n".repeat(trailing_newlines));
+            (output, diagnostics)
         })
     }
 }
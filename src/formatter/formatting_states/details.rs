@@ -0,0 +1,117 @@
+use super::*;
+
+/// A `<details>` HTML block whose inner Markdown is not separated from
+/// the `<summary>` by a blank line.
+/// ```markdown
+/// <details>
+/// <summary>Head</summary>
+/// Inner *Markdown*
+/// </details>
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct DetailsBlock<'a> {
+    /// Lines up to and including the closing `</summary>` tag.
+    head: &'a str,
+    /// Markdown between the summary and the closing `</details>` tag.
+    inner: &'a str,
+    /// The closing `</details>` tag, if it is part of the same HTML block.
+    tail: Option<&'a str>,
+}
+
+impl<'i, E, I> FormatState<'i, E, I>
+where
+    E: ExternalFormatter,
+    I: Iterator<Item = (Event<'i>, std::ops::Range<usize>)>,
+{
+    /// Write the `<details>` HTML block with its inner Markdown formatted.
+    /// Consume the HTML block's text events up to its end event.
+    pub(crate) fn write_details_block(&mut self, capacity: usize) -> std::fmt::Result {
+        let html = self.take_html_block_text(capacity);
+        let Some(details) = split_details_block(&html) else {
+            // Write the HTML block as usual.
+            self.new_external_formatted(BufferType::HtmlBlock, capacity)?;
+            return self.write_str(&html);
+        };
+
+        let inner = self.format_nested_markdown(details.inner)?;
+
+        let mut rewrite = String::with_capacity(self.buffer_capacity(capacity));
+        rewrite.push_str(details.head.trim_end());
+        rewrite.push_str("\n\n");
+        rewrite.push_str(inner.trim_end());
+        if let Some(tail) = details.tail {
+            rewrite.push_str("\n\n");
+            rewrite.push_str(tail.trim());
+        }
+
+        self.write_nested_markdown(&rewrite)
+    }
+}
+
+/// Split a `<details>` HTML block into its head, inner Markdown, and tail.
+/// Return `None` if the HTML block does not contain inner Markdown.
+pub(crate) fn split_details_block(html: &str) -> Option<DetailsBlock<'_>> {
+    let html = html.trim_end();
+    let first_line = html.lines().next()?.trim_start().to_ascii_lowercase();
+    if !first_line.starts_with("<details") {
+        return None;
+    }
+
+    let summary_end = html.to_ascii_lowercase().find("</summary>")? + "</summary>".len();
+    let head_end = html[summary_end..]
+        .find('\n')
+        .map_or(html.len(), |index| summary_end + index + 1);
+    if !html[summary_end..head_end].trim().is_empty() {
+        // Something follows the summary on the same line.
+        return None;
+    }
+    let (head, rest) = html.split_at(head_end);
+
+    let (inner, tail) = match rest.rfind('\n') {
+        Some(index) if rest[index..].trim().eq_ignore_ascii_case("</details>") => {
+            (&rest[..index], Some(&rest[index + 1..]))
+        }
+        None if rest.trim().eq_ignore_ascii_case("</details>") => ("", Some(rest)),
+        _ => (rest, None),
+    };
+    if inner.trim().is_empty() {
+        return None;
+    }
+
+    Some(DetailsBlock { head, inner, tail })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_details_blocks() {
+        assert_eq!(
+            split_details_block("<details>\n<summary>Head</summary>\nInner\n</details>\n"),
+            Some(DetailsBlock {
+                head: "<details>\n<summary>Head</summary>\n",
+                inner: "Inner",
+                tail: Some("</details>"),
+            })
+        );
+        assert_eq!(
+            split_details_block("<details><summary>Head</summary>\n* a\n* b"),
+            Some(DetailsBlock {
+                head: "<details><summary>Head</summary>\n",
+                inner: "* a\n* b",
+                tail: None,
+            })
+        );
+        // Already in the layout GitHub renders as Markdown.
+        assert_eq!(
+            split_details_block("<details>\n<summary>Head</summary>\n"),
+            None
+        );
+        assert_eq!(split_details_block("<details>\n</details>"), None);
+        assert_eq!(
+            split_details_block("<div>\n<summary>Head</summary>\nInner"),
+            None
+        );
+    }
+}
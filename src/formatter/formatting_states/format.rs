@@ -8,29 +8,86 @@ where
     pub(crate) fn format_one_event(
         &mut self,
         event: Event<'i>,
-        range: Range<usize>,
+        mut range: Range<usize>,
     ) -> std::fmt::Result {
-        let mut last_position = self.input[..range.end]
-            .char_indices()
-            .rev()
-            .find(|(_, char)| !char.is_whitespace())
-            .map(|(index, _)| index)
-            .unwrap_or(0);
-        tracing::debug!(?event, ?range, last_position);
+        if std::mem::take(&mut self.ending_own_line_link)
+            && !self.end_own_line_link_line(&event, &mut range)?
+        {
+            return Ok(());
+        }
+        if std::mem::take(&mut self.after_heading_number) {
+            match event {
+                Event::Text(_) => {
+                    let text = &self.input[range.clone()];
+                    match strip_heading_number(text, self.heading_numbers.len()) {
+                        Some("") => {
+                            // Only the old number. Separate the number from what follows.
+                            self.after_heading_number = true;
+                            return Ok(());
+                        }
+                        Some(rest) => range.start = range.end - rest.len(),
+                        None => {}
+                    }
+                    self.write_str(" ")?;
+                }
+                Event::End(TagEnd::Heading(_)) => {}
+                _ => self.write_str(" ")?,
+            }
+        }
+        if self.write_shortcode(&event, &mut range)? {
+            return Ok(());
+        }
+        tracing::trace!(?event, ?range);
+        if matches!(event, Event::Rule)
+            || block_start_kind(&event).is_some_and(|kind| kind != "paragraph")
+        {
+            self.restore_item_text_indentation();
+        }
+        if let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) = &event {
+            if self.is_verbatim_suggestion(info) {
+                return self.write_suggestion_block(range);
+            }
+        }
+        let range_end = range.end;
+        // Set by the events that know where they end,
+        // otherwise found by scanning back from `range_end` after the match.
+        let mut last_position = None;
 
         match event {
             Event::Start(tag) => {
-                self.rewrite_reference_link_definitions(&range)?;
-                last_position = range.start;
+                self.rewrite_reference_link_definitions(range.start)?;
+                last_position = Some(range.start);
                 self.start_tag(tag.clone(), range)?;
             }
             Event::End(ref tag) => {
+                if matches!(
+                    tag,
+                    TagEnd::BlockQuote | TagEnd::Item | TagEnd::FootnoteDefinition
+                ) {
+                    // Write the definitions at the end of the container before closing it.
+                    self.rewrite_reference_link_definitions(range.end)?;
+                }
+                let next_start = self.peek_with_range().map(|(_, next)| next.start);
+                if matches!(tag, TagEnd::List(_)) && next_start == Some(range.end) {
+                    // The range can end with the `> ` of the next block's line in a
+                    // block quote, which would hide the blank line before that block.
+                    let input = &self.input[..range.end];
+                    let line_start = input.rfind('\n').map_or(0, |i| i + 1);
+                    if input[line_start..]
+                        .trim_start_matches([' ', '\t', '>'])
+                        .is_empty()
+                    {
+                        last_position = Some(last_non_whitespace(&input[..line_start]));
+                    }
+                }
                 self.end_tag(*tag, range)?;
                 self.check_needs_indent(&event);
             }
             // TODO: Format display math with its own buffer.
             Event::Text(ref parsed_text) => {
-                if self
+                if self.code_block_written_through.is_some() {
+                    self.write_code_through(parsed_text)?;
+                } else if self
                     .external_formatter
                     .as_ref()
                     .is_some_and(|f| f.context() != FormattingContext::Paragraph)
@@ -38,8 +95,10 @@ where
                     // External formatting. Write the text as is.
                     self.write_str(parsed_text)?;
                 } else {
-                    last_position = range.end;
-                    let starts_with_escape = self.input[..range.start].ends_with('\\');
+                    last_position = Some(range.end);
+                    let before = self.input[..range.start].chars().next_back();
+                    let after = self.input[range.end..].chars().next();
+                    let starts_with_escape = before == Some('\\');
                     let newlines = self.count_newlines(&range);
                     let text_from_source = &self.input[range];
                     let mut text = if text_from_source.is_empty() {
@@ -58,10 +117,7 @@ where
                         self.trim_link_or_image_start = false
                     }
 
-                    if matches!(
-                        self.peek(),
-                        Some(Event::End(TagEnd::Link { .. } | TagEnd::Image { .. }))
-                    ) {
+                    if matches!(self.peek(), Some(Event::End(TagEnd::Link | TagEnd::Image))) {
                         text = text.trim_end();
                     }
 
@@ -69,7 +125,31 @@ where
                         self.write_newlines(newlines)?;
                     }
 
-                    if starts_with_escape || self.needs_escape(text) {
+                    if let Some(html_entities) = self.config.html_entities {
+                        if !self.config.ascii_only || parsed_text.is_ascii() {
+                            text = html_entities.rewrite_entity(text, parsed_text);
+                        }
+                    }
+                    let escape = starts_with_escape || self.needs_escape(text);
+                    let mut text = self.with_explicit_autolinks(text);
+                    if self.config.collapse_spaces && self.in_paragraph() {
+                        let shortcodes = self.config.shortcodes.as_ref();
+                        if let Cow::Owned(collapsed) = collapse_spaces(&text, shortcodes) {
+                            text = collapsed.into();
+                        }
+                    }
+                    for transform in &self.config.transforms {
+                        text = transform.apply_to_text(text);
+                    }
+                    match self.config.html_entities {
+                        // Reference labels must keep matching their definitions.
+                        Some(html_entities) if !self.in_reference_label() => {
+                            let before = if escape { Some('\\') } else { before };
+                            text = html_entities.encode(text, before, after);
+                        }
+                        _ => {}
+                    }
+                    if escape {
                         // recover escape characters
                         write!(self, "\\{text}")?;
                     } else {
@@ -90,18 +170,14 @@ where
             Event::Code(_) | Event::Html(_) => {
                 write!(self, "{}", &self.input[range])?;
             }
+            Event::SoftBreak if self.is_protected_line_boundary() => {
+                last_position = Some(range.end);
+                self.write_protected_line_boundary()?;
+            }
             Event::SoftBreak => {
-                last_position = range.end;
+                last_position = Some(range.end);
                 if self.in_link_or_image() {
-                    let next_is_end = matches!(
-                        self.peek(),
-                        Some(Event::End(TagEnd::Link { .. } | TagEnd::Image { .. }))
-                    );
-                    if self.trim_link_or_image_start || next_is_end {
-                        self.trim_link_or_image_start = false
-                    } else {
-                        write!(self, " ")?;
-                    }
+                    self.write_link_text_break()?;
                 } else {
                     write!(self, "{}", &self.input[range])?;
 
@@ -113,8 +189,29 @@ where
                     self.last_was_softbreak = true;
                 }
             }
+            Event::HardBreak
+                if self.config.hard_breaks_in_links_as_spaces && self.in_link_or_image() =>
+            {
+                last_position = Some(range.end);
+                self.write_link_text_break()?;
+            }
             Event::HardBreak => {
                 write!(self, "{}", &self.input[range])?;
+                self.write_line_block_indentation()?;
+            }
+            Event::InlineHtml(_) if self.is_own_line_comment(&range) => {
+                // Like links on their own line, but nothing may follow it on its line,
+                // which would become part of the HTML block the comment starts.
+                self.write_own_line_link_boundary(false)?;
+                self.write_str(self.input[range].trim())?;
+                match self.peek() {
+                    Some(Event::Text(text)) if text.starts_with(char::is_whitespace) => {
+                        self.ending_own_line_link = true;
+                    }
+                    Some(Event::SoftBreak | Event::HardBreak) => self.ending_own_line_link = true,
+                    Some(Event::End(_)) | None => {}
+                    Some(_) => self.write_own_line_link_boundary(true)?,
+                }
             }
             Event::InlineHtml(_) | Event::InlineMath(_) => {
                 let newlines = self.count_newlines(&range);
@@ -125,15 +222,40 @@ where
                 self.check_needs_indent(&event);
             }
             Event::Rule => {
+                // Text in tight list items is buffered like paragraphs.
+                if self
+                    .external_formatter
+                    .as_ref()
+                    .is_some_and(|f| f.context() == FormattingContext::Paragraph)
+                {
+                    self.flush_external_formatted(true)?;
+                }
                 let newlines = self.count_newlines(&range);
                 self.write_newlines(newlines)?;
-                write!(self, "{}", &self.input[range])?;
+                // The range includes the line ending, which would skip
+                // the indentation of the next line in lists and block quotes.
+                let rule = self.input[range].trim_end_matches(['\n', '\r']);
+                let rule_char = rule.trim_start().chars().next().unwrap_or('-');
+                if matches!(self.nested_context.last(), Some(Tag::Item))
+                    && self.rewrite_buffer.ends_with(&format!("{rule_char} "))
+                {
+                    // Right after a list marker with the same character,
+                    // the rule would take the marker into the rule.
+                    let other_char = if rule_char == '*' { '-' } else { '*' };
+                    write!(self, "{}", rule.replace(rule_char, &other_char.to_string()))?;
+                } else {
+                    write!(self, "{rule}")?;
+                }
                 self.check_needs_indent(&event)
             }
             Event::FootnoteReference(text) => {
                 write!(self, "[^{text}]")?;
             }
             Event::TaskListMarker(done) => {
+                if !self.config.flavor.map_or(true, Flavor::supports_task_lists) {
+                    // Keep the marker as plain text.
+                    self.write_str("\\")?;
+                }
                 if done {
                     write!(self, "[x] ")?;
                 } else {
@@ -141,7 +263,8 @@ where
                 }
             }
         }
-        self.last_position = last_position;
+        self.last_position =
+            last_position.unwrap_or_else(|| last_non_whitespace(&self.input[..range_end]));
         Ok(())
     }
 
@@ -154,27 +277,99 @@ where
                     self.needs_indent = false;
                 }
                 self.nested_context.push(tag);
+                self.paragraph_start = range.start;
                 let capacity = (range.end - range.start) * 2;
-                self.new_external_formatted(BufferType::Paragraph, capacity)?;
+                self.in_protected_line = self.is_protected_line(range.start);
+                let badge_row = (self.config.preserve_badge_lines
+                    || self.config.single_line_badge_rows)
+                    && is_badge_line(&self.input[range.clone()]);
+                let line_block =
+                    self.config.preserve_line_blocks && is_line_block(&self.input[range.clone()]);
+                if line_block {
+                    // Continuation lines start at the indentation instead of
+                    // under the first line's text, e.g., in list items.
+                    let line = self.rewrite_buffer.rsplit('\n').next().unwrap_or_default();
+                    let column = self.source_column(range.start) + self.indentation_len();
+                    self.line_block_column = Some(column.saturating_sub(text_width::width(line)));
+                }
+                let too_long_to_wrap = self.find_line_too_long_to_wrap(range);
+                self.in_kept_paragraph = badge_row || too_long_to_wrap || line_block;
+                if badge_row && self.config.single_line_badge_rows && !self.in_protected_line {
+                    let max_width = Some(usize::MAX);
+                    self.new_external_formatted_with_width(
+                        BufferType::Paragraph,
+                        max_width,
+                        capacity,
+                    )?;
+                } else if self.in_protected_line || badge_row || too_long_to_wrap || line_block {
+                    self.new_external_formatted_with_width(BufferType::Paragraph, None, capacity)?;
+                } else {
+                    self.new_external_formatted(BufferType::Paragraph, capacity)?;
+                }
             }
             Tag::Heading {
-                level, id, classes, ..
+                level,
+                id,
+                classes,
+                attrs,
             } => {
-                self.header_id_and_classes = Some((id, classes));
+                // Otherwise, the line before the heading could join its setext text,
+                // e.g., a paragraph's.
+                let mut follows_blank_line =
+                    starts_container_or_follows_blank_line(self.input, range.start);
                 if self.needs_indent {
-                    let newlines = self.count_newlines(&range);
+                    let newlines = self.newlines_before_block(self.count_newlines(&range));
+                    follows_blank_line |= newlines > 1;
                     self.write_newlines(newlines)?;
                     self.needs_indent = false;
                 }
                 let full_header = self.input[range].trim();
+                let has_attributes = id.is_some() || !classes.is_empty() || !attrs.is_empty();
+                let setext_text = full_header
+                    .rsplit_once('\n')
+                    .filter(|_| full_header.ends_with(['=', '-']))
+                    .map(|(text, _)| text);
+                let empty =
+                    is_empty_heading_text(setext_text.unwrap_or(full_header), has_attributes);
+                self.heading_attributes = Some(HeadingAttributes {
+                    id,
+                    classes,
+                    attrs,
+                    empty,
+                });
 
-                if full_header.contains('\n') && full_header.ends_with(['=', '-']) {
+                let to_atx = setext_text.is_some_and(|text| {
+                    self.config.heading_style == Some(HeadingStyle::Atx) && can_be_atx(text)
+                });
+                if setext_text.is_some() && !to_atx {
                     // support for alternative syntax for H1 and H2
                     // <https://www.markdownguide.org/basic-syntax/#alternate-syntax>
-                    let header_marker = full_header.split('\n').last().unwrap().trim();
+                    let underline = full_header.split('\n').next_back().unwrap().trim_end();
+                    // Exclude container prefixes such as `> ` in block quotes.
+                    let header_marker = &underline[underline.trim_end_matches(['=', '-']).len()..];
                     self.setext_header.replace(header_marker);
+                    self.setext_heading_start = self.rewrite_buffer.len();
                     // setext header are handled in `end_tag`
-                    return Ok(());
+                    if empty {
+                        // Like empty ATX headings, leave headings with only attributes unnumbered.
+                        return Ok(());
+                    }
+                    return self.write_heading_number(level);
+                }
+                if self.config.heading_style == Some(HeadingStyle::SetextWhenPossible)
+                    && setext_text.is_none()
+                    && !empty
+                    && follows_blank_line
+                    && can_be_setext(level, full_header)
+                {
+                    let marker = match level {
+                        HeadingLevel::H1 => "=",
+                        _ => "-",
+                    };
+                    self.setext_header.replace(marker);
+                    self.setext_heading_from_atx = true;
+                    self.setext_heading_start = self.rewrite_buffer.len();
+                    return self.write_heading_number(level);
                 }
 
                 let header = match level {
@@ -186,15 +381,11 @@ where
                     HeadingLevel::H6 => "###### ",
                 };
 
-                let empty_header = full_header
-                    .trim_start_matches(header)
-                    .trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '\\'))
-                    .is_empty();
-
-                if empty_header {
+                if empty {
                     write!(self, "{}", header.trim())?;
                 } else {
                     write!(self, "{header}")?;
+                    self.write_heading_number(level)?;
                 }
             }
             Tag::BlockQuote(_) => {
@@ -203,11 +394,16 @@ where
                 // from the blockquote that we're about to push on top of
                 if let Some(indent) = self.indentation.last_mut() {
                     if indent == "> " {
-                        *indent = ">".into()
+                        *indent = ">".into();
+                        // The outer block quote's marker was just written,
+                        // e.g., when `split_block_quote` restarts both.
+                        if self.rewrite_buffer.ends_with("> ") && !self.needs_indent {
+                            self.rewrite_buffer.pop();
+                        }
                     }
                 }
 
-                let newlines = self.count_newlines(&range);
+                let newlines = self.newlines_before_block(self.count_newlines(&range));
                 if self.needs_indent {
                     self.write_newlines(newlines)?;
                     self.needs_indent = false;
@@ -215,7 +411,19 @@ where
 
                 self.nested_context.push(tag);
 
-                match self.peek_with_range().map(|(e, r)| (e.clone(), r.clone())) {
+                // Reference link definitions are not events, but still block quote content.
+                let definition_start = self
+                    .reference_links
+                    .last()
+                    .map(|(.., link_range)| link_range.start)
+                    .filter(|start| *start < range.end && !self.moves_reference_links());
+                let next = self.peek_with_range().map(|(e, r)| match definition_start {
+                    Some(start) if start < r.start || matches!(e, Event::End(_)) => {
+                        (Event::Text("".into()), start..start)
+                    }
+                    _ => (e.clone(), r.clone()),
+                });
+                match next {
                     Some((Event::End(TagEnd::BlockQuote), _)) => {
                         // The next event is `End(BlockQuote)` so the current blockquote is empty!
                         write!(self, ">")?;
@@ -259,10 +467,19 @@ where
                 let info = match kind {
                     CodeBlockKind::Fenced(info_string) => {
                         self.write_newlines_before_code_block(newlines)?;
-                        rewrite_marker(self.input, &range, self)?;
+                        match self.normalized_code_fence(&range, info_string) {
+                            Some(fence) => {
+                                self.write_str(&fence)?;
+                                self.code_fence = Some(fence);
+                            }
+                            None => rewrite_marker(self.input, &range, self)?,
+                        }
 
                         self.needs_indent = true;
                         if info_string.is_empty() {
+                            if self.config.infer_code_block_language.is_some() {
+                                self.bare_fence_info_position = Some(self.rewrite_buffer.len());
+                            }
                             writeln!(self)?;
                             None
                         } else {
@@ -272,14 +489,18 @@ where
                                 .trim_start_matches(['`', '~'])
                                 .starts_with(char::is_whitespace);
 
-                            let info_string = exclude_fence
+                            let info_string: CowStr = exclude_fence
                                 .lines()
                                 .next()
                                 .unwrap_or_else(|| info_string)
                                 .trim()
                                 .into();
+                            let info_string = match self.config.normalize_code_block_info {
+                                true => lowercase_language(&info_string).into(),
+                                false => info_string,
+                            };
 
-                            if starts_with_space {
+                            if starts_with_space && !self.config.normalize_code_block_info {
                                 writeln!(self, " {info_string}")?;
                             } else {
                                 writeln!(self, "{info_string}")?;
@@ -287,6 +508,13 @@ where
                             Some(info_string)
                         }
                     }
+                    CodeBlockKind::Indented if self.indented_code_block_fence.is_some() => {
+                        self.write_newlines_before_code_block(newlines)?;
+                        let fence = self.indented_code_block_fence.clone().unwrap_or_default();
+                        writeln!(self, "{fence}")?;
+                        self.needs_indent = true;
+                        None
+                    }
                     CodeBlockKind::Indented => {
                         // TODO(ytmimi) support tab as an indent
                         let indentation = "    ";
@@ -303,24 +531,57 @@ where
                         None
                     }
                 };
-                self.new_external_formatted(BufferType::CodeBlock { info }, range.len() * 2)?;
+                match self.config.code_block_buffer_limit {
+                    Some(limit) if range.len() > limit => {
+                        self.flush_external_formatted(true)?;
+                        self.bare_fence_info_position = None;
+                        self.code_block_written_through = Some(false);
+                    }
+                    _ => self
+                        .new_external_formatted(BufferType::CodeBlock { info }, range.len() * 2)?,
+                }
                 self.nested_context.push(tag);
             }
             Tag::List(_) => {
+                // Check before writing anything to tell if this list follows another one.
+                let marker = self.unordered_list_marker(&range);
+                self.list_starts_without_items
+                    .push(Some(self.rewrite_buffer.len()));
                 if self.needs_indent {
-                    let newlines = self.count_newlines(&range);
+                    let mut newlines = self.newlines_before_block(self.count_newlines(&range));
+                    if std::mem::take(&mut self.list_after_item_paragraph) {
+                        newlines = self.newlines_before_nested_list(newlines);
+                    }
                     self.write_newlines(newlines)?;
                     self.needs_indent = false;
                 }
 
-                // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-                // let list_marker = ListMarker::from_str(&self.input[range])
-                //    .expect("Should be able to parse a list marker");
-                // self.list_markers.push(list_marker);
+                self.unordered_list_markers.push(marker);
+                self.list_numbers.push(None);
                 self.nested_context.push(tag);
             }
             Tag::Item => {
-                let newlines = self.count_newlines(&range);
+                let mut newlines = self.count_newlines(&range);
+                if self.config.drop_empty_list_items
+                    && matches!(self.peek(), Some(Event::End(TagEnd::Item)))
+                {
+                    // Keep a blank line from either side of the item so the list stays loose.
+                    self.dropped_item_newlines = self.dropped_item_newlines.max(newlines);
+                    self.events.next();
+                    return Ok(());
+                }
+                newlines = newlines.max(std::mem::take(&mut self.dropped_item_newlines));
+                let mut item_start = ItemStart {
+                    item: self.rewrite_buffer.len(),
+                    content: 0,
+                    newlines,
+                    needs_indent: self.needs_indent,
+                    list_start: None,
+                    list_number: self.list_numbers.last().copied().flatten(),
+                };
+                if let Some(list_start) = self.list_starts_without_items.last_mut() {
+                    item_start.list_start = list_start.take();
+                }
                 if self.needs_indent && newlines > 0 {
                     self.write_newlines(newlines)?;
                 }
@@ -345,17 +606,25 @@ where
                 // this is an empty list item
                 self.needs_indent = empty_list_item;
 
+                let list_depth = self
+                    .nested_context
+                    .iter()
+                    .filter(|tag| matches!(tag, Tag::List(_)))
+                    .count()
+                    .saturating_sub(1);
                 let list_marker = self
                     .config
-                    .list_marker(&self.input[range.clone()])
+                    .list_marker_at_depth(&self.input[range.clone()], list_depth)
                     .expect("Should be able to parse a list marker");
+                let list_marker = self.number_list_item(list_marker);
+                let list_marker = self.pad_to_widest_number(list_marker, range.start);
+                let list_marker = match (list_marker, self.unordered_list_markers.last()) {
+                    (ListMarker::Unordered(_), Some(Some(marker))) => {
+                        ListMarker::Unordered(marker.clone())
+                    }
+                    (list_marker, _) => list_marker,
+                };
                 tracing::debug!(?list_marker, source = &self.input[range]);
-                // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-                // Take list_marker so we can use `write!(self, ...)`
-                // let mut list_marker = self
-                //     .list_markers
-                //     .pop()
-                //     .expect("can't have list item without marker");
                 let marker_char = list_marker.marker_char();
                 match &list_marker {
                     ListMarker::Ordered { number, .. } if empty_list_item => {
@@ -373,10 +642,12 @@ where
                         write!(self, "{marker_char} ")?;
                     }
                 }
+                if self.config.drop_empty_list_items {
+                    item_start.content = self.rewrite_buffer.len();
+                    self.item_starts.push(item_start);
+                }
 
                 self.nested_context.push(tag);
-                // Increment the list marker in case this is a ordered list and
-                // swap the list marker we took earlier
                 let indentation = match self.peek() {
                     Some(Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::TableHead)) => {
                         // Have to use the "correct" indentation if
@@ -384,31 +655,73 @@ where
                         // or table follows immediately.
                         list_marker.indentation()
                     }
-                    _ => self
-                        .config
-                        .fixed_indentation
-                        .clone()
-                        .unwrap_or_else(|| list_marker.indentation()),
+                    _ => {
+                        let indentation = self
+                            .config
+                            .fixed_indentation
+                            .as_deref()
+                            .map(intern)
+                            .unwrap_or_else(|| list_marker.indentation());
+                        match self.config.flavor {
+                            // Python-Markdown only nests content indented by 4 spaces.
+                            Some(Flavor::MkDocs) if indentation.len() < 4 => "    ".into(),
+                            _ => indentation,
+                        }
+                    }
                 };
-                self.indentation.push(indentation);
-                // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-                // list_marker.increment_count();
-                // self.list_markers.push(list_marker)
+                let starts_with_paragraph = match self.peek() {
+                    Some(Event::Start(Tag::Paragraph)) => true,
+                    Some(event @ Event::Start(_)) => block_start_kind(event).is_none(),
+                    Some(Event::End(_) | Event::Rule | Event::Html(_)) | None => false,
+                    Some(_) => true,
+                };
+                match self.config.continuation_alignment {
+                    Some(ContinuationAlignment::Marker)
+                        if starts_with_paragraph && !empty_list_item =>
+                    {
+                        self.item_text_indentation = Some(indentation);
+                        self.indentation.push("".into());
+                    }
+                    _ => self.indentation.push(indentation),
+                }
             }
-            Tag::FootnoteDefinition(label) => {
+            Tag::FootnoteDefinition(ref label) => {
                 let newlines = self.count_newlines(&range);
                 self.write_newlines(newlines)?;
-                write!(self, "[^{label}]: ")?;
+                // Like list items, the content may start on the next line.
+                let starts_on_next_line = match self.events.peek() {
+                    Some((Event::End(TagEnd::FootnoteDefinition), _)) => true,
+                    Some((_, next_range)) => {
+                        self.input[range.start..next_range.start].contains('\n')
+                    }
+                    None => false,
+                };
+                if starts_on_next_line {
+                    write!(self, "[^{label}]:")?;
+                } else {
+                    write!(self, "[^{label}]: ")?;
+                }
+                if !self.has_footnote_blocks() {
+                    // The blocks of old-style footnotes are not indented.
+                    return Ok(());
+                }
+                self.needs_indent = starts_on_next_line;
+                self.nested_context.push(tag);
+                // Blocks after the first one must be indented by 4 spaces.
+                self.indentation.push("    ".into());
             }
+            // Markers are normalized as they are written to the paragraph buffer,
+            // so wrapping measures the converted markers.
             Tag::Emphasis => {
                 self.write_emphasis_marker(&range)?;
             }
             Tag::Strong => {
                 self.write_strong_marker(&range)?;
             }
-            Tag::Strikethrough => {
-                rewrite_marker(self.input, &range, self)?;
-            }
+            Tag::Strikethrough => match self.config.flavor {
+                Some(flavor) if !flavor.supports_strikethrough() => self.write_str("<del>")?,
+                _ => rewrite_marker(self.input, &range, self)?,
+            },
             Tag::Link { link_type, .. } => {
                 let newlines = self.count_newlines(&range);
                 if self.needs_indent && newlines > 0 {
@@ -416,9 +729,16 @@ where
                     self.needs_indent = false;
                 }
 
+                if self.is_own_line_link(link_type, &range) {
+                    self.write_own_line_link_boundary(false)?;
+                }
                 let email_or_auto = matches!(link_type, LinkType::Email | LinkType::Autolink);
-                let opener = if email_or_auto { "<" } else { "[" };
-                self.write_str(opener)?;
+                // Bare GFM autolinks, e.g., `www.example.com`, have no brackets to keep.
+                if !email_or_auto {
+                    self.write_str("[")?;
+                } else if self.input[range].starts_with('<') {
+                    self.write_str("<")?;
+                }
                 self.nested_context.push(tag);
 
                 if matches!(self.peek(), Some(Event::Text(_) | Event::SoftBreak)) {
@@ -445,14 +765,27 @@ where
                     self.write_newlines(newlines)?;
                     self.needs_indent = false;
                 }
-                self.table_state.replace(TableState::new(alignment.clone()));
-                write!(self, "|")?;
-                self.indentation.push("|".into());
+                let mut table_state = TableState::new(alignment.clone());
+                if self.config.compact_tables {
+                    table_state = table_state.compact(&self.input[range]);
+                }
+                self.table_state.replace(table_state);
                 self.nested_context.push(tag);
             }
             Tag::TableHead => {
                 self.nested_context.push(tag);
             }
+            Tag::TableRow if self.is_table_caption_row(&range) => {
+                let line = self.input[range].trim();
+                if let Some(state) = self.table_state.as_mut() {
+                    state.push_caption_line(line);
+                }
+                for (event, _) in self.events.by_ref() {
+                    if event == Event::End(TagEnd::TableRow) {
+                        break;
+                    }
+                }
+            }
             Tag::TableRow => {
                 self.nested_context.push(tag);
                 if let Some(state) = self.table_state.as_mut() {
@@ -460,6 +793,9 @@ where
                 }
             }
             Tag::TableCell => {
+                if let Some(state) = self.table_state.as_mut() {
+                    state.set_cell_range(range);
+                }
                 if !matches!(self.peek(), Some(Event::End(TagEnd::TableCell))) {
                     return Ok(());
                 }
@@ -469,17 +805,36 @@ where
                 }
             }
             Tag::HtmlBlock => {
-                let newlines = self.count_newlines(&range);
+                let mut newlines = self.count_newlines(&range);
+                // Skip list items because a blank line would make the list loose.
+                if self.config.blank_lines_around_details
+                    && !self.rewrite_buffer.is_empty()
+                    && !self.nested_context.contains(&Tag::Item)
+                    && is_details_tag(&self.input[range.clone()])
+                {
+                    newlines = newlines.max(2);
+                }
                 tracing::trace!(newlines);
+                self.parse_transform_marker(range.clone());
                 self.flush_external_formatted(false)?;
                 for _ in 0..newlines {
                     self.write_char('\n')?;
                 }
 
-                self.new_external_formatted(BufferType::HtmlBlock, range.len() * 2)?;
+                if self.config.format_details_content && is_details_tag(&self.input[range.clone()])
+                {
+                    self.write_details_block(range.len() * 2)?;
+                } else if self.config.convert_html_blocks {
+                    self.write_converted_html_block(range.len() * 2)?;
+                } else if self.config.preserve_html_block_whitespace {
+                    self.write_html_block_verbatim(range.len() * 2)?;
+                } else {
+                    self.new_external_formatted(BufferType::HtmlBlock, range.len() * 2)?;
+                }
             }
             Tag::MetadataBlock(kind) => {
-                self.write_metadata_block_separator(&kind, range)?;
+                self.write_metadata_block_separator(&kind, range.clone())?;
+                self.new_external_formatted(BufferType::MetadataBlock { kind }, range.len())?;
             }
         }
         Ok(())
@@ -490,31 +845,34 @@ where
             TagEnd::Paragraph => {
                 let popped_tag = self.nested_context.pop();
                 debug_assert_eq!(popped_tag, Some(Tag::Paragraph));
-                self.flush_external_formatted(true)?;
+                self.flush_paragraph(range)?;
+                self.in_kept_paragraph = false;
+                self.line_block_column = None;
+                self.restore_item_text_indentation();
+                self.list_after_item_paragraph = self.nested_context.last() == Some(&Tag::Item)
+                    && matches!(self.peek(), Some(Event::Start(Tag::List(_))));
             }
             TagEnd::Heading(_) => {
-                let (fragment_identifier, classes) = self
-                    .header_id_and_classes
+                let attributes = self
+                    .heading_attributes
                     .take()
                     .expect("Should have pushed a header tag");
-                match (fragment_identifier, classes.is_empty()) {
-                    (Some(id), false) => {
-                        let classes = rewirte_header_classes(classes)?;
-                        write!(self, " {{#{id}{classes}}}")?;
-                    }
-                    (Some(id), true) => {
-                        write!(self, " {{#{id}}}")?;
+                self.strip_heading_punctuation(range);
+                let rewritten_attributes = attributes.rewrite()?;
+                if !rewritten_attributes.is_empty() {
+                    // Empty setext headings have nothing before their attributes
+                    // on the line to separate them from.
+                    if !(attributes.empty && self.setext_header.is_some()) {
+                        self.write_str(" ")?;
                     }
-                    (None, false) => {
-                        let classes = rewirte_header_classes(classes)?;
-                        write!(self, " {{{}}}", classes.trim())?;
-                    }
-                    (None, true) => {}
+                    self.write_str(&rewritten_attributes)?;
                 }
 
                 if let Some(marker) = self.setext_header.take() {
+                    let underline = self.setext_underline(marker);
+                    self.setext_heading_from_atx = false;
                     self.write_newlines(1)?;
-                    write!(self, "{marker}")?;
+                    write!(self, "{underline}")?;
                 }
             }
             TagEnd::BlockQuote => {
@@ -545,7 +903,9 @@ where
                     .external_formatter
                     .as_ref()
                     .is_some_and(|f| f.is_empty());
-                self.flush_external_formatted(true)?;
+                // Drop the pending newline, like trimming the last one of a flushed block.
+                self.code_block_written_through = None;
+                self.flush_code_block()?;
 
                 let popped_tag = self.nested_context.pop();
                 let Some(Tag::CodeBlock(kind)) = &popped_tag else {
@@ -555,7 +915,15 @@ where
                     CodeBlockKind::Fenced(_) => {
                         // write closing code fence
                         self.write_newline_after_code_block(empty_code_block)?;
-                        rewrite_marker(self.input, &range, self)?;
+                        match self.code_fence.take() {
+                            Some(fence) => self.write_str(&fence)?,
+                            None => rewrite_marker(self.input, &range, self)?,
+                        }
+                    }
+                    CodeBlockKind::Indented if self.indented_code_block_fence.is_some() => {
+                        self.write_newline_after_code_block(empty_code_block)?;
+                        let fence = self.indented_code_block_fence.take().unwrap_or_default();
+                        self.write_str(&fence)?;
                     }
                     CodeBlockKind::Indented => {
                         let popped_indentation = self
@@ -569,44 +937,102 @@ where
             TagEnd::List(_) => {
                 let popped_tag = self.nested_context.pop();
                 debug_assert_eq!(popped_tag.unwrap().to_end(), tag);
-                // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-                // self.list_markers.pop();
-
+                self.list_numbers.pop();
+                let marker = self.unordered_list_markers.pop().flatten();
+                self.dropped_item_newlines = 0;
+                if let Some(Some(list_start)) = self.list_starts_without_items.pop() {
+                    // All items were dropped, so drop the list too.
+                    self.rewrite_buffer.truncate(list_start);
+                    return Ok(());
+                }
+                if let Some(marker) = marker {
+                    let depth = self.nested_context.len();
+                    self.last_unordered_list = Some((depth, self.rewrite_buffer.len(), marker));
+                }
                 // To prevent the next code block from being interpreted as a list we'll add an
                 // HTML comment See https://spec.commonmark.org/0.30/#example-308, which states:
                 //
                 //     To separate consecutive lists of the same type, or to separate a list from an
                 //     indented code block that would otherwise be parsed as a subparagraph of the
                 //     final list item, you can insert a blank HTML comment
-                if let Some(Event::Start(Tag::CodeBlock(CodeBlockKind::Indented))) = self.peek() {
-                    self.write_newlines(1)?;
-                    writeln!(self, "<!-- Don't absorb code block into list -->")?;
-                    write!(self, "<!-- Consider a fenced code block instead -->")?;
+                let code_range = match self.events.peek() {
+                    Some((Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)), range)) => {
+                        range.clone()
+                    }
+                    _ => return Ok(()),
                 };
+                if self.config.fence_code_blocks_after_lists {
+                    let fence_char = match self.config.code_fence_style {
+                        Some(CodeFenceStyle::Tilde) => '~',
+                        Some(CodeFenceStyle::Backtick) | None => '`',
+                    };
+                    let min_length = self.config.code_fence_min_length.unwrap_or(3);
+                    let fence = code_fence_for(&self.input[code_range], fence_char, min_length);
+                    self.indented_code_block_fence = Some(fence);
+                    return Ok(());
+                }
+                self.write_newlines(1)?;
+                let separator = self.config.list_code_block_separator.clone();
+                match separator.as_deref().map(str::trim) {
+                    Some("") => write!(self, "<!-- -->")?,
+                    Some(separator) => write!(self, "<!-- {separator} -->")?,
+                    None => {
+                        writeln!(self, "<!-- Don't absorb code block into list -->")?;
+                        write!(self, "<!-- Consider a fenced code block instead -->")?;
+                    }
+                }
             }
             TagEnd::Item => {
                 let newlines = self.count_newlines(&range);
+                let dropped_item = self
+                    .item_starts
+                    .pop()
+                    .filter(|start| start.content == self.rewrite_buffer.len());
+                if let Some(item_start) = dropped_item {
+                    // All nested lists were dropped, so the item is empty and dropped too.
+                    self.nested_context.pop();
+                    self.item_text_indentation = None;
+                    self.indentation.pop();
+                    self.rewrite_buffer.truncate(item_start.item);
+                    if let Some(list_start) = self.list_starts_without_items.last_mut() {
+                        *list_start = item_start.list_start;
+                    }
+                    if let Some(list_number) = self.list_numbers.last_mut() {
+                        *list_number = item_start.list_number;
+                    }
+                    self.dropped_item_newlines = item_start.newlines;
+                    self.needs_indent = item_start.needs_indent;
+                    return Ok(());
+                }
                 if self.needs_indent && newlines > 0 {
                     self.write_newlines_no_trailing_whitespace(newlines)?;
                 }
                 let popped_tag = self.nested_context.pop();
                 debug_assert_eq!(popped_tag.unwrap().to_end(), tag);
+                self.item_text_indentation = None;
                 let popped_indentation = self.indentation.pop();
                 debug_assert!(popped_indentation.is_some());
 
                 // if the next event is a Start(Item), then we need to set needs_indent
                 self.needs_indent = matches!(self.peek(), Some(Event::Start(Tag::Item)));
             }
-            TagEnd::FootnoteDefinition => {}
+            TagEnd::FootnoteDefinition if !self.has_footnote_blocks() => {}
+            TagEnd::FootnoteDefinition => {
+                let popped_tag = self.nested_context.pop();
+                debug_assert_eq!(popped_tag.unwrap().to_end(), tag);
+                let popped_indentation = self.indentation.pop();
+                debug_assert_eq!(popped_indentation.as_deref(), Some("    "));
+            }
             TagEnd::Emphasis => {
                 self.write_emphasis_marker(&range)?;
             }
             TagEnd::Strong => {
                 self.write_strong_marker(&range)?;
             }
-            TagEnd::Strikethrough => {
-                rewrite_marker(self.input, &range, self)?;
-            }
+            TagEnd::Strikethrough => match self.config.flavor {
+                Some(flavor) if !flavor.supports_strikethrough() => self.write_str("</del>")?,
+                _ => rewrite_marker(self.input, &range, self)?,
+            },
             TagEnd::Link | TagEnd::Image => {
                 let popped_tag = self
                     .nested_context
@@ -652,17 +1078,28 @@ where
                     }
                     LinkType::Collapsed | LinkType::CollapsedUnknown => write!(self, "][]")?,
                     LinkType::Shortcut | LinkType::ShortcutUnknown => write!(self, "]")?,
-                    LinkType::Autolink | LinkType::Email => write!(self, ">")?,
+                    LinkType::Autolink | LinkType::Email if text.starts_with('<') => {
+                        write!(self, ">")?
+                    }
+                    LinkType::Autolink | LinkType::Email => {}
+                }
+                if self.is_own_line_link(link_type, &range) {
+                    self.ending_own_line_link = true;
                 }
             }
             TagEnd::Table => {
                 let popped_tag = self.nested_context.pop();
                 debug_assert_eq!(popped_tag.unwrap().to_end(), tag);
-                if let Some(state) = self.table_state.take() {
+                if let Some(mut state) = self.table_state.take() {
+                    let table_transforms = std::mem::take(&mut self.table_transforms);
+                    for transform in self.config.transforms.iter().chain(&table_transforms) {
+                        transform.apply_to_table(&mut state);
+                    }
+                    if let Some(hook) = self.config.table_hook {
+                        state.apply_hook(hook);
+                    }
                     self.join_with_indentation(&state.format()?, false, true)?;
                 }
-                let popped_indentation = self.indentation.pop().expect("we added `|` in start_tag");
-                debug_assert_eq!(popped_indentation, "|");
             }
             TagEnd::TableRow | TagEnd::TableHead => {
                 let popped_tag = self.nested_context.pop();
@@ -679,6 +1116,7 @@ where
                 self.check_needs_indent(&Event::End(tag));
             }
             TagEnd::MetadataBlock(kind) => {
+                self.flush_external_formatted(true)?;
                 self.write_metadata_block_separator(&kind, range)?;
             }
         }
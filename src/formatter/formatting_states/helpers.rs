@@ -5,10 +5,13 @@ where
     E: ExternalFormatter,
     I: Iterator<Item = (Event<'i>, std::ops::Range<usize>)>,
 {
-    pub(crate) fn formatter_width(&self) -> Option<usize> {
-        self.config
-            .max_width
-            .map(|w| w.saturating_sub(self.indentation_len()))
+    /// The width left for the content of `context` after indentation.
+    pub(crate) fn formatter_width(&self, context: FormattingContext) -> Option<usize> {
+        self.available_width(self.config.max_width_for(context))
+    }
+
+    pub(crate) fn available_width(&self, max_width: Option<usize>) -> Option<usize> {
+        max_width.map(|w| w.saturating_sub(self.indentation_len()))
     }
 
     /// Peek at the next Markdown Event
@@ -17,7 +20,7 @@ where
     }
 
     /// Peek at the next Markdown Event and it's original position in the input
-    pub(crate) fn peek_with_range(&mut self) -> Option<(&Event, &Range<usize>)> {
+    pub(crate) fn peek_with_range(&mut self) -> Option<(&Event<'i>, &Range<usize>)> {
         self.events.peek().map(|(e, r)| (e, r))
     }
 
@@ -31,7 +34,9 @@ where
         self.needs_indent = match self.peek() {
             Some(Event::Start(_) | Event::Rule | Event::Html(_) | Event::End(TagEnd::Item)) => true,
             Some(Event::End(TagEnd::BlockQuote)) => matches!(event, Event::End(_)),
-            Some(Event::Text(_)) => matches!(event, Event::End(_) | Event::Start(Tag::Item)),
+            Some(Event::Text(_)) => {
+                matches!(event, Event::End(_) | Event::Start(Tag::Item) | Event::Rule)
+            }
             _ => matches!(event, Event::Rule),
         };
     }
@@ -81,6 +86,33 @@ where
         )
     }
 
+    /// Check if we're in the text of a link or image whose text is its label,
+    /// e.g., `[label]` or `[label][]`.
+    pub(crate) fn in_reference_label(&self) -> bool {
+        self.nested_context.iter().any(|tag| match tag {
+            Tag::Link { link_type, .. } | Tag::Image { link_type, .. } => matches!(
+                link_type,
+                LinkType::Shortcut
+                    | LinkType::ShortcutUnknown
+                    | LinkType::Collapsed
+                    | LinkType::CollapsedUnknown
+            ),
+            _ => false,
+        })
+    }
+
+    /// Write a line break in link or image text as a space,
+    /// unless it is at the start or end of the text.
+    pub(crate) fn write_link_text_break(&mut self) -> std::fmt::Result {
+        let next_is_end = matches!(self.peek(), Some(Event::End(TagEnd::Link | TagEnd::Image)));
+        if self.trim_link_or_image_start || next_is_end {
+            self.trim_link_or_image_start = false;
+            Ok(())
+        } else {
+            write!(self, " ")
+        }
+    }
+
     /// Check if we're in a "paragraph". A `Paragraph` might not necessarily be on the
     /// nested_context stack.
     pub(crate) fn in_paragraph(&self) -> bool {
@@ -94,37 +126,40 @@ where
 
     /// Get the length of the indentation
     pub(crate) fn indentation_len(&self) -> usize {
-        self.indentation.iter().map(|i| i.len()).sum()
+        self.indentation.byte_len()
     }
 
     /// Get an exclusive reference to the current buffer we're writing to. That could be the main
     /// rewrite buffer, the code block buffer, the internal table state, or anything else we're
     /// writing to while reformatting
     pub(crate) fn current_buffer(&mut self) -> Option<&mut dyn std::fmt::Write> {
+        self.current_buffer_kind().get(
+            &mut self.rewrite_buffer,
+            &mut self.external_formatter,
+            &mut self.table_state,
+        )
+    }
+
+    /// Which buffer [`Self::current_buffer`] is.
+    fn current_buffer_kind(&self) -> BufferKind {
         if self.force_rewrite_buffer {
             tracing::trace!("force_rewrite_buffer");
-            Some(&mut self.rewrite_buffer)
+            BufferKind::Rewrite
         } else if self.in_fenced_code_block() || self.in_indented_code_block() {
             tracing::trace!("code_block_buffer");
-            self.external_formatter
-                .as_mut()
-                .map(|f| f as &mut dyn std::fmt::Write)
+            BufferKind::External
         } else if self.in_html_block() {
             tracing::trace!("html_block");
-            self.external_formatter
-                .as_mut()
-                .map(|h| h as &mut dyn std::fmt::Write)
+            BufferKind::External
         } else if self.in_table_header() || self.in_table_row() {
             tracing::trace!("table_state");
-            self.table_state
-                .as_mut()
-                .map(|s| s as &mut dyn std::fmt::Write)
-        } else if let Some(external_formatter) = self.external_formatter.as_mut() {
+            BufferKind::Table
+        } else if let Some(external_formatter) = self.external_formatter.as_ref() {
             tracing::trace!(context = ?external_formatter.context());
-            Some(external_formatter as &mut dyn std::fmt::Write)
+            BufferKind::External
         } else {
             tracing::trace!("rewrite_buffer");
-            Some(&mut self.rewrite_buffer)
+            BufferKind::Rewrite
         }
     }
 
@@ -144,54 +179,48 @@ where
     }
 
     pub(crate) fn count_newlines(&self, range: &Range<usize>) -> usize {
-        if self.last_position == range.start {
+        self.count_newlines_from(self.last_position, range)
+    }
+
+    /// Like [`Self::count_newlines`], but from `last_position` instead of
+    /// the end of the last event written.
+    pub(crate) fn count_newlines_from(&self, last_position: usize, range: &Range<usize>) -> usize {
+        if last_position == range.start {
             return 0;
         }
 
-        let snippet = if self.last_position < range.start {
+        let snippet = if last_position < range.start {
             // between two markdown evernts
-            &self.input[self.last_position..range.start]
+            &self.input[last_position..range.start]
         } else {
             // likely in some nested context
-            self.input[self.last_position..range.end].trim_end_matches('\n')
+            self.input[last_position..range.end].trim_end_matches('\n')
         };
 
         snippet.chars().filter(|char| *char == '\n').count()
     }
 
+    /// Write the indentation prefix like [`Self::write_str`] would,
+    /// borrowing the cached prefix alongside the buffer instead of copying it.
     pub(crate) fn write_indentation(
         &mut self,
         trim_trailing_whiltespace: bool,
     ) -> std::fmt::Result {
-        let last_non_complete_whitespace_indent = self
-            .indentation
-            .iter()
-            .rposition(|indent| !indent.chars().all(char::is_whitespace));
-
-        let position = if trim_trailing_whiltespace {
-            let Some(position) = last_non_complete_whitespace_indent else {
-                // All indents are just whitespace. We don't want to push blank lines
-                return Ok(());
-            };
-            position
-        } else {
-            self.indentation.len()
-        };
-
-        // Temporarily take indentation to work around the borrow checker
-        let indentation = std::mem::take(&mut self.indentation);
-
-        for (i, indent) in indentation.iter().take(position + 1).enumerate() {
-            let is_last = i == position;
-
-            if is_last && trim_trailing_whiltespace {
-                self.write_str(indent.trim())?;
-            } else {
-                self.write_str(indent)?;
-            }
+        let in_html_block = !self.force_rewrite_buffer && self.in_html_block();
+        let buffer_kind = self.current_buffer_kind();
+        let prefix = self.indentation.prefix(trim_trailing_whiltespace);
+        if in_html_block {
+            self.html_block_text.push_str(prefix);
+        }
+        let buffer = buffer_kind.get(
+            &mut self.rewrite_buffer,
+            &mut self.external_formatter,
+            &mut self.table_state,
+        );
+        if let Some(writer) = buffer {
+            tracing::trace!(text = prefix, "write_str");
+            writer.write_str(prefix)?
         }
-        // Put the indentation back!
-        self.indentation = indentation;
         Ok(())
     }
 
@@ -272,36 +301,76 @@ where
         }
     }
 
+    /// Write a reference link definition,
+    /// padding its `[label]:` to `column` wide.
     pub(crate) fn write_reference_link_definition_inner(
         &mut self,
         label: &str,
         dest: &str,
         title: Option<&(String, char)>,
+        column: usize,
     ) -> std::fmt::Result {
+        let label = label.trim();
+        let padding = column.saturating_sub(reference_definition_width(label));
+        write!(self, "[{label}]:{:padding$}", "")?;
         // empty links can be specified with <>
         let dest = links::format_link_url(dest, true);
-        self.write_newlines(1)?;
         if let Some((title, quote)) = title {
-            write!(self, r#"[{}]: {dest} {quote}{title}{quote}"#, label.trim())?;
+            let title = links::quote_title(title, *quote);
+            write!(self, " {dest} ")?;
+            let mut lines = title.split('\n');
+            self.write_str(lines.next().unwrap_or_default())?;
+            for line in lines {
+                if self.config.single_line_link_titles {
+                    self.write_str(" ")?;
+                } else {
+                    self.write_str("\n")?;
+                    self.write_indentation(line.is_empty())?;
+                }
+                self.write_str(line)?;
+            }
         } else {
-            write!(self, "[{}]: {dest}", label.trim())?;
+            write!(self, " {dest}")?;
+        }
+        Ok(())
+    }
+
+    /// Write reference link `definitions`, each after its number of newlines,
+    /// aligning consecutive ones if `align_reference_definitions` is set.
+    pub(crate) fn write_reference_link_definitions(
+        &mut self,
+        definitions: Vec<(usize, ReferenceLinkDefinition)>,
+    ) -> std::fmt::Result {
+        let columns =
+            reference_definition_columns(&definitions, self.config.align_reference_definitions);
+        for ((newlines, (label, dest, title, range)), column) in
+            definitions.into_iter().zip(columns)
+        {
+            self.write_newlines(newlines)?;
+            self.write_reference_link_definition_inner(&label, &dest, title.as_ref(), column)?;
+            self.last_position = range.end;
+            self.needs_indent = true;
         }
         Ok(())
     }
 
+    /// Write the reference link definitions that start before `position`,
+    /// or set them aside if they should move to the end of the document.
     pub(crate) fn rewrite_reference_link_definitions(
         &mut self,
-        range: &Range<usize>,
+        position: usize,
     ) -> std::fmt::Result {
         if self.reference_links.is_empty() {
             return Ok(());
         }
         // use std::mem::take to work around the borrow checker
         let mut reference_links = std::mem::take(&mut self.reference_links);
+        let mut definitions = vec![];
+        let mut last_position = self.last_position;
 
         loop {
             match reference_links.last() {
-                Some((_, _, _, link_range)) if link_range.start > range.start => {
+                Some((_, _, _, link_range)) if link_range.start >= position => {
                     // The reference link on the top of the stack comes further along in the file
                     break;
                 }
@@ -310,33 +379,95 @@ where
             }
 
             let (label, dest, title, link_range) = reference_links.pop().expect("we have a value");
-            let newlines = self.count_newlines(&link_range);
-            self.write_newlines(newlines)?;
-            self.write_reference_link_definition_inner(&label, &dest, title.as_ref())?;
-            self.last_position = link_range.end;
-            self.needs_indent = true;
+            if self.moves_reference_links() {
+                self.last_position = link_range.end;
+                self.moved_reference_links
+                    .push((label, dest, title, link_range));
+                continue;
+            }
+            let newlines = self.count_newlines_from(last_position, &link_range);
+            last_position = link_range.end;
+            definitions.push((newlines, (label, dest, title, link_range)));
         }
 
         // put the reference_links back
         self.reference_links = reference_links;
-        Ok(())
+        self.write_reference_link_definitions(definitions)
+    }
+
+    /// Check if footnote definitions contain the blocks indented after them,
+    /// i.e., they are parsed as GitHub's footnotes.
+    pub(crate) fn has_footnote_blocks(&self) -> bool {
+        self.config
+            .flavor
+            .is_some_and(Flavor::supports_footnote_blocks)
+    }
+
+    /// Check if reference link definitions should move to the end of the document.
+    pub(crate) fn moves_reference_links(&self) -> bool {
+        self.config.reference_definitions_placement == Some(ReferenceDefinitionPlacement::End)
     }
 
-    /// Write out reference links at the end of the file
+    /// Write out reference links at the end of the file,
+    /// sorted and separated as configured.
     pub(crate) fn rewrite_final_reference_links(mut self) -> Result<String, std::fmt::Error> {
         // use std::mem::take to work around the borrow checker
-        let reference_links = std::mem::take(&mut self.reference_links);
+        let mut reference_links = std::mem::take(&mut self.reference_links);
         tracing::trace!(?reference_links);
 
-        // need to iterate in reverse because reference_links is a stack
-        for (label, dest, title, range) in reference_links.into_iter().rev() {
-            let newlines = self.count_newlines(&range);
-            self.write_newlines(newlines)?;
+        // reference_links is a stack, so reverse it into source order
+        reference_links.reverse();
+        let first_range = reference_links.first().map(|(.., range)| range.clone());
+        let moved = !self.moved_reference_links.is_empty();
+        reference_links.splice(0..0, std::mem::take(&mut self.moved_reference_links));
 
-            // empty links can be specified with <>
-            self.write_reference_link_definition_inner(&label, &dest, title.as_ref())?;
-            self.last_position = range.end
+        let placement = self.config.reference_definitions_placement;
+        let order = match placement {
+            Some(ReferenceDefinitionPlacement::Preserve) => None,
+            _ => self.config.reference_definitions_order,
+        };
+        match order {
+            Some(ReferenceDefinitionOrder::Label) => {
+                reference_links.sort_by_cached_key(|(label, ..)| links::normalize_label(label))
+            }
+            Some(ReferenceDefinitionOrder::FirstUse) => {
+                let labels =
+                    links::reference_labels_by_first_use(self.input, self.config.parser_options());
+                reference_links.sort_by_cached_key(|(label, ..)| {
+                    let label = links::normalize_label(label);
+                    labels
+                        .iter()
+                        .position(|used| *used == label)
+                        .unwrap_or(usize::MAX)
+                })
+            }
+            None => {}
         }
+        let blank_lines = match placement {
+            Some(ReferenceDefinitionPlacement::Preserve) => None,
+            // Moved definitions have no source blank lines between them.
+            _ => self
+                .config
+                .blank_lines_between_reference_definitions
+                .or((order.is_some() || moved).then_some(0)),
+        };
+
+        let mut last_position = self.last_position;
+        let mut definitions = vec![];
+        for (index, definition) in reference_links.into_iter().enumerate() {
+            let range = &definition.3;
+            let newlines = match (index, blank_lines) {
+                (0, _) if moved => 2,
+                (0, _) => {
+                    self.count_newlines_from(last_position, first_range.as_ref().unwrap_or(range))
+                }
+                (_, Some(blank_lines)) => blank_lines + 1,
+                (_, None) => self.count_newlines_from(last_position, range),
+            };
+            last_position = range.end;
+            definitions.push((newlines, definition));
+        }
+        self.write_reference_link_definitions(definitions)?;
         Ok(self.rewrite_buffer)
     }
 
@@ -376,49 +507,977 @@ where
         Ok(())
     }
 
+    /// Write code block `text` to the rewrite buffer as is,
+    /// indenting each line like [`Self::join_with_indentation`] would
+    /// once the code block is flushed.
+    /// The newline after a non-blank line is only written before the next line,
+    /// so the last one can be dropped at the end of the code block.
+    pub(crate) fn write_code_through(&mut self, text: &str) -> std::fmt::Result {
+        self.force_rewrite_buffer = true;
+        for line in text.split_inclusive('\n') {
+            if self.code_block_written_through.replace(false) == Some(true) {
+                self.write_str("\n")?;
+            }
+            let is_blank = line.trim().is_empty();
+            if self.rewrite_buffer.ends_with('\n') {
+                self.write_indentation(is_blank)?;
+            }
+            match line.strip_suffix('\n') {
+                _ if is_blank => self.write_str(line.trim_start_matches(' '))?,
+                Some(line) => {
+                    self.write_str(line)?;
+                    self.code_block_written_through = Some(true);
+                }
+                None => self.write_str(line)?,
+            }
+        }
+        self.force_rewrite_buffer = false;
+        Ok(())
+    }
+
+    /// `capacity` capped at `code_block_buffer_limit` to pre-allocate buffers with.
+    pub(crate) fn buffer_capacity(&self, capacity: usize) -> usize {
+        match self.config.code_block_buffer_limit {
+            Some(limit) => capacity.min(limit),
+            None => capacity,
+        }
+    }
+
     pub(crate) fn new_external_formatted(
         &mut self,
         buffer_type: BufferType,
         capacity: usize,
+    ) -> std::fmt::Result {
+        let max_width = self.formatter_width(buffer_type.to_formatting_context());
+        self.new_external_formatted_with_width(buffer_type, max_width, capacity)
+    }
+
+    pub(crate) fn new_external_formatted_with_width(
+        &mut self,
+        buffer_type: BufferType,
+        max_width: Option<usize>,
+        capacity: usize,
     ) -> std::fmt::Result {
         self.flush_external_formatted(true)?;
-        self.external_formatter = Some(E::new(buffer_type, self.formatter_width(), capacity));
+        let capacity = self.buffer_capacity(capacity);
+        let mut external_formatter = E::new(buffer_type, max_width, capacity);
+        external_formatter.set_indentation(self.indentation_len());
+        if let Some(tolerance) = self.config.minimal_diff_tolerance {
+            external_formatter.set_line_break_tolerance(tolerance);
+        }
+        if let Some(pattern) = &self.config.shortcodes {
+            if external_formatter.context() == FormattingContext::Paragraph {
+                external_formatter.set_unbreakable_spans(pattern);
+            }
+        }
+        self.external_formatter = Some(external_formatter);
         Ok(())
     }
 
+    /// `text` from the source with the bare autolinks in it written as explicit links,
+    /// i.e., `<url>` or `[www.example.com](http://www.example.com)`.
+    /// Links split across text events get their opener and closer separately.
+    pub(crate) fn with_explicit_autolinks<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let input_range = self.input.as_bytes().as_ptr_range();
+        if self.bare_autolinks.is_empty() || !input_range.contains(&text.as_ptr()) {
+            return text.into();
+        }
+        let start = text.as_ptr() as usize - self.input.as_ptr() as usize;
+        let end = start + text.len();
+
+        let mut insertions = vec![];
+        for (range, destination) in &self.bare_autolinks {
+            if (start..end).contains(&range.start) {
+                let opener = if destination.is_some() { "[" } else { "<" };
+                insertions.push((range.start - start, opener.into()));
+            }
+            if range.end > start && range.end <= end {
+                let closer = match destination {
+                    Some(destination) => format!("]({destination})"),
+                    None => ">".into(),
+                };
+                insertions.push((range.end - start, closer));
+            }
+        }
+        if insertions.is_empty() {
+            return text.into();
+        }
+
+        let mut rewrite = String::with_capacity(text.len() + insertions.len() * 2);
+        let mut written = 0;
+        for (offset, insertion) in insertions {
+            rewrite.push_str(&text[written..offset]);
+            rewrite.push_str(&insertion);
+            written = offset;
+        }
+        rewrite.push_str(&text[written..]);
+        rewrite.into()
+    }
+
+    /// Keep the shortcodes in paragraphs as in the source:
+    /// extend or trim text `range` to cover whole shortcodes,
+    /// write shortcodes that start with other events whole,
+    /// and return whether to skip `event` because its shortcode is written already.
+    pub(crate) fn write_shortcode(
+        &mut self,
+        event: &Event,
+        range: &mut Range<usize>,
+    ) -> Result<bool, std::fmt::Error> {
+        if self.shortcodes.is_empty() || !self.in_paragraph() {
+            return Ok(false);
+        }
+        let written = &self.written_shortcode;
+        if written.start <= range.start && range.end <= written.end && !written.is_empty() {
+            return Ok(true);
+        }
+
+        if let Event::Text(_) = event {
+            range.start = range.start.max(written.end);
+            let end = range.end;
+            if let Some(shortcode) = self
+                .shortcodes
+                .iter()
+                .find(|s| s.start < end && end < s.end)
+            {
+                range.end = shortcode.end;
+                self.written_shortcode = shortcode.clone();
+            }
+            return Ok(false);
+        }
+        let Some(shortcode) = (self.shortcodes.iter())
+            .find(|s| s.start <= range.start && range.end <= s.end)
+            .cloned()
+        else {
+            return Ok(false);
+        };
+        self.write_str(&self.input[shortcode.clone()])?;
+        self.written_shortcode = shortcode;
+        Ok(true)
+    }
+
+    /// Check if the table row at `range` is a line of a Pandoc table caption,
+    /// i.e., it starts with `:` and has no pipes, or a caption started already.
+    pub(crate) fn is_table_caption_row(&self, range: &Range<usize>) -> bool {
+        if !self
+            .config
+            .flavor
+            .is_some_and(Flavor::supports_table_captions)
+        {
+            return false;
+        }
+        let row = self.input[range.clone()].trim();
+        let is_caption_start = row.starts_with(':') && !row.contains('|');
+        self.table_state
+            .as_ref()
+            .is_some_and(|state| state.has_caption() || is_caption_start)
+    }
+
+    /// Check if `event` ends a block quote that the next block quote should be merged into
+    /// because only blank lines separate them.
+    pub(crate) fn merges_block_quote(&mut self, event: &Event, range: &Range<usize>) -> bool {
+        if self.config.block_quote_separation != Some(BlockQuoteSeparation::Merge)
+            || *event != Event::End(TagEnd::BlockQuote)
+            || self.nested_context.last() != Some(&Tag::BlockQuote(None))
+        {
+            return false;
+        }
+        let input = self.input;
+        self.events.peek().is_some_and(|(next, next_range)| {
+            *next == Event::Start(Tag::BlockQuote(None))
+                && input[range.end..next_range.start]
+                    .chars()
+                    .all(|c| c.is_whitespace() || c == '>')
+                // Block quotes in them would then be separated by a blank `>` line
+                // only, and merge on the next run.
+                && !nested_block_quote_children(&input[range.clone()]).1
+                && !nested_block_quote_children(&input[next_range.clone()]).0
+        })
+    }
+
+    /// End the current block quote and start another one
+    /// if the next block in it follows a blank `>` line and it should be split.
+    pub(crate) fn split_block_quote(&mut self) -> std::fmt::Result {
+        if self.config.block_quote_separation != Some(BlockQuoteSeparation::Split)
+            || self.nested_context.last() != Some(&Tag::BlockQuote(None))
+            || !self.needs_indent
+        {
+            return Ok(());
+        }
+        let Some(next_start) = self
+            .events
+            .peek()
+            .filter(|(next, _)| block_start_kind(next).is_some())
+            .map(|(_, next_range)| next_range.start)
+        else {
+            return Ok(());
+        };
+        // The line before the next block's is a blank `>` line.
+        let Some(line_end) = self.input[..next_start].rfind('\n') else {
+            return Ok(());
+        };
+        let previous_line = self.input[..line_end]
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default();
+        if !previous_line.chars().all(|c| c.is_whitespace() || c == '>') {
+            return Ok(());
+        }
+        // Drop the blank `>` line previous blocks like lists may have written already.
+        let content_end = self.rewrite_buffer.trim_end_matches([' ', '>']).len();
+        if self.rewrite_buffer[..content_end].ends_with('\n') {
+            self.rewrite_buffer.truncate(content_end - 1);
+        }
+        // Count the newlines from before the blank `>` line, so a blank line
+        // separates the block quotes even if the previous block's range ends later,
+        // as the next block would otherwise continue it lazily.
+        let position = self.input[..line_end].rfind('\n').unwrap_or(0);
+        // Directly nested block quotes are split too, as their blocks would
+        // then be separated by a blank `>` line.
+        let depth = self
+            .nested_context
+            .iter()
+            .rev()
+            .take_while(|tag| **tag == Tag::BlockQuote(None))
+            .count();
+        self.last_position = position;
+        for _ in 0..depth {
+            self.format_one_event(Event::End(TagEnd::BlockQuote), position..position)?;
+        }
+        self.last_position = position;
+        for _ in 0..depth {
+            let quote_start = Event::Start(Tag::BlockQuote(None));
+            self.format_one_event(quote_start, next_start..next_start)?;
+        }
+        Ok(())
+    }
+
+    /// Check if the paragraph line starting at `start` matches `protected_lines`.
+    pub(crate) fn is_protected_line(&self, start: usize) -> bool {
+        self.config.protected_lines.as_ref().is_some_and(|pattern| {
+            let line = self.input[start..].lines().next().unwrap_or_default();
+            pattern.is_match(line)
+        })
+    }
+
+    /// Keep the transforms of the HTML block at `range` for the next table
+    /// if it is a [`transform::MARKER`] comment, reporting it if invalid.
+    pub(crate) fn parse_transform_marker(&mut self, range: Range<usize>) {
+        let html = self.input[range.clone()].trim_end();
+        match transform::parse_marker(html) {
+            Some(Ok(transforms)) => self.table_transforms = transforms,
+            Some(Err(message)) => self.diagnostics.push(Diagnostic {
+                range: range.start..range.start + html.len(),
+                kind: DiagnosticKind::InvalidTransformMarker { message },
+            }),
+            None => {}
+        }
+    }
+
+    /// Check if the paragraph at `range` has a line longer than
+    /// `max_wrapped_line_length`, reporting the first such line if so.
+    pub(crate) fn find_line_too_long_to_wrap(&mut self, range: Range<usize>) -> bool {
+        let Some(limit) = self.config.max_wrapped_line_length else {
+            return false;
+        };
+        let mut line_start = range.start;
+        for line in self.input[range].split_inclusive('\n') {
+            let length = line.trim_end().len();
+            if length > limit {
+                self.diagnostics.push(Diagnostic {
+                    range: line_start..line_start + length,
+                    kind: DiagnosticKind::LineTooLongToWrap { length },
+                });
+                return true;
+            }
+            line_start += line.len();
+        }
+        false
+    }
+
+    /// Width of the source line before `offset`, including container markers.
+    pub(crate) fn source_column(&self, offset: usize) -> usize {
+        let line_start = self.input[..offset].rfind('\n').map_or(0, |i| i + 1);
+        text_width::width(&self.input[line_start..offset])
+    }
+
+    /// After a hard break in a line block, indent the next line
+    /// as far past the first line as it is in the source.
+    pub(crate) fn write_line_block_indentation(&mut self) -> std::fmt::Result {
+        let Some(column) = self.line_block_column else {
+            return Ok(());
+        };
+        let Some(next_start) = self.peek_with_range().map(|(_, next)| next.start) else {
+            return Ok(());
+        };
+        let indentation = self.source_column(next_start).saturating_sub(column);
+        write!(self, "{:indentation$}", "")
+    }
+
+    /// Check if the link of `link_type` at `range` should be on its own line
+    /// because it is at least `own_line_link_width` wide.
+    pub(crate) fn is_own_line_link(&self, link_type: LinkType, range: &Range<usize>) -> bool {
+        let Some(min_width) = self.config.own_line_link_width else {
+            return false;
+        };
+        if link_type != LinkType::Inline
+            || !self.in_paragraph()
+            || self.in_link_or_image()
+            || self.in_kept_paragraph
+            || self.in_protected_line
+            || self.config.unwrap_paragraphs
+        {
+            return false;
+        }
+        // Each word with the space after it, except for the last one.
+        let width = self.input[range.clone()]
+            .split_whitespace()
+            .map(|word| text_width::width(word) + 1)
+            .sum::<usize>();
+        if width.saturating_sub(1) < min_width {
+            return false;
+        }
+        // The text left before the link and the text moved after it get lines
+        // of their own, which must not start a block, e.g., `*` a list item.
+        let line_start = self.input[..range.start]
+            .rfind('\n')
+            .map_or(0, |index| index + 1)
+            .max(self.paragraph_start);
+        let after = &self.input[range.end..];
+        let line_end = after.find('\n').unwrap_or(after.len());
+        let rest = after[..line_end]
+            .find(char::is_whitespace)
+            .map_or("", |index| &after[index..line_end]);
+        // Continuation lines start with the prefixes of their block quotes.
+        let before = self.input[line_start..range.start].trim_start_matches(['>', ' ', '\t']);
+        // A line break after text right before the link would separate them,
+        // e.g., turning `*[link](url)*` into no emphasis.
+        let separated = before.is_empty() || before.ends_with(char::is_whitespace);
+        separated && is_paragraph_line(before) && is_paragraph_line(rest)
+    }
+
+    /// Check if the inline HTML at `range` is one of `own_line_comments`
+    /// directly in a paragraph, which goes on its own line.
+    pub(crate) fn is_own_line_comment(&self, range: &Range<usize>) -> bool {
+        if !matches!(self.nested_context.last(), Some(Tag::Paragraph)) {
+            return false;
+        }
+        let html = self.input[range.clone()].trim();
+        self.config
+            .own_line_comments
+            .iter()
+            .any(|comment| comment == html)
+    }
+
+    /// End the line of a link moved to its own line at the first whitespace
+    /// from `event` on, skipping that whitespace by advancing `range`.
+    /// Return `false` if nothing of `event` is left to format.
+    pub(crate) fn end_own_line_link_line(
+        &mut self,
+        event: &Event<'i>,
+        range: &mut Range<usize>,
+    ) -> Result<bool, std::fmt::Error> {
+        match event {
+            Event::SoftBreak => {
+                self.write_own_line_link_boundary(true)?;
+                Ok(false)
+            }
+            Event::HardBreak => {
+                self.format_one_event(event.clone(), range.clone())?;
+                self.write_own_line_link_boundary(true)?;
+                Ok(false)
+            }
+            Event::Text(_) => {
+                let text = &self.input[range.clone()];
+                let Some(index) = text.find(char::is_whitespace) else {
+                    // The whole text is on the line of the link.
+                    self.ending_own_line_link = true;
+                    return Ok(true);
+                };
+                if index > 0 {
+                    let before = range.start..range.start + index;
+                    self.format_one_event(Event::Text(text[..index].into()), before)?;
+                }
+                self.write_own_line_link_boundary(true)?;
+                range.start += text.len() - text[index..].trim_start().len();
+                Ok(range.start < range.end)
+            }
+            _ => {
+                // Inline elements stay on the line of the link.
+                self.ending_own_line_link =
+                    block_start_kind(event).is_none() && !is_block_end(event);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Flush the paragraph so far and continue it on a new line in a new buffer,
+    /// like [`Self::write_protected_line_boundary`], unless nothing precedes
+    /// the boundary on its line.
+    /// The new line is only wrapped if `wrap`,
+    /// so that links on their own line are not broken up.
+    pub(crate) fn write_own_line_link_boundary(&mut self, wrap: bool) -> std::fmt::Result {
+        let Some(external_formatter) = self.external_formatter.take() else {
+            return Ok(());
+        };
+        let context = external_formatter.context();
+        let formatted = external_formatter.into_buffer();
+        let formatted = formatted.trim_end_matches(' ');
+        if !formatted.is_empty() {
+            // A soft or hard break may already end the line.
+            let ends_line = formatted.ends_with('\n');
+            self.write_external_formatted(context, formatted, !ends_line)?;
+            if !ends_line {
+                self.write_str("\n")?;
+            }
+            self.write_indentation(false)?;
+            self.last_was_softbreak = true;
+        }
+        let input_len = self.input.len();
+        let next_start = self.peek_with_range().map_or(input_len, |(_, r)| r.start);
+        let capacity = self.input[next_start..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .len()
+            * 2;
+        match wrap {
+            true => self.new_external_formatted(BufferType::Paragraph, capacity),
+            false => self.new_external_formatted_with_width(BufferType::Paragraph, None, capacity),
+        }
+    }
+
+    /// Check if the next soft break ends or starts a protected paragraph line.
+    pub(crate) fn is_protected_line_boundary(&mut self) -> bool {
+        if self.config.protected_lines.is_none() || !self.in_paragraph() || self.in_link_or_image()
+        {
+            return false;
+        }
+        match self.peek_with_range() {
+            Some((_, next)) => {
+                let next_start = next.start;
+                self.in_protected_line || self.is_protected_line(next_start)
+            }
+            None => false,
+        }
+    }
+
+    /// Flush the paragraph lines before a soft break at a protected line
+    /// boundary, and continue the paragraph on the next line in a new buffer,
+    /// so lines are never merged across the boundary.
+    /// Protected lines get a buffer without a maximum width.
+    pub(crate) fn write_protected_line_boundary(&mut self) -> std::fmt::Result {
+        let input_len = self.input.len();
+        let next_start = self.peek_with_range().map_or(input_len, |(_, r)| r.start);
+        self.flush_external_formatted(true)?;
+        self.write_str("\n")?;
+        self.write_indentation(false)?;
+        self.last_was_softbreak = true;
+
+        self.in_protected_line = self.is_protected_line(next_start);
+        let capacity = self.input[next_start..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .len()
+            * 2;
+        if self.in_protected_line {
+            self.new_external_formatted_with_width(BufferType::Paragraph, None, capacity)
+        } else {
+            self.new_external_formatted(BufferType::Paragraph, capacity)
+        }
+    }
+
     pub(crate) fn flush_external_formatted(&mut self, trim_last_newline: bool) -> std::fmt::Result {
         if let Some(external_formatter) = self.external_formatter.take() {
             tracing::debug!("Flushing external formatter.");
-            let external = !matches!(external_formatter.context(), FormattingContext::Paragraph);
-            match (external, self.rewrite_buffer.chars().last()) {
-                (false, _) | (_, Some('\n' | ' ' | '$') | None) => {}
-                // Code and HTML blocks should have a `\n` or some sort of
-                // indentation before them.
-                _ => self.write_str("\n")?,
-            }
-            self.join_with_indentation(
-                &external_formatter.into_buffer(),
-                self.needs_indent && external,
-                trim_last_newline,
-            )?;
+            let context = external_formatter.context();
+            let mut formatted = external_formatter.into_buffer();
+            if context == FormattingContext::HtmlBlock {
+                let text = std::mem::take(&mut self.html_block_text);
+                // Otherwise, the Markdown-looking lines in the block,
+                // e.g., `# heading` or `- item`, would parse differently.
+                if formatted != text && !is_single_html_block(&formatted) {
+                    tracing::warn!(
+                        "The formatted HTML block would not parse as one. Keeping it as is."
+                    );
+                    formatted = text;
+                }
+            }
+            self.write_external_formatted(context, &formatted, trim_last_newline)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the trailing punctuation `transforms` strip from the heading
+    /// at `range` just written, reporting it if any.
+    pub(crate) fn strip_heading_punctuation(&mut self, range: Range<usize>) {
+        let transforms = &self.config.transforms;
+        let heading = &self.rewrite_buffer;
+        let Some(len) = transforms
+            .iter()
+            .find_map(|t| t.stripped_heading_len(heading))
+        else {
+            return;
+        };
+        let punctuation = self.rewrite_buffer.split_off(len);
+        let heading_end = range.start + self.input[range.clone()].trim_end().len();
+        self.diagnostics.push(Diagnostic {
+            range: range.start..heading_end,
+            kind: DiagnosticKind::HeadingTrailingPunctuation { punctuation },
+        });
+    }
+
+    /// The marker to write the items of the list at `range` with, if it is unordered.
+    /// A list right after another unordered list written with the same marker
+    /// would continue it, so it keeps its source marker, or another one if that is the same.
+    pub(crate) fn unordered_list_marker(
+        &mut self,
+        range: &Range<usize>,
+    ) -> Option<UnorderedListMarker> {
+        let source = &self.input[range.clone()];
+        let ListMarker::Unordered(source_marker) = ListMarker::from_str(source).ok()? else {
+            return None;
+        };
+        let depth = self
+            .nested_context
+            .iter()
+            .filter(|tag| matches!(tag, Tag::List(_)))
+            .count();
+        let marker = match self.config.list_marker_at_depth(source, depth) {
+            Ok(marker) => self.task_list_marker(marker, range.start),
+            Err(_) => return None,
+        };
+        let ListMarker::Unordered(marker) = marker else {
+            return None;
+        };
+        let previous = match self.last_unordered_list.take() {
+            Some((depth, end, previous))
+                if depth == self.nested_context.len() && end == self.rewrite_buffer.len() =>
+            {
+                previous
+            }
+            _ => return Some(marker),
+        };
+        if marker != previous {
+            return Some(marker);
+        }
+        [
+            source_marker,
+            UnorderedListMarker::Hyphen,
+            UnorderedListMarker::Asterisk,
+        ]
+        .into_iter()
+        .find(|marker| *marker != previous)
+    }
+
+    /// `list_marker` numbered one past the last item of its list
+    /// if `ordered_list_numbering` is `Incrementing`,
+    /// remembering its number for the next item.
+    pub(crate) fn number_list_item(&mut self, mut list_marker: ListMarker) -> ListMarker {
+        let (ListMarker::Ordered { number, .. }, Some(last_number)) =
+            (&mut list_marker, self.list_numbers.last_mut())
+        else {
+            return list_marker;
+        };
+        if let (Some(OrderedListNumbering::Incrementing), Some(last)) =
+            (self.config.ordered_list_numbering, *last_number)
+        {
+            *number = last + 1;
+        }
+        *last_number = Some(*number);
+        list_marker
+    }
+
+    /// `list_marker` of the item at `offset` zero-padded to
+    /// the widest number in its list, if it is ordered.
+    pub(crate) fn pad_to_widest_number(
+        &self,
+        list_marker: ListMarker,
+        offset: usize,
+    ) -> ListMarker {
+        let widest = self
+            .widest_list_numbers
+            .binary_search_by_key(&offset, |(start, _)| *start)
+            .map(|index| self.widest_list_numbers[index].1);
+        match (list_marker, widest) {
+            (ListMarker::Ordered { number, marker, .. }, Ok(widest)) => ListMarker::Ordered {
+                zero_padding: widest.saturating_sub(list::number_width(number)),
+                number,
+                marker,
+            },
+            (list_marker, _) => list_marker,
+        }
+    }
+
+    /// `list_marker` of the item at `offset`, or `bullet_for_task_lists`
+    /// if the item is in a task list.
+    pub(crate) fn task_list_marker(&self, list_marker: ListMarker, offset: usize) -> ListMarker {
+        match (list_marker, &self.config.bullet_for_task_lists) {
+            (ListMarker::Unordered(_), Some(marker))
+                if self.task_list_items.binary_search(&offset).is_ok() =>
+            {
+                ListMarker::Unordered(marker.clone())
+            }
+            (list_marker, _) => list_marker,
+        }
+    }
+
+    /// Indent the rest of the current list item under its text,
+    /// after its first paragraph aligned under the marker.
+    pub(crate) fn restore_item_text_indentation(&mut self) {
+        if let Some(indentation) = self.item_text_indentation.take() {
+            if let Some(level) = self.indentation.last_mut() {
+                *level = indentation;
+            }
+        }
+    }
+
+    /// Flush the paragraph formatter, first showing the formatted text
+    /// of the paragraph at `range` to the `text_inspector`.
+    pub(crate) fn flush_paragraph(&mut self, range: Range<usize>) -> std::fmt::Result {
+        let Some(inspector) = self.text_inspector.as_deref_mut() else {
+            return self.flush_external_formatted(true);
+        };
+        let Some(external_formatter) = self.external_formatter.take() else {
+            return Ok(());
+        };
+        let context = external_formatter.context();
+        let text = external_formatter.into_buffer();
+        inspector.inspect_paragraph(&text, range);
+        self.write_external_formatted(context, &text, true)
+    }
+
+    fn write_external_formatted(
+        &mut self,
+        context: FormattingContext,
+        formatted: &str,
+        trim_last_newline: bool,
+    ) -> std::fmt::Result {
+        let external = !matches!(context, FormattingContext::Paragraph);
+        match (external, self.rewrite_buffer.chars().last()) {
+            (false, _) | (_, Some('\n' | ' ' | '$') | None) => {}
+            // Code and HTML blocks should have a `\n` or some sort of
+            // indentation before them.
+            _ => self.write_str("\n")?,
+        }
+        self.join_with_indentation(formatted, self.needs_indent && external, trim_last_newline)
+    }
+
+    /// Flush the code block formatter, first filling in the info string of
+    /// a bare fence with the language `infer_code_block_language` infers
+    /// from the formatted code.
+    pub(crate) fn flush_code_block(&mut self) -> std::fmt::Result {
+        let (Some(info_position), Some(infer)) = (
+            self.bare_fence_info_position.take(),
+            self.config.infer_code_block_language,
+        ) else {
+            return self.flush_external_formatted(true);
+        };
+        let Some(external_formatter) = self.external_formatter.take() else {
+            return Ok(());
+        };
+        let context = external_formatter.context();
+        let code = external_formatter.into_buffer();
+        if let Some(language) = infer(&code) {
+            let language = language.trim();
+            if !language.contains(['`', '\n']) {
+                self.rewrite_buffer.insert_str(info_position, language);
+            }
+        }
+        self.write_external_formatted(context, &code, true)
+    }
+
+    /// Check if the fenced code block with `info` is a top-level review suggestion
+    /// to keep as is because of `preserve_suggestion_blocks`.
+    pub(crate) fn is_verbatim_suggestion(&self, info: &str) -> bool {
+        self.config.preserve_suggestion_blocks
+            && self.nested_context.is_empty()
+            && info.split_whitespace().next() == Some("suggestion")
+    }
+
+    /// Write the suggestion block at `range` and the newlines before it
+    /// exactly as in the input, skipping the events of its content.
+    pub(crate) fn write_suggestion_block(&mut self, range: Range<usize>) -> std::fmt::Result {
+        self.rewrite_reference_link_definitions(range.start)?;
+        let newlines = self.count_newlines(&range);
+        self.write_newlines_before_code_block(newlines)?;
+        let block = &self.input[range.clone()];
+        self.write_str(block.strip_suffix('\n').unwrap_or(block))?;
+
+        let mut end = Event::End(TagEnd::CodeBlock);
+        for (event, _) in self.events.by_ref() {
+            if matches!(event, Event::End(TagEnd::CodeBlock)) {
+                end = event;
+                break;
+            }
         }
+        self.check_needs_indent(&end);
+        self.last_position = last_non_whitespace(&self.input[..range.end]);
         Ok(())
     }
 
+    /// The opening fence of the fenced code block at `range` with `info`
+    /// if `code_fence_style` or `code_fence_min_length` is set.
+    pub(crate) fn normalized_code_fence(&self, range: &Range<usize>, info: &str) -> Option<String> {
+        if self.config.code_fence_style.is_none() && self.config.code_fence_min_length.is_none() {
+            return None;
+        }
+        let block = &self.input[range.clone()];
+        let source_char = block.chars().next()?;
+        let source_fence = find_marker(self.input, range, |c| c != source_char);
+        let fence_char = match self.config.code_fence_style {
+            Some(CodeFenceStyle::Backtick) if !info.contains('`') => '`',
+            Some(CodeFenceStyle::Tilde) => '~',
+            _ => source_char,
+        };
+        let mut lines = block.lines().skip(1).collect_vec();
+        if self.has_closing_fence(range) {
+            lines.pop();
+        }
+        let min_length = match self.config.code_fence_min_length {
+            Some(min_length) => min_length,
+            None => source_fence.len(),
+        };
+        Some(code_fence_for(
+            &lines.join("\n"),
+            fence_char,
+            min_length.max(3),
+        ))
+    }
+
+    /// Check if the fenced code block at `range` ends with a closing fence.
+    ///
+    /// A closed block's range stops at the end of its closing fence, while an
+    /// unclosed one runs through the newline after its last line. Only at the end of
+    /// the input does that not tell them apart, so there the code itself is checked.
+    fn has_closing_fence(&self, range: &Range<usize>) -> bool {
+        if self.input[range.clone()].ends_with('\n') {
+            return false;
+        }
+        if range.end < self.input.len() {
+            return true;
+        }
+        let code_end = Parser::new_ext(self.input, self.config.parser_options())
+            .into_offset_iter()
+            .filter(|(event, code)| {
+                matches!(event, Event::Text(_)) && range.start < code.start && code.end <= range.end
+            })
+            .last()
+            .map(|(_, code)| code.end);
+        code_end != Some(range.end)
+    }
+
+    /// The underline `marker` of the setext heading written since
+    /// `setext_heading_start`, resized if `setext_underline` is set
+    /// or the heading was an ATX heading.
+    pub(crate) fn setext_underline(&self, marker: &'i str) -> Cow<'i, str> {
+        let setext_underline = match self.setext_heading_from_atx {
+            true => self
+                .config
+                .setext_underline
+                .or(Some(SetextUnderline::HeadingWidth)),
+            false => self.config.setext_underline,
+        };
+        let width = match setext_underline {
+            None => return marker.into(),
+            Some(SetextUnderline::Fixed(width)) => width,
+            Some(SetextUnderline::HeadingWidth) => {
+                // Lines after the first start with the container's indentation.
+                let indentation = self.indentation_len();
+                self.rewrite_buffer[self.setext_heading_start..]
+                    .split('\n')
+                    .enumerate()
+                    .map(|(index, line)| match index {
+                        0 => text_width::width(line),
+                        _ => text_width::width(line).saturating_sub(indentation),
+                    })
+                    .max()
+                    .unwrap_or_default()
+            }
+        };
+        marker[..1].repeat(width.max(1)).into()
+    }
+
+    /// Write `fixed_emphasis_marker`, or the source marker if the span
+    /// would no longer be emphasis with it, e.g., `_` inside a word.
     pub(crate) fn write_emphasis_marker(&mut self, range: &Range<usize>) -> std::fmt::Result {
         match self.config.fixed_emphasis_marker {
-            None => rewrite_marker_with_limit(self.input, range, self, Some(1)),
-            Some(marker) => self.write_str(marker),
+            Some(marker) if keeps_emphasis(self.input, range, marker) => self.write_str(marker),
+            _ => rewrite_marker_with_limit(self.input, range, self, Some(1)),
         }
     }
 
+    /// Write `fixed_strong_marker` like [`Self::write_emphasis_marker`].
     pub(crate) fn write_strong_marker(&mut self, range: &Range<usize>) -> std::fmt::Result {
         match self.config.fixed_strong_marker {
-            None => rewrite_marker_with_limit(self.input, range, self, Some(2)),
-            Some(marker) => self.write_str(marker),
+            Some(marker) if keeps_emphasis(self.input, range, marker) => self.write_str(marker),
+            _ => rewrite_marker_with_limit(self.input, range, self, Some(2)),
+        }
+    }
+
+    /// Separate a list, heading, or block quote from the preceding line by
+    /// a blank line if the configured flavor requires it.
+    pub(crate) fn newlines_before_block(&self, newlines: usize) -> usize {
+        match self.config.flavor {
+            Some(Flavor::Pandoc)
+                if newlines == 1
+                    && !self.config.preserve_line_numbers
+                    && !self.nested_context.contains(&Tag::Item) =>
+            {
+                2
+            }
+            _ => newlines,
+        }
+    }
+
+    /// Newlines between a list item's paragraph and the nested list
+    /// starting with the next item, according to `nested_list_spacing`.
+    pub(crate) fn newlines_before_nested_list(&mut self, newlines: usize) -> usize {
+        match self.config.nested_list_spacing {
+            Some(NestedListSpacing::BlankLine) => 2,
+            Some(NestedListSpacing::NoBlankLine) if newlines > 1 => {
+                let input = self.input;
+                let Some((Event::Start(Tag::Item), range)) = self.peek_with_range() else {
+                    return newlines;
+                };
+                let item = input[range.clone()].trim_start_matches([' ', '\t']);
+                let depth = self
+                    .nested_context
+                    .iter()
+                    .filter(|tag| matches!(tag, Tag::List(_)));
+                // Only lists with a bullet or numbered `1` interrupt a paragraph,
+                // and not with an empty item. Others would join the paragraph.
+                let interrupts = match self.config.list_marker_at_depth(item, depth.count()) {
+                    Ok(ListMarker::Ordered { number, .. }) => number == 1,
+                    Ok(ListMarker::Unordered(_)) => true,
+                    Err(_) => false,
+                };
+                let first_line = item.lines().next().unwrap_or("");
+                let empty = first_line
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .get(1..)
+                    .map_or(true, |text| text.trim().is_empty());
+                match interrupts && !empty {
+                    true => 1,
+                    false => newlines,
+                }
+            }
+            _ => newlines,
         }
     }
 
+    /// Consume the text events of the current HTML block up to its end event.
+    pub(crate) fn take_html_block_text(&mut self, capacity: usize) -> String {
+        let mut html = String::with_capacity(self.buffer_capacity(capacity));
+        // The parser reports the leading spaces of an indented first line as text.
+        while let Some(Event::Html(text) | Event::Text(text)) = self.peek() {
+            html.push_str(text);
+            self.events.next();
+        }
+        html
+    }
+
+    /// Write the HTML block's text events at the current indentation,
+    /// keeping the leading whitespace of every line relative to the least
+    /// indented one, including whitespace-only lines,
+    /// which the HTML block formatter and [`Self::join_with_indentation`] trim.
+    pub(crate) fn write_html_block_verbatim(&mut self, capacity: usize) -> std::fmt::Result {
+        let html = self.take_html_block_text(capacity);
+        let html = html.strip_suffix('\n').unwrap_or(&html);
+        let leading_spaces = |line: &str| line.len() - line.trim_start_matches(' ').len();
+        // Dedent so the block starts at the current indentation,
+        // however far it was indented past its container in the source.
+        let dedent = html
+            .split('\n')
+            .filter(|line| !line.trim().is_empty())
+            .map(leading_spaces)
+            .min()
+            .unwrap_or_default();
+        if !matches!(self.rewrite_buffer.chars().last(), Some('\n' | ' ') | None) {
+            self.write_str("\n")?;
+        }
+        self.force_rewrite_buffer = true;
+        for (index, line) in html.split('\n').enumerate() {
+            if index > 0 {
+                self.write_str("\n")?;
+            }
+            if index > 0 || self.needs_indent {
+                // Only trim the indentation of empty lines,
+                // so whitespace-only lines keep all their whitespace.
+                self.write_indentation(line.is_empty())?;
+            }
+            self.write_str(&line[leading_spaces(line).min(dedent)..])?;
+        }
+        self.force_rewrite_buffer = false;
+        Ok(())
+    }
+
+    /// Format `markdown` nested in the current block with the same configuration,
+    /// narrowed by the current indentation.
+    pub(crate) fn format_nested_markdown(&self, markdown: &str) -> Result<String, std::fmt::Error> {
+        let config = Config {
+            max_width: self.available_width(self.config.max_width),
+            paragraph_max_width: self.available_width(self.config.paragraph_max_width),
+            code_block_max_width: self.available_width(self.config.code_block_max_width),
+            ..self.config.clone()
+        };
+        <MarkdownFormatter<E>>::with_config(config).format(markdown)
+    }
+
+    /// Write already formatted Markdown as a block at the current indentation.
+    pub(crate) fn write_nested_markdown(&mut self, markdown: &str) -> std::fmt::Result {
+        if !matches!(self.rewrite_buffer.chars().last(), Some('\n' | ' ') | None) {
+            self.write_str("\n")?;
+        }
+        self.join_with_indentation(markdown, self.needs_indent, true)
+    }
+
+    /// Write the current HTML block converted to Markdown if it only consists
+    /// of simple elements, or as is otherwise.
+    pub(crate) fn write_converted_html_block(&mut self, capacity: usize) -> std::fmt::Result {
+        let html = self.take_html_block_text(capacity);
+        let converted = html_to_markdown::convert(&html)
+            .filter(|markdown| !self.config.ascii_only || markdown.is_ascii() || !html.is_ascii());
+        match converted {
+            Some(markdown) => {
+                let formatted = self.format_nested_markdown(&markdown)?;
+                self.write_nested_markdown(formatted.trim_end())
+            }
+            None => {
+                self.new_external_formatted(BufferType::HtmlBlock, capacity)?;
+                self.write_str(&html)
+            }
+        }
+    }
+
+    /// Write the hierarchical number of a top-level heading at `level`
+    /// if `number_headings_from` is set, e.g., `2.1` for the first subsection
+    /// of the second section.
+    pub(crate) fn write_heading_number(&mut self, level: HeadingLevel) -> std::fmt::Result {
+        let Some(from) = self.config.number_headings_from else {
+            return Ok(());
+        };
+        if !self.nested_context.is_empty() {
+            // Headings in block quotes and lists are not part of the outline.
+            return Ok(());
+        }
+        let level = level as usize;
+        if level < from {
+            self.heading_numbers.clear();
+            return Ok(());
+        }
+        let depth = level - from;
+        self.heading_numbers.resize(depth + 1, 0);
+        self.heading_numbers[depth] += 1;
+        let number = self.heading_numbers.iter().join(".");
+        self.write_str(&number)?;
+        self.after_heading_number = true;
+        Ok(())
+    }
+
+    /// Metadata blocks only start documents, so their separators are never indented.
+    /// Front matter-like blocks in lists and block quotes are parsed as
+    /// thematic breaks and setext headings instead.
     pub(crate) fn write_metadata_block_separator(
         &mut self,
         kind: &MetadataBlockKind,
@@ -434,11 +1493,89 @@ where
     }
 }
 
+/// Strip the existing number of a heading numbered with `n_components` numbers,
+/// e.g., `2.1 ` or `2.1. ` for two, and the whitespace following it.
+/// Return `None` if `text` does not start with such a number.
+pub(crate) fn strip_heading_number(text: &str, n_components: usize) -> Option<&str> {
+    let mut rest = text;
+    for index in 0..n_components {
+        if index > 0 {
+            rest = rest.strip_prefix('.')?;
+        }
+        let digits_end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits_end == 0 {
+            return None;
+        }
+        rest = &rest[digits_end..];
+    }
+    let rest = rest.strip_prefix('.').unwrap_or(rest);
+    match rest.trim_start() {
+        trimmed if trimmed.len() < rest.len() || rest.is_empty() => Some(trimmed),
+        _ => None,
+    }
+}
+
+/// Check if every line of a `paragraph` but the last ends with a hard break,
+/// i.e., trailing whitespace or a backslash, as in poetry and addresses.
+pub(crate) fn is_line_block(paragraph: &str) -> bool {
+    let mut lines = paragraph.trim_end().lines().peekable();
+    let mut n_lines = 0;
+    while let Some(line) = lines.next() {
+        n_lines += 1;
+        if lines.peek().is_some() && !line.ends_with("  ") && !line.ends_with(['\t', '\\']) {
+            return false;
+        }
+    }
+    n_lines > 1
+}
+
+/// Check if a paragraph only consists of images and links around images,
+/// separated by whitespace, e.g., a row of badges.
+pub(crate) fn is_badge_line(paragraph: &str) -> bool {
+    let mut image_depth = 0_usize;
+    let mut has_image = false;
+    for event in Parser::new_ext(paragraph, Options::all()) {
+        match event {
+            Event::Start(Tag::Image { .. }) => {
+                image_depth += 1;
+                has_image = true;
+            }
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            Event::Start(Tag::Paragraph | Tag::Link { .. })
+            | Event::End(TagEnd::Paragraph | TagEnd::Link)
+            | Event::SoftBreak => {}
+            Event::Text(text) if image_depth > 0 || text.trim().is_empty() => {}
+            _ if image_depth > 0 => {}
+            _ => return false,
+        }
+    }
+    has_image
+}
+
+/// Check if an HTML block starts with a `<details>` or `</details>` tag.
+pub(crate) fn is_details_tag(html: &str) -> bool {
+    let html = html.trim_start().to_ascii_lowercase();
+    ["<details", "</details"].iter().any(|tag| {
+        html.strip_prefix(tag)
+            .is_some_and(|rest| rest.starts_with(|c: char| c == '>' || c.is_whitespace()))
+    })
+}
+
 /// Count the number of `\n` in a snippet.
 pub(crate) fn count_newlines(snippet: &str) -> usize {
     snippet.chars().filter(|char| *char == '\n').count()
 }
 
+/// Byte index of the last non-whitespace character in `text`, or 0 if none.
+pub(crate) fn last_non_whitespace(text: &str) -> usize {
+    text.trim_end()
+        .char_indices()
+        .next_back()
+        .map_or(0, |(index, _)| index)
+}
+
 /// Find some marker that denotes the start of a markdown construct.
 /// for example, `**` for bold or `_` for italics.
 pub(crate) fn find_marker<'i, P>(input: &'i str, range: &Range<usize>, predicate: P) -> &'i str
@@ -469,6 +1606,26 @@ pub(crate) fn rewrite_marker_with_limit<W: std::fmt::Write>(
     }
 }
 
+/// Check if the emphasis or strong span at `range` stays one with `marker`
+/// by parsing it again between the characters around it,
+/// as CommonMark only takes `_` at word boundaries as emphasis.
+pub(crate) fn keeps_emphasis(input: &str, range: &Range<usize>, marker: &str) -> bool {
+    let text = &input[range.clone()];
+    if text.starts_with(marker) || text.len() < 2 * marker.len() {
+        return true;
+    }
+    // Only the characters next to the markers decide if they open and close it.
+    let inner = &text[marker.len()..text.len() - marker.len()];
+    let (Some(first), Some(last)) = (inner.chars().next(), inner.chars().next_back()) else {
+        return true;
+    };
+    let before = input[..range.start].chars().next_back().unwrap_or(' ');
+    let after = input[range.end..].chars().next().unwrap_or(' ');
+    let run = format!("{before}{marker}{first}{last}{marker}{after}");
+    Parser::new_ext(&run, parser_options())
+        .any(|event| matches!(event, Event::Start(Tag::Emphasis | Tag::Strong)))
+}
+
 /// Finds a marker in the source text and writes it to the buffer
 pub(crate) fn rewrite_marker<W: std::fmt::Write>(
     input: &str,
@@ -478,13 +1635,309 @@ pub(crate) fn rewrite_marker<W: std::fmt::Write>(
     rewrite_marker_with_limit(input, range, writer, None)
 }
 
-/// Rewrite a list of h1, h2, h3, h4, h5, h6 classes
-pub(crate) fn rewirte_header_classes(classes: Vec<CowStr>) -> Result<String, std::fmt::Error> {
-    let item_len = classes.iter().map(|i| i.len()).sum::<usize>();
-    let capacity = item_len + classes.len() * 2;
-    let mut result = String::with_capacity(capacity);
-    for class in classes {
-        write!(result, " .{class}")?;
+/// `text` with each run of spaces collapsed to one space,
+/// except in the spans `keep` matches.
+pub(crate) fn collapse_spaces<'t>(text: &'t str, keep: Option<&Regex>) -> Cow<'t, str> {
+    if !text.contains("  ") {
+        return text.into();
+    }
+    let mut collapsed = String::with_capacity(text.len());
+    let push_collapsed = |collapsed: &mut String, part: &str| {
+        for char in part.chars() {
+            if char != ' ' || !collapsed.ends_with(' ') {
+                collapsed.push(char);
+            }
+        }
+    };
+    let mut written = 0;
+    for span in keep.into_iter().flat_map(|pattern| pattern.find_iter(text)) {
+        push_collapsed(&mut collapsed, &text[written..span.start()]);
+        collapsed.push_str(span.as_str());
+        written = span.end();
+    }
+    push_collapsed(&mut collapsed, &text[written..]);
+    collapsed.into()
+}
+
+/// Code block `info` with its language, i.e., up to the first whitespace, `{`, or `,`,
+/// lowercased.
+pub(crate) fn lowercase_language(info: &str) -> String {
+    let language_end = info.find([' ', '\t', '{', ',']).unwrap_or(info.len());
+    let (language, rest) = info.split_at(language_end);
+    language.to_lowercase() + rest
+}
+
+/// Width of the `[label]:` of a reference link definition.
+fn reference_definition_width(label: &str) -> usize {
+    text_width::width(label) + 3
+}
+
+/// The column to pad the `[label]:` of each of `definitions` to,
+/// the widest one of its run of definitions on consecutive lines
+/// that is at most `max_column` wide, or 0 if `max_column` is not set.
+fn reference_definition_columns(
+    definitions: &[(usize, ReferenceLinkDefinition)],
+    max_column: Option<usize>,
+) -> Vec<usize> {
+    let Some(max_column) = max_column else {
+        return vec![0; definitions.len()];
+    };
+    let mut columns = Vec::with_capacity(definitions.len());
+    let mut start = 0;
+    while start < definitions.len() {
+        let run_len = 1 + definitions[start + 1..]
+            .iter()
+            .take_while(|(newlines, _)| *newlines == 1)
+            .count();
+        let run = &definitions[start..start + run_len];
+        let column = run
+            .iter()
+            .map(|(_, (label, ..))| reference_definition_width(label.trim()))
+            .filter(|width| *width <= max_column)
+            .max()
+            .unwrap_or_default();
+        columns.extend(std::iter::repeat(column).take(run_len));
+        start += run_len;
+    }
+    columns
+}
+
+/// A fence of `fence_char` longer than any run of it in `code`,
+/// and at least `min_length` long.
+pub(crate) fn code_fence_for(code: &str, fence_char: char, min_length: usize) -> String {
+    let longest_run = code
+        .split(|c| c != fence_char)
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    fence_char
+        .to_string()
+        .repeat((longest_run + 1).max(min_length))
+}
+
+/// What an open list item changed when it was written, so `drop_empty_list_items`
+/// can still drop it if it turns out empty once its nested lists are dropped.
+pub(crate) struct ItemStart {
+    /// Rewrite buffer length before the item.
+    pub item: usize,
+    /// Rewrite buffer length right after the item's marker.
+    pub content: usize,
+    /// Newlines before the item.
+    pub newlines: usize,
+    pub needs_indent: bool,
+    /// The list's entry in `list_starts_without_items` before the item.
+    pub list_start: Option<usize>,
+    /// The list's last number before the item.
+    pub list_number: Option<usize>,
+}
+
+/// The `{#id .class key=value}` attributes of a heading.
+pub(crate) struct HeadingAttributes<'i> {
+    pub id: Option<CowStr<'i>>,
+    pub classes: Vec<CowStr<'i>>,
+    pub attrs: Vec<(CowStr<'i>, Option<CowStr<'i>>)>,
+    /// The heading has no text besides its attributes, e.g., `# {#anchor}`.
+    pub empty: bool,
+}
+
+impl HeadingAttributes<'_> {
+    /// Rewrite the attributes as `{#id .class key=value}`,
+    /// or an empty string if there are none.
+    pub(crate) fn rewrite(&self) -> Result<String, std::fmt::Error> {
+        let mut result = String::new();
+        if let Some(id) = &self.id {
+            write!(result, " #{id}")?;
+        }
+        for class in &self.classes {
+            write!(result, " .{class}")?;
+        }
+        for (key, value) in &self.attrs {
+            match value {
+                Some(value) => write!(result, " {key}={value}")?,
+                None => write!(result, " {key}")?,
+            }
+        }
+        Ok(match result.is_empty() {
+            true => result,
+            false => format!("{{{}}}", &result[1..]),
+        })
+    }
+}
+
+/// Check if the text of a setext heading can be an ATX heading,
+/// i.e., it is one line, as a line break in it could be a hard break,
+/// and it does not end with what would be taken for a closing sequence.
+pub(crate) fn can_be_atx(text: &str) -> bool {
+    !text.contains('\n')
+        && !text
+            .split_whitespace()
+            .next_back()
+            .is_some_and(|word| word.chars().all(|c| c == '#'))
+}
+
+/// Whether the first and the last block in the block quote `text` are
+/// block quotes themselves.
+pub(crate) fn nested_block_quote_children(text: &str) -> (bool, bool) {
+    let mut depth = 0_usize;
+    let mut children = vec![];
+    for event in Parser::new_ext(text, parser_options()) {
+        match event {
+            Event::Start(tag) => {
+                if depth == 1 {
+                    children.push(matches!(tag, Tag::BlockQuote(_)));
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    let first = children.first().copied().unwrap_or(false);
+    let last = children.last().copied().unwrap_or(false);
+    (first, last)
+}
+
+/// Check if `line` is paragraph text both as the first line of a paragraph
+/// and after another line, instead of starting a block or underlining a heading.
+pub(crate) fn is_paragraph_line(line: &str) -> bool {
+    let line = line.trim();
+    let is_one_paragraph = |text: &str| {
+        let events = Parser::new_ext(text, parser_options()).collect_vec();
+        events.first() == Some(&Event::Start(Tag::Paragraph))
+            && events.last() == Some(&Event::End(TagEnd::Paragraph))
+            && events
+                .iter()
+                .filter(|event| matches!(event, Event::End(TagEnd::Paragraph)))
+                .count()
+                == 1
+    };
+    line.is_empty() || (is_one_paragraph(line) && is_one_paragraph(&format!("p\n{line}")))
+}
+
+/// Check if `html` parses as exactly one HTML block on its own.
+pub(crate) fn is_single_html_block(html: &str) -> bool {
+    let mut events = Parser::new_ext(html, parser_options());
+    if events.next() != Some(Event::Start(Tag::HtmlBlock)) {
+        return false;
+    }
+    let mut events = events.skip_while(|event| *event != Event::End(TagEnd::HtmlBlock));
+    events.next().is_some() && events.next().is_none()
+}
+
+/// Check if the block starting at `start` in `input` starts its container
+/// or follows a blank line, so no line before it could join it.
+pub(crate) fn starts_container_or_follows_blank_line(input: &str, start: usize) -> bool {
+    let line_start = input[..start].rfind('\n').map_or(0, |index| index + 1);
+    let is_container_prefix = |c: char| c == '>' || c.is_whitespace();
+    line_start == 0
+        || !input[line_start..start].chars().all(is_container_prefix)
+        || input[..line_start - 1]
+            .rsplit('\n')
+            .next()
+            .is_some_and(|line| line.chars().all(is_container_prefix))
+}
+
+/// Check if the ATX heading `full_header` of `level` can be a setext heading
+/// by parsing its text again underlined.
+pub(crate) fn can_be_setext(level: HeadingLevel, full_header: &str) -> bool {
+    if level > HeadingLevel::H2 {
+        return false;
+    }
+    let text = full_header.trim_start_matches('#').trim();
+    let text = match text.rsplit_once(char::is_whitespace) {
+        Some((text, closing)) if closing.chars().all(|c| c == '#') => text.trim_end(),
+        _ => text,
+    };
+    let underlined = format!("{text}\n===");
+    matches!(
+        Parser::new_ext(&underlined, parser_options()).next(),
+        Some(Event::Start(Tag::Heading { .. }))
+    )
+}
+
+/// Check if the text of a heading is empty once its ATX markers,
+/// closing sequence, and `{...}` attributes are removed.
+pub(crate) fn is_empty_heading_text(text: &str, has_attributes: bool) -> bool {
+    let trim = |text: &str| {
+        text.trim_start_matches('#')
+            .trim()
+            .trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '\\'))
+            .len()
+    };
+    let text = text.trim();
+    let text = match text.ends_with('}') && has_attributes {
+        true => &text[..text.rfind('{').unwrap_or(text.len())],
+        false => text,
+    };
+    trim(text) == 0
+}
+
+/// The kind of block `event` starts, to name its `tracing` span.
+pub(crate) fn block_start_kind(event: &Event) -> Option<&'static str> {
+    let Event::Start(tag) = event else {
+        return None;
+    };
+    let kind = match tag {
+        Tag::Paragraph => "paragraph",
+        Tag::Heading { .. } => "heading",
+        Tag::BlockQuote(_) => "block_quote",
+        Tag::CodeBlock(_) => "code_block",
+        Tag::HtmlBlock => "html_block",
+        Tag::List(_) => "list",
+        Tag::Item => "item",
+        Tag::FootnoteDefinition(_) => "footnote_definition",
+        Tag::Table(_) => "table",
+        Tag::MetadataBlock(_) => "metadata_block",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Whether `event` ends a block [`block_start_kind`] names.
+pub(crate) fn is_block_end(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::End(
+            TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote
+                | TagEnd::CodeBlock
+                | TagEnd::HtmlBlock
+                | TagEnd::List(_)
+                | TagEnd::Item
+                | TagEnd::FootnoteDefinition
+                | TagEnd::Table
+                | TagEnd::MetadataBlock(_)
+        )
+    )
+}
+
+/// The buffers [`FormatState::current_buffer`] chooses from.
+#[derive(Clone, Copy)]
+enum BufferKind {
+    /// The main rewrite buffer.
+    Rewrite,
+    /// The external formatter, e.g., the code block buffer.
+    External,
+    /// The internal table state.
+    Table,
+}
+
+impl BufferKind {
+    /// Borrow this buffer from the fields of a [`FormatState`],
+    /// so that the other fields stay available.
+    fn get<'b, E: ExternalFormatter>(
+        self,
+        rewrite_buffer: &'b mut String,
+        external_formatter: &'b mut Option<E>,
+        table_state: &'b mut Option<TableState<'_>>,
+    ) -> Option<&'b mut dyn std::fmt::Write> {
+        match self {
+            Self::Rewrite => Some(rewrite_buffer),
+            Self::External => external_formatter
+                .as_mut()
+                .map(|f| f as &mut dyn std::fmt::Write),
+            Self::Table => table_state.as_mut().map(|s| s as &mut dyn std::fmt::Write),
+        }
     }
-    Ok(result)
 }
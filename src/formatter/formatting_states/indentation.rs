@@ -0,0 +1,84 @@
+use super::*;
+
+/// Indentation of the nested containers, e.g., `> ` for block quotes,
+/// with the prefixes written at the start of every line cached
+/// until the indentation changes.
+#[derive(Default)]
+pub(crate) struct Indentation {
+    levels: SmallVec<[Cow<'static, str>; 8]>,
+    /// All levels joined.
+    prefix: String,
+    /// Levels up to the last one that is not all whitespace, joined,
+    /// with that last level trimmed.
+    trimmed_prefix: String,
+    /// Whether the prefixes need rebuilding because the levels changed.
+    stale: bool,
+}
+
+/// Indentation made of up to this many spaces is borrowed instead of allocated.
+const SPACES: &str = "                                ";
+
+/// `level` borrowed from the common indentation levels if it is one of them,
+/// otherwise copied.
+pub(crate) fn intern(level: &str) -> Cow<'static, str> {
+    match level {
+        ">" => ">".into(),
+        "> " => "> ".into(),
+        _ if level.len() <= SPACES.len() && level.bytes().all(|byte| byte == b' ') => {
+            SPACES[..level.len()].into()
+        }
+        _ => level.to_owned().into(),
+    }
+}
+
+impl Indentation {
+    pub(crate) fn push(&mut self, level: Cow<'static, str>) {
+        self.levels.push(level);
+        self.stale = true;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Cow<'static, str>> {
+        self.stale = true;
+        self.levels.pop()
+    }
+
+    pub(crate) fn last_mut(&mut self) -> Option<&mut Cow<'static, str>> {
+        self.stale = true;
+        self.levels.last_mut()
+    }
+
+    /// Total length of the levels in bytes.
+    pub(crate) fn byte_len(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    /// The prefix to write at the start of a line,
+    /// without trailing whitespace if `trim_trailing_whitespace`.
+    pub(crate) fn prefix(&mut self, trim_trailing_whitespace: bool) -> &str {
+        if self.stale {
+            self.rebuild();
+        }
+        match trim_trailing_whitespace {
+            true => &self.trimmed_prefix,
+            false => &self.prefix,
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.prefix.clear();
+        self.trimmed_prefix.clear();
+        let last_non_whitespace = self
+            .levels
+            .iter()
+            .rposition(|level| !level.chars().all(char::is_whitespace));
+        for (index, level) in self.levels.iter().enumerate() {
+            self.prefix.push_str(level);
+            match last_non_whitespace {
+                Some(last) if index < last => self.trimmed_prefix.push_str(level),
+                Some(last) if index == last => self.trimmed_prefix.push_str(level.trim()),
+                _ => {}
+            }
+        }
+        self.stale = false;
+    }
+}
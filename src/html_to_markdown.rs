@@ -0,0 +1,539 @@
+//! Conversion of simple HTML blocks, e.g., pasted from rich text editors, to Markdown.
+//! Only paragraphs, lists, tables, links, images, and a few inline elements
+//! are supported. Anything else makes the conversion give up.
+
+/// Elements the conversion understands.
+const SUPPORTED_ELEMENTS: &[&str] = &[
+    "a", "b", "br", "code", "em", "i", "img", "li", "ol", "p", "span", "strong", "table", "tbody",
+    "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+/// Elements without content or closing tag.
+const VOID_ELEMENTS: &[&str] = &["br", "img"];
+
+type Attributes = Vec<(String, String)>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Element {
+        name: String,
+        attributes: Attributes,
+        children: Vec<Node>,
+    },
+    Text(String),
+}
+
+impl Node {
+    fn attribute(&self, key: &str) -> Option<&str> {
+        match self {
+            Node::Element { attributes, .. } => attributes
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str()),
+            Node::Text(_) => None,
+        }
+    }
+
+    fn is_whitespace(&self) -> bool {
+        matches!(self, Node::Text(text) if text.trim().is_empty())
+    }
+}
+
+/// Convert an HTML block consisting only of supported elements to Markdown.
+/// Return `None` if the HTML contains anything else,
+/// e.g., comments, unsupported elements, or unbalanced tags.
+pub(crate) fn convert(html: &str) -> Option<String> {
+    let nodes = parse(html)?;
+    let blocks = blocks(&nodes)?;
+    (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+}
+
+fn parse(html: &str) -> Option<Vec<Node>> {
+    // Open elements with their attributes and the children parsed so far.
+    let mut open: Vec<(String, Attributes, Vec<Node>)> = vec![];
+    let mut roots = vec![];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let node = if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>')?;
+            let name = tag[..end].trim().to_ascii_lowercase();
+            rest = &tag[end + 1..];
+            let (open_name, attributes, children) = open.pop()?;
+            if open_name != name {
+                return None;
+            }
+            Node::Element {
+                name,
+                attributes,
+                children,
+            }
+        } else if let Some(tag) = rest.strip_prefix('<') {
+            let name_end = tag
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(tag.len());
+            let name = tag[..name_end].to_ascii_lowercase();
+            if !SUPPORTED_ELEMENTS.contains(&name.as_str()) {
+                return None;
+            }
+            let (attributes, after_tag) = parse_attributes(&tag[name_end..])?;
+            rest = after_tag;
+            if !VOID_ELEMENTS.contains(&name.as_str()) {
+                open.push((name, attributes, vec![]));
+                continue;
+            }
+            Node::Element {
+                name,
+                attributes,
+                children: vec![],
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = decode_entities(&rest[..end]);
+            rest = &rest[end..];
+            Node::Text(text)
+        };
+        match open.last_mut() {
+            Some((_, _, children)) => children.push(node),
+            None => roots.push(node),
+        }
+    }
+    open.is_empty().then_some(roots)
+}
+
+/// Parse the attributes of a start tag up to and including its `>`.
+/// Return the attributes and the input after the tag.
+fn parse_attributes(mut tag: &str) -> Option<(Attributes, &str)> {
+    let mut attributes = vec![];
+    loop {
+        tag = tag.trim_start();
+        if let Some(rest) = tag.strip_prefix("/>").or_else(|| tag.strip_prefix('>')) {
+            return Some((attributes, rest));
+        }
+        let name_end = tag.find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))?;
+        if name_end == 0 {
+            return None;
+        }
+        let name = tag[..name_end].to_ascii_lowercase();
+        tag = tag[name_end..].trim_start();
+        let value = match tag.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (raw, rest) = match value.chars().next()? {
+                    quote @ ('"' | '\'') => {
+                        let end = value[1..].find(quote)? + 1;
+                        (&value[1..end], &value[end + 1..])
+                    }
+                    _ => {
+                        let end = value.find(|c: char| c.is_whitespace() || c == '>')?;
+                        value.split_at(end)
+                    }
+                };
+                tag = rest;
+                decode_entities(raw)
+            }
+            None => String::new(),
+        };
+        attributes.push((name, value));
+    }
+}
+
+/// Decode the common named and all numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded_char = rest.find(';').and_then(|end| {
+            let char = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                numeric => {
+                    let number = numeric.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((char, end))
+        });
+        match decoded_char {
+            Some((char, end)) => {
+                decoded.push(char);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Render `nodes` as Markdown blocks.
+fn blocks(nodes: &[Node]) -> Option<Vec<String>> {
+    let mut blocks = vec![];
+    let mut inline_run = Inline::default();
+    for node in nodes {
+        match node {
+            Node::Element { name, children, .. }
+                if matches!(name.as_str(), "p" | "ul" | "ol" | "table") =>
+            {
+                blocks.extend(std::mem::take(&mut inline_run).finish());
+                let block = match name.as_str() {
+                    "p" => {
+                        let mut paragraph = Inline::default();
+                        paragraph.nodes(children)?;
+                        paragraph.finish()
+                    }
+                    "table" => Some(table(children)?),
+                    _ => Some(list(node, children)?),
+                };
+                blocks.extend(block);
+            }
+            node => inline_run.node(node)?,
+        }
+    }
+    blocks.extend(inline_run.finish());
+    Some(blocks)
+}
+
+fn list(list: &Node, items: &[Node]) -> Option<String> {
+    let ordered = matches!(list, Node::Element { name, .. } if name == "ol");
+    let start = match list.attribute("start") {
+        Some(start) => start.trim().parse().ok()?,
+        None => 1usize,
+    };
+    let mut rendered = vec![];
+    let items = items.iter().filter(|item| !item.is_whitespace());
+    for (number, item) in (start..).zip(items) {
+        let Node::Element { name, children, .. } = item else {
+            return None;
+        };
+        if name != "li" {
+            return None;
+        }
+        let marker = match ordered {
+            true => format!("{number}. "),
+            false => "- ".to_owned(),
+        };
+        let indentation = " ".repeat(marker.len());
+        let mut item = String::new();
+        for (index, block) in blocks(children)?.into_iter().enumerate() {
+            if index > 0 {
+                // Keep nested lists tight.
+                item.push_str(if is_list(&block) { "\n" } else { "\n\n" });
+            }
+            for (line_index, line) in block.lines().enumerate() {
+                if line_index > 0 {
+                    item.push('\n');
+                }
+                if index + line_index > 0 && !line.is_empty() {
+                    item.push_str(&indentation);
+                }
+                item.push_str(line);
+            }
+        }
+        rendered.push(match item.is_empty() {
+            true => marker.trim_end().to_owned(),
+            false => marker + &item,
+        });
+    }
+    (!rendered.is_empty()).then(|| rendered.join("\n"))
+}
+
+fn is_list(block: &str) -> bool {
+    let digits_end = block
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(block.len());
+    block.starts_with("- ") || digits_end > 0 && block[digits_end..].starts_with(". ")
+}
+
+fn table(children: &[Node]) -> Option<String> {
+    let mut rows: Vec<&Node> = vec![];
+    for child in children.iter().filter(|child| !child.is_whitespace()) {
+        match child {
+            Node::Element { name, children, .. }
+                if matches!(name.as_str(), "thead" | "tbody" | "tfoot") =>
+            {
+                for row in children.iter().filter(|row| !row.is_whitespace()) {
+                    rows.push(row);
+                }
+            }
+            row => rows.push(row),
+        }
+    }
+
+    let mut alignments = vec![];
+    let mut rendered_rows = vec![];
+    for (row_index, row) in rows.into_iter().enumerate() {
+        let Node::Element { name, children, .. } = row else {
+            return None;
+        };
+        if name != "tr" {
+            return None;
+        }
+        let mut cells = vec![];
+        for cell in children.iter().filter(|cell| !cell.is_whitespace()) {
+            let Node::Element { name, children, .. } = cell else {
+                return None;
+            };
+            if name != "th" && name != "td" {
+                return None;
+            }
+            let mut content = Inline {
+                in_table: true,
+                ..Inline::default()
+            };
+            content.nodes(children)?;
+            cells.push(content.finish().unwrap_or_default());
+            if row_index == 0 {
+                alignments.push(match cell.attribute("align") {
+                    Some("left") => ":--",
+                    Some("center") => ":-:",
+                    Some("right") => "--:",
+                    _ => "---",
+                });
+            }
+        }
+        rendered_rows.push(cells);
+    }
+
+    let n_columns = rendered_rows
+        .iter()
+        .map(Vec::len)
+        .max()
+        .filter(|n| *n > 0)?;
+    alignments.resize(n_columns, "---");
+    let mut table = String::new();
+    for (index, mut cells) in rendered_rows.into_iter().enumerate() {
+        cells.resize(n_columns, String::new());
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if index == 0 {
+            table.push_str(&format!("| {} |\n", alignments.join(" | ")));
+        }
+    }
+    table.pop();
+    Some(table)
+}
+
+/// Inline Markdown under construction, with whitespace collapsed like in HTML.
+#[derive(Default)]
+struct Inline {
+    markdown: String,
+    /// Escape `|` and disallow line breaks.
+    in_table: bool,
+}
+
+impl Inline {
+    fn nodes(&mut self, nodes: &[Node]) -> Option<()> {
+        nodes.iter().try_for_each(|node| self.node(node))
+    }
+
+    fn node(&mut self, node: &Node) -> Option<()> {
+        let (name, children) = match node {
+            Node::Text(text) => {
+                self.text(text);
+                return Some(());
+            }
+            Node::Element { name, children, .. } => (name.as_str(), children),
+        };
+        match name {
+            "b" | "strong" => self.wrap(children, "**")?,
+            "i" | "em" => self.wrap(children, "*")?,
+            "span" => self.nodes(children)?,
+            "code" => {
+                let mut code = String::new();
+                for child in children {
+                    let Node::Text(text) = child else {
+                        return None;
+                    };
+                    code.push_str(text);
+                }
+                let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+                if code.is_empty() || self.in_table && code.contains('|') {
+                    return None;
+                }
+                let fence = match code.contains('`') {
+                    true => "`` ",
+                    false => "`",
+                };
+                let closing_fence: String = fence.chars().rev().collect();
+                self.markdown
+                    .push_str(&format!("{fence}{code}{closing_fence}"));
+            }
+            "a" => {
+                let destination = destination(node.attribute("href")?);
+                let mut text = Inline {
+                    in_table: self.in_table,
+                    ..Inline::default()
+                };
+                text.nodes(children)?;
+                let text = text.finish().unwrap_or_default();
+                let title = match node.attribute("title") {
+                    Some(title) => format!(" \"{}\"", title.replace('"', "\\\"")),
+                    None => String::new(),
+                };
+                self.markdown
+                    .push_str(&format!("[{text}]({destination}{title})"));
+            }
+            "img" => {
+                let source = destination(node.attribute("src")?);
+                let mut alt = Inline::default();
+                alt.text(node.attribute("alt").unwrap_or_default());
+                let alt = alt.finish().unwrap_or_default();
+                self.markdown.push_str(&format!("![{alt}]({source})"));
+            }
+            "br" if !self.in_table => {
+                while self.markdown.ends_with(' ') {
+                    self.markdown.pop();
+                }
+                self.markdown.push_str("\\\n");
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Append `text`, collapsing whitespace and escaping Markdown syntax.
+    fn text(&mut self, text: &str) {
+        let mut chars = text.chars().peekable();
+        while let Some(char) = chars.next() {
+            match char {
+                char if char.is_whitespace() => self.space(),
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '~' => {
+                    self.markdown.push('\\');
+                    self.markdown.push(char);
+                }
+                '|' if self.in_table => self.markdown.push_str("\\|"),
+                '&' if chars
+                    .peek()
+                    .is_some_and(|next| next.is_ascii_alphanumeric() || *next == '#') =>
+                {
+                    self.markdown.push_str("\\&")
+                }
+                char => self.markdown.push(char),
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        if !self.markdown.ends_with([' ', '\n']) {
+            self.markdown.push(' ');
+        }
+    }
+
+    /// Wrap the inline content of `children` in emphasis `marker`s,
+    /// keeping surrounding whitespace outside the markers.
+    fn wrap(&mut self, children: &[Node], marker: &str) -> Option<()> {
+        let mut inner = Inline {
+            in_table: self.in_table,
+            ..Inline::default()
+        };
+        inner.nodes(children)?;
+        let content = inner.markdown.trim();
+        if content.is_empty() {
+            if !inner.markdown.is_empty() {
+                self.space();
+            }
+            return Some(());
+        }
+        if inner.markdown.starts_with(' ') {
+            self.space();
+        }
+        self.markdown
+            .push_str(&format!("{marker}{content}{marker}"));
+        if inner.markdown.ends_with(' ') {
+            self.space();
+        }
+        Some(())
+    }
+
+    /// The Markdown, with block-level syntax at its start escaped,
+    /// or `None` if it is empty.
+    fn finish(self) -> Option<String> {
+        let markdown = self.markdown.trim().trim_end_matches('\\').trim_end();
+        if markdown.is_empty() {
+            return None;
+        }
+        let digits_end = markdown
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(markdown.len());
+        let escaped = if markdown.starts_with(['#', '>', '-', '+', '=']) {
+            format!("\\{markdown}")
+        } else if digits_end > 0 && markdown[digits_end..].starts_with(['.', ')']) {
+            format!("{}\\{}", &markdown[..digits_end], &markdown[digits_end..])
+        } else {
+            markdown.to_owned()
+        };
+        Some(escaped)
+    }
+}
+
+/// A link destination, in angle brackets if it contains spaces or parentheses.
+fn destination(url: &str) -> String {
+    let url = url.trim();
+    match url.contains([' ', '(', ')']) {
+        true => format!("<{}>", url.replace('<', "%3C").replace('>', "%3E")),
+        false => url.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converts_inline_elements() {
+        assert_eq!(
+            convert("<p>Some <b>bold </b>and <i>italic</i> text with a <a href=\"https://example.com\" title='Title'>link</a>.</p>").as_deref(),
+            Some("Some **bold** and *italic* text with a [link](https://example.com \"Title\").")
+        );
+        assert_eq!(
+            convert("<p>A <code>code</code> span,<br>\nan <img src=\"a b.png\" alt=\"image\"/> &amp; *stars*</p>").as_deref(),
+            Some("A `code` span,\\\nan ![image](<a b.png>) & \\*stars\\*")
+        );
+        assert_eq!(
+            convert("<p>1. Not a list</p>").as_deref(),
+            Some("1\\. Not a list")
+        );
+    }
+
+    #[test]
+    fn converts_lists() {
+        assert_eq!(
+            convert("<ul>\n<li>One</li>\n<li>Two\n<ol start=\"3\"><li>Three</li><li>Four</li></ol></li>\n</ul>").as_deref(),
+            Some("- One\n- Two\n  3. Three\n  4. Four")
+        );
+    }
+
+    #[test]
+    fn converts_tables() {
+        assert_eq!(
+            convert("<table>\n<thead><tr><th>Name</th><th align=\"right\">Size</th></tr></thead>\n<tbody>\n<tr><td>a|b</td><td>1</td></tr>\n<tr><td><b>c</b></td></tr>\n</tbody>\n</table>").as_deref(),
+            Some("| Name | Size |\n| --- | --: |\n| a\\|b | 1 |\n| **c** |  |")
+        );
+    }
+
+    #[test]
+    fn gives_up_on_unsupported_html() {
+        for html in [
+            "<div>Text</div>",
+            "<!-- comment -->",
+            "<p>Unclosed",
+            "<p>Mismatched</b>",
+            "<table><tr><td><p>Block in cell</p></td></tr></table>",
+            "<a>No destination</a>",
+        ] {
+            assert_eq!(convert(html), None, "{html}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Skip files that should not be formatted, e.g., generated changelogs or vendored docs.
+//!
+//! [`Config::discover`] skips files matched by:
+//! - The `ignore` globs in configuration files.
+//! - The globs in [`IGNORE_FILE_NAME`] files, one per line,
+//!   with blank lines and lines starting with `#` skipped.
+//!
+//! Globs are relative to the directory of the file they are in.
+use std::path::{Component, Path, PathBuf};
+
+use super::*;
+
+/// File name of the ignore files [`Config::discover`] reads.
+pub const IGNORE_FILE_NAME: &str = ".markdownfmtignore";
+
+/// Check if the Markdown file or directory at `path` is ignored by
+/// configuration or ignore files in it or its ancestor directories.
+/// Shorthand for checking if [`Config::discover`] returns `None`.
+pub fn is_ignored(path: impl AsRef<Path>) -> Result<bool, DiscoverError> {
+    Ok(Config::discover(path)?.is_none())
+}
+
+/// The `ignore` globs of the configuration and ignore files in a directory,
+/// with that directory, which they are relative to.
+pub(crate) type DirGlobs = (PathBuf, Vec<String>);
+
+/// The [`DirGlobs`] of `dir`, reading its files once
+/// so that a tree walk can check every path under `dir` against them.
+pub(crate) fn dir_globs(dir: &Path) -> Result<DirGlobs, DiscoverError> {
+    Ok((dir.into(), Config::default().merge_dir(dir)?))
+}
+
+/// Whether `path` is matched by the globs of any of `dirs` it is under,
+/// like [`is_ignored`] with the globs of `path`'s ancestors already read.
+pub(crate) fn is_ignored_by(dirs: &[DirGlobs], path: &Path) -> bool {
+    dirs.iter().any(|(dir, globs)| {
+        path.strip_prefix(dir)
+            .is_ok_and(|relative| globs.iter().any(|glob| glob_matches(glob, relative)))
+    })
+}
+
+/// The globs in the content of an ignore file.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::ignore::ignore_file_globs;
+/// let source = "# Generated.\nCHANGELOG.md\n\nvendor/**\n";
+/// assert_eq!(ignore_file_globs(source).collect::<Vec<_>>(), ["CHANGELOG.md", "vendor/**"]);
+/// ```
+pub fn ignore_file_globs(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Whether `glob` matches `path` or one of its ancestors, relative to the same directory.
+/// `*` and `?` match within a path component, and `**` any number of components.
+/// Globs without `/` match at any depth, like in `.gitignore`.
+///
+/// ```rust
+/// # use std::path::Path;
+/// # use fmtm_ytmimi_markdown_fmt::ignore::glob_matches;
+/// assert!(glob_matches("docs/**/*.md", Path::new("docs/api/index.md")));
+/// assert!(glob_matches("vendor", Path::new("third_party/vendor/README.md")));
+/// assert!(!glob_matches("*.txt", Path::new("README.md")));
+/// ```
+pub fn glob_matches(glob: &str, path: &Path) -> bool {
+    let glob = glob.trim_start_matches("./").trim_end_matches('/');
+    let mut glob_components = glob.split('/').collect::<Vec<_>>();
+    if glob_components.len() == 1 {
+        glob_components.insert(0, "**");
+    }
+    let components = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let components = components.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    (1..=components.len()).any(|len| components_match(&glob_components, &components[..len]))
+}
+
+fn components_match(glob: &[&str], components: &[&str]) -> bool {
+    match glob.split_first() {
+        None => components.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=components.len()).any(|skip| components_match(rest, &components[skip..]))
+        }
+        Some((first, rest)) => components
+            .split_first()
+            .is_some_and(|(component, components)| {
+                component_matches(first.as_bytes(), component.as_bytes())
+                    && components_match(rest, components)
+            }),
+    }
+}
+
+fn component_matches(glob: &[u8], name: &[u8]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| component_matches(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && component_matches(rest, &name[1..]),
+        Some((byte, rest)) => name.first() == Some(byte) && component_matches(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        let path = Path::new("docs/generated/api.md");
+        for glob in [
+            "docs/**",
+            "generated",
+            "*.md",
+            "docs/*/api.md",
+            "**/a?i.md",
+            "./docs/",
+        ] {
+            assert!(glob_matches(glob, path), "{glob}");
+        }
+        for glob in ["api", "*.txt", "generated/api.md", "docs/api.md"] {
+            assert!(!glob_matches(glob, path), "{glob}");
+        }
+    }
+}
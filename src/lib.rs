@@ -20,7 +20,7 @@
 //! 1. are easy!
 //! "##;
 //!
-//! let output = MarkdownFormatter::default().format(markdown)?;
+//! let output = <MarkdownFormatter>::default().format(markdown)?;
 //! assert_eq!(output, expected);
 //! # Ok::<(), std::fmt::Error>(())
 //! ```
@@ -36,7 +36,8 @@
 //!     fn format(
 //!         &mut self,
 //!         buffer_type: BufferType,
-//!         _max_width: Option<usize>,
+//!         _available_width: Option<usize>,
+//!         _indent: usize,
 //!         input: String,
 //!     ) -> String {
 //!         let BufferType::CodeBlock { info } = buffer_type else {
@@ -44,7 +45,7 @@
 //!         };
 //!         match info {
 //!             Some(info) if info.as_ref() == "markdown" => {
-//!                 MarkdownFormatter::default().format(&input).unwrap_or(input)
+//!                 <MarkdownFormatter>::default().format(&input).unwrap_or(input)
 //!             }
 //!             _ => input,
 //!         }
@@ -82,11 +83,15 @@
 //!     >,
 //! >;
 //! let output =
-//!     MyFormatter::with_config_and_external_formatter(Config::sichanghe_opinion()).format(input)?;
+//!     MyFormatter::with_config(Config::sichanghe_opinion()).format(input)?;
 //! assert_eq!(output, expected);
 //! # Ok::<(), std::fmt::Error>(())
 //! ````
 
+// Denied here instead of in the `[lints]` table of `Cargo.toml`, so that the
+// generated spec test crates, which have no crate docs, still build.
+#![deny(missing_docs)]
+
 use std::{
     borrow::Cow, collections::VecDeque, fmt::Write, iter::Peekable, marker::PhantomData,
     num::ParseIntError, ops::Range, str::FromStr,
@@ -94,37 +99,77 @@ use std::{
 
 use itertools::{EitherOrBoth, Itertools};
 use pulldown_cmark::{
-    Alignment, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd,
+    CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, MetadataBlockKind, Options, Parser, Tag,
+    TagEnd,
 };
+use regex::Regex;
+use smallvec::SmallVec;
 use textwrap::Options as TextWrapOptions;
-use unicode_segmentation::UnicodeSegmentation;
 
 mod adapters;
+pub mod anchor;
+mod backend;
+pub mod batch;
 mod builder;
+pub mod code_language;
 mod config;
+mod debug;
+pub mod diff;
+mod display;
+pub mod doc_comment;
 mod escape;
 mod external_formatter;
+mod footnotes;
 mod formatter;
+mod html_to_markdown;
+pub mod ignore;
 mod links;
+pub mod lint;
 pub mod list;
+#[cfg(feature = "mdbook")]
+pub mod mdbook_preprocessor;
+#[cfg(feature = "notebook")]
+mod notebook;
+pub mod precommit;
+mod sections;
 mod table;
 #[cfg(test)]
 mod test;
+pub mod text_width;
+pub mod transform;
 mod utils;
 
 use crate::{
     adapters::{LooseListExt, SequentialBlockExt},
+    anchor::SlugStyle,
+    backend::{Backend, ParserBackend, ReferenceLinkDefinition},
+    doc_comment::{DocComment, DocCommentStyle},
     formatter::FormatState,
     table::TableState,
-    utils::unicode_str_width,
+    transform::Transform,
+    utils::{apply_edits, parser_options},
 };
 pub use crate::{
     builder::MarkdownFormatter,
-    config::Config,
+    config::{
+        BlockQuoteSeparation, CONFIG_FILE_NAME, CodeFenceStyle, Config, ConfigWarning,
+        ContinuationAlignment, DiscoverError, Flavor, HeadingStyle, HtmlEntities,
+        NestedListSpacing, OrderedListNumbering, ReferenceDefinitionOrder,
+        ReferenceDefinitionPlacement, SetextUnderline,
+    },
+    debug::debug_events,
+    display::Formatted,
     external_formatter::{
         BufferType, DefaultFormatterCombination, ExternalFormatter, FnFormatter,
         FormatterCombination, FormatterFn, FormattingContext, Paragraph, PreservingBuffer,
         TrimTo4Indent,
     },
+    formatter::FormatError,
+    lint::{Diagnostic, DiagnosticKind, TextInspector},
     list::{ListMarker, OrderedListMarker, ParseListMarkerError, UnorderedListMarker},
+    table::{Cell, Table, format_table},
 };
+pub use pulldown_cmark::Alignment;
+
+#[cfg(feature = "yaml")]
+pub use crate::external_formatter::YamlFrontMatter;
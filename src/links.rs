@@ -12,14 +12,237 @@ where
     ) -> std::fmt::Result {
         let url = format_link_url(url, false);
         match title {
-            Some((title, ')')) => write!(self, r#"]({url} ({}))"#, title.as_ref())?,
-            Some((title, quote)) => write!(self, r#"]({url} {quote}{}{quote})"#, title.as_ref())?,
+            Some((title, quote)) => {
+                let title = quote_title(title.as_ref(), quote);
+                write!(self, "]({url} {title})")?
+            }
             None => write!(self, "]({url})")?,
         }
         Ok(())
     }
 }
 
+/// Quote link `title`, given with its source backslash escapes
+/// and originally quoted with `quote`, i.e., `"`, `'`, or `)` for parentheses.
+///
+/// The original quote is kept unless the title contains it,
+/// in which case the title switches to `"` or `'` if it does not contain that,
+/// or else the conflicting characters are escaped.
+pub(crate) fn quote_title(title: &str, quote: char) -> String {
+    let conflicts = |char: char, quote: char| match quote {
+        ')' => matches!(char, '(' | ')'),
+        _ => char == quote,
+    };
+    let contains = |quote: char| title.chars().any(|char| conflicts(char, quote));
+    let new_quote = match ['"', '\'']
+        .into_iter()
+        .find(|&q| q != quote && !contains(q))
+    {
+        Some(alternate) if contains(quote) => alternate,
+        _ => quote,
+    };
+    let (opener, closer) = match new_quote {
+        ')' => ('(', ')'),
+        q => (q, q),
+    };
+
+    let mut result = String::with_capacity(title.len() + 2);
+    result.push(opener);
+    let mut chars = title.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                // The escape was only needed for the original quote.
+                Some(escaped) if new_quote != quote && conflicts(escaped, quote) => {
+                    result.push(escaped)
+                }
+                Some(escaped) => {
+                    result.push('\\');
+                    result.push(escaped);
+                }
+                None => result.push('\\'),
+            },
+            _ if conflicts(char, new_quote) => {
+                result.push('\\');
+                result.push(char);
+            }
+            _ => result.push(char),
+        }
+    }
+    result.push(closer);
+    result
+}
+
+/// Normalize a link `label` for matching, like CommonMark does:
+/// ignore case and collapse whitespace.
+pub(crate) fn normalize_label(label: &str) -> String {
+    label.split_whitespace().join(" ").to_lowercase()
+}
+
+/// The normalized labels of the reference links and images in `input`
+/// parsed with `options`, in the order they are first used.
+pub(crate) fn reference_labels_by_first_use(input: &str, options: Options) -> Vec<String> {
+    let mut labels: Vec<String> = vec![];
+    for event in Parser::new_ext(input, options) {
+        let (Event::Start(Tag::Link { link_type, id, .. })
+        | Event::Start(Tag::Image { link_type, id, .. })) = event
+        else {
+            continue;
+        };
+        if matches!(
+            link_type,
+            LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+        ) {
+            let label = normalize_label(&id);
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+    }
+    labels
+}
+
+/// Find the destinations of all links and images,
+/// including those in reference definitions,
+/// returning the source range of each destination and the destination.
+///
+/// Destinations that are escaped in the source cannot be located and are skipped.
+pub(crate) fn link_destinations(input: &str) -> Vec<(Range<usize>, String)> {
+    let parser = Parser::new_ext(input, parser_options());
+    let mut destinations = parser
+        .reference_definitions()
+        .iter()
+        .filter_map(|(_, definition)| find_destination(input, &definition.dest, &definition.span))
+        .collect::<Vec<_>>();
+    for (event, range) in parser.into_offset_iter() {
+        if let Event::Start(
+            Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }
+            | Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            },
+        ) = event
+        {
+            destinations.extend(find_destination(input, &dest_url, &range));
+        }
+    }
+    destinations.sort_by_key(|(range, _)| range.start);
+    destinations
+}
+
+/// Find the bare URLs and email addresses that GFM links automatically,
+/// i.e., its extended autolinks, in the text of `input` parsed with `options`
+/// outside of links and code,
+/// returning the source range of each and, for `www.` links,
+/// the `http://` destination they link to.
+pub(crate) fn bare_autolinks(input: &str, options: Options) -> Vec<(Range<usize>, Option<String>)> {
+    let mut text_runs: Vec<Range<usize>> = vec![];
+    let mut excluded_depth = 0_usize;
+    for (event, range) in Parser::new_ext(input, options).into_offset_iter() {
+        match event {
+            Event::Start(
+                Tag::Link { .. }
+                | Tag::Image { .. }
+                | Tag::CodeBlock(_)
+                | Tag::HtmlBlock
+                | Tag::MetadataBlock(_),
+            ) => excluded_depth += 1,
+            Event::End(
+                TagEnd::Link
+                | TagEnd::Image
+                | TagEnd::CodeBlock
+                | TagEnd::HtmlBlock
+                | TagEnd::MetadataBlock(_),
+            ) => excluded_depth -= 1,
+            Event::Text(_) if excluded_depth == 0 => match text_runs.last_mut() {
+                Some(run) if run.end == range.start => run.end = range.end,
+                _ => text_runs.push(range),
+            },
+            _ => {}
+        }
+    }
+
+    let url = Regex::new(r"(?:https?|ftp)://[^\s<]+|www\.[^\s<]+").expect("valid regex");
+    let email =
+        Regex::new(r"[A-Za-z0-9.+_-]+@[A-Za-z0-9_-]+(?:\.[A-Za-z0-9_-]+)+").expect("valid regex");
+    let at_boundary = |start: usize| {
+        input[..start].chars().next_back().map_or(true, |c| {
+            c.is_whitespace() || matches!(c, '*' | '_' | '~' | '(')
+        })
+    };
+
+    let mut autolinks = vec![];
+    for run in text_runs {
+        let text = &input[run.clone()];
+        let mut urls = vec![];
+        for found in url.find_iter(text) {
+            let link = trim_autolink_path(found.as_str());
+            let domain = link.split_once("://").or_else(|| link.split_once("www."));
+            let domain = domain.map_or("", |(_, rest)| rest);
+            if at_boundary(run.start + found.start()) && domain.contains(|c: char| c != '.') {
+                urls.push(found.start()..found.start() + link.len());
+            }
+        }
+        for found in email.find_iter(text) {
+            let in_url = urls.iter().any(|url| url.contains(&found.start()));
+            if !in_url
+                && at_boundary(run.start + found.start())
+                && !found.as_str().ends_with(['-', '_'])
+            {
+                let range = run.start + found.start()..run.start + found.end();
+                autolinks.push((range, None));
+            }
+        }
+        for url in urls {
+            let link = &text[url.clone()];
+            let destination = link.starts_with("www.").then(|| format!("http://{link}"));
+            autolinks.push((run.start + url.start..run.start + url.end, destination));
+        }
+    }
+    autolinks.sort_by_key(|(range, _)| range.start);
+    autolinks
+}
+
+/// Trim the trailing punctuation, unbalanced `)`, and entity references
+/// that GFM excludes from extended autolinks.
+fn trim_autolink_path(mut link: &str) -> &str {
+    loop {
+        let trimmed = if let Some(rest) =
+            link.strip_suffix(['?', '!', '.', ',', ':', '*', '_', '~', '"', '\''])
+        {
+            rest
+        } else if link.ends_with(')') && link.matches(')').count() > link.matches('(').count() {
+            &link[..link.len() - 1]
+        } else if let Some(rest) = link.strip_suffix(';') {
+            let before_name = rest.trim_end_matches(|c: char| c.is_ascii_alphanumeric());
+            match before_name.strip_suffix('&') {
+                Some(before_entity) if before_name.len() < rest.len() => before_entity,
+                _ => return link,
+            }
+        } else {
+            return link;
+        };
+        link = trimmed;
+    }
+}
+
+fn find_destination(
+    input: &str,
+    dest: &str,
+    range: &Range<usize>,
+) -> Option<(Range<usize>, String)> {
+    if dest.is_empty() {
+        return None;
+    }
+    let start = range.start + input[range.clone()].rfind(dest)?;
+    Some((start..start + dest.len(), dest.to_owned()))
+}
+
 pub(crate) fn format_link_url(url: &str, wrap_empty_urls: bool) -> Cow<'_, str> {
     if wrap_empty_urls && url.is_empty() {
         Cow::from("<>")
@@ -159,6 +382,28 @@ pub(super) fn recover_escaped_link_destination_and_title(
     split_inline_url_from_title(rest, has_title)
 }
 
+/// Remove the block quote markers of the `quote_depth` block quotes around a reference
+/// definition, and the leading whitespace, from the continuation lines of its `title`.
+/// Lazy continuation lines without the markers are handled too.
+pub(super) fn strip_title_line_prefixes(title: &str, quote_depth: usize) -> String {
+    title
+        .split('\n')
+        .enumerate()
+        .map(|(index, mut line)| {
+            if index == 0 {
+                return line;
+            }
+            for _ in 0..quote_depth {
+                match line.trim_start().strip_prefix('>') {
+                    Some(rest) => line = rest,
+                    None => break,
+                }
+            }
+            line.trim_start()
+        })
+        .join("\n")
+}
+
 /// To avoid hitting `:` within the link label.
 fn reference_definition_without_label<'a>(complete_link: &'a str, link_label: &str) -> &'a str {
     // If the link label is escaped, we may not find it.
@@ -0,0 +1,430 @@
+//! Diagnostics about problems found in Markdown documents.
+use super::*;
+
+/// A problem found in a Markdown document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte range of the problem in the input.
+    pub range: Range<usize>,
+    /// What the problem is.
+    pub kind: DiagnosticKind,
+}
+
+/// Kind of a [`Diagnostic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// Formatting changed the anchor of the heading this link points to,
+    /// so the link would break.
+    ChangedAnchor {
+        /// Anchor before formatting.
+        old: String,
+        /// Anchor after formatting.
+        new: String,
+    },
+    /// A `#fragment` link points to no heading or HTML anchor in the document.
+    UnresolvedFragment {
+        /// The fragment without `#`.
+        fragment: String,
+    },
+    /// A relative link points to a file the resolver could not find.
+    UnresolvedFile {
+        /// The link destination without any `#fragment` or `?query`.
+        path: String,
+    },
+    /// The paragraph with this line was not wrapped because the line is longer
+    /// than [`Config::max_wrapped_line_length`].
+    LineTooLongToWrap {
+        /// Length of the line in bytes.
+        length: usize,
+    },
+    /// A [`transform::MARKER`] comment lists a transform that cannot be parsed,
+    /// so the next table is formatted without its transforms.
+    InvalidTransformMarker {
+        /// What is wrong with the transforms.
+        message: String,
+    },
+    /// The heading ended with punctuation that
+    /// [`Transform::StripHeadingPunctuation`] removed.
+    HeadingTrailingPunctuation {
+        /// The punctuation removed, e.g., `:`.
+        punctuation: String,
+    },
+    /// The heading text is wider than allowed, e.g., by [`long_headings`].
+    HeadingTooLong {
+        /// Width of the heading text in columns.
+        width: usize,
+    },
+    /// The document lacks a required section, reported by [`missing_sections`]
+    /// with an empty range where the section belongs.
+    MissingSection {
+        /// The required heading title.
+        title: String,
+    },
+    /// The footnote label is already defined with a different body,
+    /// reported by [`conflicting_footnotes`].
+    ConflictingFootnote {
+        /// The label without `[^` and `]`.
+        label: String,
+    },
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Range { start, end } = self.range;
+        write!(f, "{start}..{end}: ")?;
+        match &self.kind {
+            DiagnosticKind::ChangedAnchor { old, new } => {
+                write!(
+                    f,
+                    "link to `#{old}` breaks because the heading anchor is now `#{new}`"
+                )
+            }
+            DiagnosticKind::UnresolvedFragment { fragment } => {
+                write!(f, "no heading or anchor for link to `#{fragment}`")
+            }
+            DiagnosticKind::UnresolvedFile { path } => write!(f, "cannot find linked `{path}`"),
+            DiagnosticKind::LineTooLongToWrap { length } => {
+                write!(
+                    f,
+                    "paragraph not wrapped because a line is {length} bytes long"
+                )
+            }
+            DiagnosticKind::InvalidTransformMarker { message } => {
+                write!(f, "ignored transform marker: {message}")
+            }
+            DiagnosticKind::HeadingTrailingPunctuation { punctuation } => {
+                write!(f, "removed trailing `{punctuation}` from heading")
+            }
+            DiagnosticKind::HeadingTooLong { width } => {
+                write!(f, "heading is {width} columns wide")
+            }
+            DiagnosticKind::MissingSection { title } => {
+                write!(f, "missing required section `{title}`")
+            }
+            DiagnosticKind::ConflictingFootnote { label } => {
+                write!(f, "footnote `[^{label}]` is already defined differently")
+            }
+        }
+    }
+}
+
+/// Inspects the prose of documents as they are formatted,
+/// e.g., for spell checkers or terminology linters
+/// to reuse the formatter's parse instead of parsing the Markdown again.
+///
+/// Closures taking the same arguments as [`TextInspector::inspect_paragraph`]
+/// are inspectors:
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::MarkdownFormatter;
+/// let input = "#  Title\n\nTeh  first\nparagraph.\n\n> Second *one*.\n";
+/// let mut paragraphs = vec![];
+/// let rewrite = <MarkdownFormatter>::default()
+///     .format_with_inspector(input, &mut |text: &str, range: std::ops::Range<usize>| {
+///         paragraphs.push((text.to_owned(), &input[range]))
+///     })
+///     .unwrap();
+/// assert_eq!(rewrite, "# Title\n\nTeh  first\nparagraph.\n\n> Second *one*.\n");
+/// assert_eq!(
+///     paragraphs,
+///     [
+///         ("Teh  first\nparagraph.".into(), "Teh  first\nparagraph.\n"),
+///         ("Second *one*.".into(), "Second *one*.\n"),
+///     ]
+/// );
+/// ```
+pub trait TextInspector {
+    /// Inspect the formatted Markdown `text` of the paragraph at `range` of the input,
+    /// without the indentation or block quote markers of its containers.
+    fn inspect_paragraph(&mut self, text: &str, range: Range<usize>);
+}
+
+impl<F> TextInspector for F
+where
+    F: FnMut(&str, Range<usize>),
+{
+    fn inspect_paragraph(&mut self, text: &str, range: Range<usize>) {
+        self(text, range)
+    }
+}
+
+/// Report `#fragment` links that do not resolve to any heading anchor
+/// or HTML `id`/`name` attribute in the document.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::lint::dead_links;
+/// let input = "# Usage\n\nSee [usage](#usage) and [install](#install).\n";
+/// let diagnostics = dead_links(input);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(&input[diagnostics[0].range.clone()], "#install");
+/// ```
+pub fn dead_links(input: &str) -> Vec<Diagnostic> {
+    dead_links_with_resolver(input, |_| true)
+}
+
+/// Like [`dead_links`], but also report relative links to files
+/// for which `resolve_file` returns `false`.
+///
+/// `resolve_file` is called with each link destination that has no URL scheme,
+/// stripped of any `#fragment` or `?query`.
+pub fn dead_links_with_resolver<F>(input: &str, resolve_file: F) -> Vec<Diagnostic>
+where
+    F: FnMut(&str) -> bool,
+{
+    dead_links_with_slug_style(input, SlugStyle::GitHub, resolve_file)
+}
+
+/// Like [`dead_links_with_resolver`], but resolve `#fragment` links against
+/// heading anchors generated in `slug_style`
+/// instead of GitHub's, e.g., for documents published on GitLab.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{anchor::SlugStyle, lint::dead_links_with_slug_style};
+/// let input = "# 1 Intro\n\nSee [GitHub's](#1-intro) and [Pandoc's](#intro) anchors.\n";
+/// let diagnostics = dead_links_with_slug_style(input, SlugStyle::Pandoc, |_| true);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(&input[diagnostics[0].range.clone()], "#1-intro");
+/// ```
+pub fn dead_links_with_slug_style<F>(
+    input: &str,
+    slug_style: SlugStyle,
+    mut resolve_file: F,
+) -> Vec<Diagnostic>
+where
+    F: FnMut(&str) -> bool,
+{
+    let anchors = anchor::document_anchors(input, slug_style);
+    links::link_destinations(input)
+        .into_iter()
+        .filter_map(|(range, dest)| {
+            let kind = if let Some(fragment) = dest.strip_prefix('#') {
+                if anchors.contains(fragment) {
+                    return None;
+                }
+                DiagnosticKind::UnresolvedFragment {
+                    fragment: fragment.to_owned(),
+                }
+            } else {
+                let path = dest.split(['#', '?']).next().unwrap_or_default();
+                if has_url_scheme(path) || path.is_empty() || resolve_file(path) {
+                    return None;
+                }
+                DiagnosticKind::UnresolvedFile {
+                    path: path.to_owned(),
+                }
+            };
+            Some(Diagnostic { range, kind })
+        })
+        .collect()
+}
+
+/// Report headings whose text, without the `#` markers,
+/// is wider than `max_width` columns.
+/// The diagnostic ranges cover the heading text.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::lint::long_headings;
+/// let input = "# Short\n\n## A heading that goes on and on ##\n";
+/// let diagnostics = long_headings(input, 20);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(&input[diagnostics[0].range.clone()], "A heading that goes on and on");
+/// ```
+pub fn long_headings(input: &str, max_width: usize) -> Vec<Diagnostic> {
+    heading_texts(input)
+        .into_iter()
+        .filter_map(|heading| {
+            let width = text_width::width(&input[heading.text.clone()]);
+            (width > max_width).then_some(Diagnostic {
+                range: heading.text,
+                kind: DiagnosticKind::HeadingTooLong { width },
+            })
+        })
+        .collect()
+}
+
+/// Report the sections titled in `required` that `input` lacks,
+/// i.e., top-level headings with those titles, ignoring case,
+/// each with an empty range where
+/// [`MarkdownFormatter::insert_missing_sections`] would insert it.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{DiagnosticKind, lint::missing_sections};
+/// let input = "# Tool\n\n## Usage\n\nRun it.\n";
+/// let diagnostics = missing_sections(input, &["Install", "Usage", "License"]);
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].range, 8..8);
+/// assert_eq!(
+///     diagnostics[1].kind,
+///     DiagnosticKind::MissingSection { title: "License".into() }
+/// );
+/// ```
+pub fn missing_sections(input: &str, required: &[&str]) -> Vec<Diagnostic> {
+    sections::missing_sections(input, required)
+        .into_iter()
+        .map(|missing| Diagnostic {
+            range: missing.offset..missing.offset,
+            kind: DiagnosticKind::MissingSection {
+                title: required[missing.index].to_owned(),
+            },
+        })
+        .collect()
+}
+
+/// Report the top-level footnote definitions whose label is already defined
+/// with a different body, e.g., after merging documents,
+/// which [`MarkdownFormatter::dedup_footnotes`] cannot deduplicate.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{DiagnosticKind, lint::conflicting_footnotes};
+/// let input = "Text[^1].\n\n[^1]: First.\n\n[^1]: First.\n\n[^1]: Second.\n";
+/// let diagnostics = conflicting_footnotes(input);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(&input[diagnostics[0].range.clone()], "[^1]: Second.\n");
+/// assert_eq!(
+///     diagnostics[0].kind,
+///     DiagnosticKind::ConflictingFootnote { label: "1".into() }
+/// );
+/// ```
+pub fn conflicting_footnotes(input: &str) -> Vec<Diagnostic> {
+    footnotes::conflicting_footnotes(input)
+}
+
+/// Source ranges of a heading.
+pub(crate) struct HeadingText {
+    /// The whole heading.
+    pub range: Range<usize>,
+    /// The heading text, from its first to its last inline.
+    pub text: Range<usize>,
+    /// Start of the first ` — ` outside of inline markup,
+    /// if the heading is an ATX heading.
+    pub em_dash: Option<usize>,
+}
+
+/// The non-empty headings in `input`, in source order.
+pub(crate) fn heading_texts(input: &str) -> Vec<HeadingText> {
+    let mut headings = vec![];
+    let mut heading: Option<HeadingText> = None;
+    let mut inline_depth = 0;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        let Some(current) = &mut heading else {
+            if let Event::Start(Tag::Heading { .. }) = event {
+                let text = range.end..range.start;
+                heading = Some(HeadingText {
+                    range,
+                    text,
+                    em_dash: None,
+                });
+            }
+            continue;
+        };
+        match event {
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(heading) = heading.take().filter(|h| h.text.start < h.text.end) {
+                    headings.push(heading);
+                }
+                continue;
+            }
+            Event::Start(_) => inline_depth += 1,
+            Event::End(_) => inline_depth -= 1,
+            Event::Text(_) if inline_depth == 0 && current.em_dash.is_none() => {
+                let is_atx = input[current.range.clone()].trim_start().starts_with('#');
+                current.em_dash = input[range.clone()]
+                    .find(" — ")
+                    .filter(|_| is_atx)
+                    .map(|offset| range.start + offset);
+            }
+            _ => {}
+        }
+        current.text.start = current.text.start.min(range.start);
+        current.text.end = current.text.end.max(range.end);
+    }
+    headings
+}
+
+/// Check if the link destination starts with a URL scheme like `https:` or `mailto:`.
+fn has_url_scheme(dest: &str) -> bool {
+    dest.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_dead_links() {
+        let input = r##"# Usage
+<a name="install"></a>
+
+[usage](#usage) [install](#install) [missing](#missing)
+[readme](README.md#usage) [gone](gone.md) [web](https://example.com/gone.md)
+![image](./img/logo.png?raw=true) [mail](mailto:me@example.com)
+"##;
+        let diagnostics = dead_links_with_resolver(input, |path| path == "README.md");
+        let found = diagnostics
+            .iter()
+            .map(|d| (&input[d.range.clone()], d.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (
+                    "#missing",
+                    DiagnosticKind::UnresolvedFragment {
+                        fragment: "missing".into()
+                    }
+                ),
+                (
+                    "gone.md",
+                    DiagnosticKind::UnresolvedFile {
+                        path: "gone.md".into()
+                    }
+                ),
+                (
+                    "./img/logo.png?raw=true",
+                    DiagnosticKind::UnresolvedFile {
+                        path: "./img/logo.png".into()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(dead_links(input).len(), 1);
+    }
+
+    #[test]
+    fn report_lines_too_long_to_wrap() {
+        let long_line = "minified ".repeat(20);
+        let input = format!("Short  paragraph\nwrapped.\n\n> {long_line}\n> more\n");
+        let (output, diagnostics) = <MarkdownFormatter>::with_config(Config {
+            max_width: Some(20),
+            max_wrapped_line_length: Some(100),
+            ..Default::default()
+        })
+        .format_with_diagnostics(&input)
+        .unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Short  paragraph\nwrapped.\n\n> {}\n> more\n",
+                long_line.trim_end()
+            )
+        );
+        let found = diagnostics
+            .iter()
+            .map(|d| (&input[d.range.clone()], d.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [(
+                long_line.trim_end(),
+                DiagnosticKind::LineTooLongToWrap { length: 179 }
+            )]
+        );
+    }
+}
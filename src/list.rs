@@ -37,17 +37,6 @@ impl std::default::Default for ListMarker {
 }
 
 impl ListMarker {
-    // TODO(ytmimi) Add a configuration to allow incrementing ordered lists
-    #[allow(dead_code)]
-    pub(super) fn increment_count(&mut self) {
-        match self {
-            Self::Ordered { number, .. } => {
-                *number += 1;
-            }
-            Self::Unordered(_) => {}
-        }
-    }
-
     pub(super) fn indentation(&self) -> Cow<'static, str> {
         let indent_index = self.indentation_len();
 
@@ -80,15 +69,107 @@ impl ListMarker {
                 number,
                 ..
             } => {
-                let char_len = number.checked_ilog10().unwrap_or(0) + 1;
                 // + 2 to for '. '
-                zero_padding + (char_len + 2) as usize
+                zero_padding + number_width(*number) + 2
             }
             Self::Unordered(_) => 2,
         }
     }
 }
 
+/// Number of digits in `number`.
+pub(crate) fn number_width(number: usize) -> usize {
+    number.checked_ilog10().unwrap_or(0) as usize + 1
+}
+
+/// Source offsets of the ordered list items in `input`, sorted,
+/// each with the width of the widest number in its list,
+/// including zero padding, as `config` writes them.
+pub(crate) fn widest_list_numbers(input: &str, config: &Config) -> Vec<(usize, usize)> {
+    let mut items = vec![];
+    // Item offsets and widest number of each open list.
+    let mut lists: Vec<(Vec<usize>, usize)> = vec![];
+    // Number of the last item of each open list, to count up from.
+    let mut numbers: Vec<usize> = vec![];
+    let incrementing = config.ordered_list_numbering == Some(OrderedListNumbering::Incrementing);
+    for (event, range) in Parser::new_ext(input, config.parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::List(_)) => {
+                lists.push((vec![], 0));
+                numbers.push(0);
+            }
+            Event::Start(Tag::Item) => {
+                let (
+                    Some((starts, widest)),
+                    Ok(ListMarker::Ordered {
+                        zero_padding,
+                        number,
+                        ..
+                    }),
+                ) = (
+                    lists.last_mut(),
+                    config.list_marker_at_depth(&input[range.clone()], 0),
+                )
+                else {
+                    continue;
+                };
+                let last = numbers
+                    .last_mut()
+                    .expect("lists and numbers are pushed together");
+                let number = match incrementing && !starts.is_empty() {
+                    true => *last + 1,
+                    false => number,
+                };
+                *last = number;
+                starts.push(range.start);
+                *widest = (*widest).max(zero_padding + number_width(number));
+            }
+            Event::End(TagEnd::List(_)) => {
+                numbers.pop();
+                if let Some((starts, widest)) = lists.pop() {
+                    items.extend(starts.into_iter().map(|start| (start, widest)));
+                }
+            }
+            _ => {}
+        }
+    }
+    items.sort_unstable();
+    items
+}
+
+/// Source offsets of the items of the unordered lists in `input`
+/// parsed with `options` with at least one task list item, sorted.
+pub(crate) fn task_list_items(input: &str, options: Options) -> Vec<usize> {
+    let mut items = vec![];
+    // Item offsets of each open list, and whether it has a task list item.
+    let mut lists: Vec<(Vec<usize>, bool)> = vec![];
+    for (event, range) in Parser::new_ext(input, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::List(_)) => lists.push((vec![], false)),
+            Event::Start(Tag::Item) => {
+                if let Some((starts, _)) = lists.last_mut() {
+                    starts.push(range.start);
+                }
+            }
+            Event::TaskListMarker(_) => {
+                if let Some((_, has_task)) = lists.last_mut() {
+                    *has_task = true;
+                }
+            }
+            Event::End(TagEnd::List(ordered)) => {
+                if let Some((starts, has_task)) = lists.pop() {
+                    if has_task && !ordered {
+                        items.extend(starts);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    items.sort_unstable();
+    items
+}
+
 /// Marker symbol after the number for ordered lists.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum OrderedListMarker {
@@ -199,11 +280,8 @@ impl std::str::FromStr for ListMarker {
         };
 
         let number: usize = s[..offset].parse()?;
-        let zero_padding = if number != 0 {
-            s[..offset].chars().take_while(|b| *b == '0').count()
-        } else {
-            0
-        };
+        // Faithful to the digits written, e.g., 2 for `000.`.
+        let zero_padding = offset.saturating_sub(number_width(number));
 
         Ok(ListMarker::Ordered {
             zero_padding,
@@ -263,5 +341,8 @@ mod test {
         check_ordered_list!("20)", number = 20, padding = 0, marker = Parenthesis);
         check_ordered_list!("003.", number = 3, padding = 2, marker = Period);
         check_ordered_list!("003)", number = 3, padding = 2, marker = Parenthesis);
+        check_ordered_list!("0.", number = 0, padding = 0, marker = Period);
+        check_ordered_list!("000.", number = 0, padding = 2, marker = Period);
+        check_ordered_list!("010)", number = 10, padding = 1, marker = Parenthesis);
     }
 }
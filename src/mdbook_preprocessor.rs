@@ -0,0 +1,114 @@
+//! [mdBook](https://rust-lang.github.io/mdBook/) preprocessor formatting
+//! every chapter, e.g., to check in CI that a book is formatted.
+use ::mdbook::{
+    book::{Book, BookItem},
+    errors::{Error, Result},
+    preprocess::{Preprocessor, PreprocessorContext},
+};
+
+use super::*;
+
+/// Matches mdBook's `{{#include file.rs}}`-like directives,
+/// which are kept as is.
+const DIRECTIVE_PATTERN: &str = r"\{\{\s*#[a-z_]+[^}]*\}\}";
+
+/// [`Preprocessor`] formatting the content of every chapter with its
+/// [`MarkdownFormatter`].
+///
+/// mdBook directives like `{{#include file.rs}}` and `{{#playground example.rs}}`
+/// are treated like [`Config::shortcodes`],
+/// so they are never wrapped across lines or escaped.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter, mdbook_preprocessor::MdBookPreprocessor};
+/// use mdbook::preprocess::Preprocessor;
+///
+/// let formatter = <MarkdownFormatter>::with_config(Config::sichanghe_opinion());
+/// let preprocessor = MdBookPreprocessor::new(formatter);
+/// assert_eq!(preprocessor.name(), "markdown-fmt");
+/// ```
+pub struct MdBookPreprocessor<E = DefaultFormatterCombination>
+where
+    E: ExternalFormatter,
+{
+    formatter: MarkdownFormatter<E>,
+}
+
+impl<E> MdBookPreprocessor<E>
+where
+    E: ExternalFormatter,
+{
+    /// Create a [`MdBookPreprocessor`] formatting chapters with `formatter`.
+    pub fn new(mut formatter: MarkdownFormatter<E>) -> Self {
+        let pattern = match &formatter.config.shortcodes {
+            Some(shortcodes) => format!("(?:{shortcodes})|{DIRECTIVE_PATTERN}"),
+            None => DIRECTIVE_PATTERN.into(),
+        };
+        formatter.config.shortcodes =
+            Some(Regex::new(&pattern).expect("valid mdBook directive pattern"));
+        Self { formatter }
+    }
+
+    /// Format the content of every chapter of `book`.
+    fn format_book(&self, mut book: Book) -> Result<Book> {
+        let mut result = Ok(());
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            if result.is_err() {
+                return;
+            }
+            match self.formatter.clone().format(&chapter.content) {
+                Ok(content) => chapter.content = content,
+                Err(_) => {
+                    let message = format!("cannot format chapter `{}`", chapter.name);
+                    result = Err(Error::msg(message));
+                }
+            }
+        });
+        result.map(|()| book)
+    }
+}
+
+impl<E> Preprocessor for MdBookPreprocessor<E>
+where
+    E: ExternalFormatter,
+{
+    fn name(&self) -> &str {
+        "markdown-fmt"
+    }
+
+    fn run(&self, _ctx: &PreprocessorContext, book: Book) -> Result<Book> {
+        self.format_book(book)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::mdbook::book::Chapter;
+
+    use super::*;
+
+    #[test]
+    fn formats_chapters_keeping_directives() {
+        let mut book = Book::new();
+        let content = "#  Intro\n\nSee the {{#include ../src/main.rs:2:4}} lines\nand *this*.\n";
+        book.push_item(Chapter::new("Intro", content.into(), "intro.md", vec![]));
+
+        let formatter = <MarkdownFormatter>::with_config(Config {
+            max_width: Some(20),
+            ..Default::default()
+        });
+        let book = MdBookPreprocessor::new(formatter)
+            .format_book(book)
+            .unwrap();
+        let BookItem::Chapter(chapter) = book.iter().next().unwrap() else {
+            unreachable!();
+        };
+        assert_eq!(
+            chapter.content,
+            "# Intro\n\nSee the\n{{#include ../src/main.rs:2:4}}\nlines and *this*.\n"
+        );
+    }
+}
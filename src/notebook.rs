@@ -0,0 +1,266 @@
+//! Just enough JSON to find and rewrite the Markdown cells of Jupyter notebooks
+//! without touching any other byte.
+use super::*;
+
+/// The byte ranges of the `source` values of the Markdown cells in
+/// `notebook` JSON, with their decoded text.
+/// Return `None` if `notebook` is not valid notebook JSON.
+pub(crate) fn markdown_sources(notebook: &str) -> Option<Vec<(Range<usize>, String)>> {
+    let mut json = Json::new(notebook);
+    let fields = json.object()?;
+    json.end()?;
+    let Some((_, cells)) = fields.into_iter().find(|(key, _)| key == "cells") else {
+        return Some(vec![]);
+    };
+
+    let mut sources = vec![];
+    for cell in Json::at(notebook, cells.start).array()? {
+        let fields = Json::at(notebook, cell.start).object()?;
+        let field = |name: &str| fields.iter().find(|(key, _)| key == name);
+        let is_markdown = field("cell_type")
+            .and_then(|(_, value)| Json::at(notebook, value.start).string())
+            .is_some_and(|cell_type| cell_type == "markdown");
+        if let (true, Some((_, source))) = (is_markdown, field("source")) {
+            sources.push((source.clone(), source_text(notebook, source)?));
+        }
+    }
+    Some(sources)
+}
+
+/// Text of a cell `source` value, either a string or an array of line strings.
+fn source_text(notebook: &str, source: &Range<usize>) -> Option<String> {
+    match notebook[source.clone()].starts_with('[') {
+        true => Json::at(notebook, source.start)
+            .array()?
+            .into_iter()
+            .map(|line| Json::at(notebook, line.start).string())
+            .collect(),
+        false => Json::at(notebook, source.start).string(),
+    }
+}
+
+/// `text` as a cell `source` value laid out like `old_source`,
+/// i.e., as one string, or as an array of lines indented like its items.
+pub(crate) fn source_json(text: &str, old_source: &str) -> String {
+    let Some(items) = old_source.strip_prefix('[') else {
+        return string_json(text);
+    };
+    let item_separator = &items[..items.len() - items.trim_start().len()];
+    let closing_separator = {
+        let items = items.trim_end().strip_suffix(']').unwrap_or(items);
+        &items[items.trim_end().len()..]
+    };
+    if text.is_empty() {
+        return "[]".into();
+    }
+    let lines = text
+        .split_inclusive('\n')
+        .map(string_json)
+        .join(&format!(",{item_separator}"));
+    format!("[{item_separator}{lines}{closing_separator}]")
+}
+
+fn string_json(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for char in text.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => _ = write!(result, "\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Cursor over JSON `input`.
+struct Json<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Json<'a> {
+    fn new(input: &'a str) -> Self {
+        Self::at(input, 0)
+    }
+
+    fn at(input: &'a str, position: usize) -> Self {
+        Self { input, position }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn eat(&mut self, char: char) -> Option<()> {
+        self.skip_whitespace();
+        self.rest().starts_with(char).then(|| self.position += 1)
+    }
+
+    fn end(&mut self) -> Option<()> {
+        self.skip_whitespace();
+        self.rest().is_empty().then_some(())
+    }
+
+    /// Skip the next value and return its range.
+    fn value(&mut self) -> Option<Range<usize>> {
+        self.skip_whitespace();
+        let start = self.position;
+        match self.rest().chars().next()? {
+            '{' => _ = self.object()?,
+            '[' => _ = self.array()?,
+            '"' => _ = self.string()?,
+            _ => {
+                let rest = self.rest();
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return None;
+                }
+                self.position += len;
+            }
+        }
+        Some(start..self.position)
+    }
+
+    /// The keys and value ranges of the next object.
+    fn object(&mut self) -> Option<Vec<(String, Range<usize>)>> {
+        self.eat('{')?;
+        let mut fields = vec![];
+        if self.eat('}').is_some() {
+            return Some(fields);
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.eat(':')?;
+            fields.push((key, self.value()?));
+            if self.eat('}').is_some() {
+                return Some(fields);
+            }
+            self.eat(',')?;
+        }
+    }
+
+    /// The ranges of the items of the next array.
+    fn array(&mut self) -> Option<Vec<Range<usize>>> {
+        self.eat('[')?;
+        let mut items = vec![];
+        if self.eat(']').is_some() {
+            return Some(items);
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(']').is_some() {
+                return Some(items);
+            }
+            self.eat(',')?;
+        }
+    }
+
+    /// The decoded next string.
+    fn string(&mut self) -> Option<String> {
+        self.eat('"')?;
+        let mut result = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((index, char)) = chars.next() {
+            match char {
+                '"' => {
+                    self.position += index + 1;
+                    return Some(result);
+                }
+                '\\' => {
+                    let escaped = match chars.next()?.1 {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => match hex_code(&mut chars)? {
+                            // High surrogate, followed by `\u` and the low one.
+                            high @ 0xD800..=0xDBFF => {
+                                let (Some((_, '\\')), Some((_, 'u'))) =
+                                    (chars.next(), chars.next())
+                                else {
+                                    return None;
+                                };
+                                let low = hex_code(&mut chars)?.checked_sub(0xDC00)?;
+                                char::from_u32(0x10000 + ((high - 0xD800) << 10) + low)?
+                            }
+                            code => char::from_u32(code)?,
+                        },
+                        c @ ('"' | '\\' | '/') => c,
+                        _ => return None,
+                    };
+                    result.push(escaped);
+                }
+                c => result.push(c),
+            }
+        }
+        None
+    }
+}
+
+/// The code point of the 4 hexadecimal digits of a `\u` escape.
+fn hex_code(chars: &mut std::str::CharIndices) -> Option<u32> {
+    let digits = (0..4).map(|_| chars.next().map(|(_, c)| c));
+    u32::from_str_radix(&digits.collect::<Option<String>>()?, 16).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_only_markdown_cells() {
+        let notebook = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": [
+    "#  Title\n",
+    "\n",
+    "*  \"quoted\" é"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "metadata": {},
+   "outputs": [],
+   "source": ["#  not Markdown"]
+  },
+  {"cell_type": "markdown", "metadata": {}, "source": "Already   *fine*"}
+ ],
+ "metadata": {"kernelspec": {"name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+        let expected = notebook
+            .replace(r##""#  Title\n""##, r##""# Title\n""##)
+            .replace(r#""*  \"quoted\" é""#, r#""* \"quoted\" é""#);
+        let formatted = <MarkdownFormatter>::default()
+            .format_notebook(notebook)
+            .unwrap();
+        assert_eq!(formatted, expected);
+
+        assert!(
+            <MarkdownFormatter>::default()
+                .format_notebook("{\"cells\": [}")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Format staged files in one process, e.g., to back a `pre-commit` hook.
+use std::path::{Path, PathBuf};
+
+use super::*;
+use crate::batch::MARKDOWN_EXTENSIONS;
+
+/// A staged file that formatting changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StagedFile {
+    /// Path of the file, as given to [`format_staged`].
+    pub path: PathBuf,
+    /// The formatted staged content.
+    pub formatted: String,
+}
+
+/// Format the staged Markdown files among `paths` with `config`,
+/// and return those that formatting changes.
+///
+/// `read_staged` reads the staged content of a file,
+/// e.g., with `git show :README.md` or from the index through a Git library,
+/// so unstaged changes in the working tree do not affect the result.
+/// Files without one of the [`MARKDOWN_EXTENSIONS`] and those
+/// [`ignore::is_ignored`] are skipped without being read.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, precommit::format_staged};
+/// use std::collections::HashMap;
+///
+/// let index = HashMap::from([("README.md", "#  Title\n"), ("CHANGES.md", "# Done\n")]);
+/// let changed = format_staged(["README.md", "CHANGES.md", "main.rs"], &Config::default(), |path| {
+///     Ok(index[path.to_str().unwrap()].to_owned())
+/// })
+/// .unwrap();
+/// assert_eq!(changed.len(), 1);
+/// assert_eq!(changed[0].path.to_str(), Some("README.md"));
+/// assert_eq!(changed[0].formatted, "# Title\n");
+/// ```
+pub fn format_staged<P, F>(
+    paths: impl IntoIterator<Item = P>,
+    config: &Config,
+    mut read_staged: F,
+) -> Result<Vec<StagedFile>, PrecommitError>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path) -> std::io::Result<String>,
+{
+    let mut changed = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        let is_markdown = path
+            .extension()
+            .is_some_and(|extension| MARKDOWN_EXTENSIONS.iter().any(|e| extension == *e));
+        if !is_markdown || matches!(ignore::is_ignored(path), Ok(true)) {
+            continue;
+        }
+        let staged = read_staged(path).map_err(|error| PrecommitError::Read {
+            path: path.into(),
+            error,
+        })?;
+        let formatted = <MarkdownFormatter>::with_config(config.clone())
+            .format(&staged)
+            .map_err(|_| PrecommitError::Format { path: path.into() })?;
+        if formatted != staged {
+            changed.push(StagedFile {
+                path: path.into(),
+                formatted,
+            });
+        }
+    }
+    Ok(changed)
+}
+
+/// Error in [`format_staged`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PrecommitError {
+    /// The staged content of a file could not be read.
+    Read {
+        /// Path of the file.
+        path: PathBuf,
+        /// The error the callback returned.
+        error: std::io::Error,
+    },
+    /// A file could not be formatted.
+    Format {
+        /// Path of the file.
+        path: PathBuf,
+    },
+}
+
+impl std::fmt::Display for PrecommitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read { path, error } => {
+                write!(f, "cannot read staged `{}`: {error}", path.display())
+            }
+            Self::Format { path } => write!(f, "cannot format `{}`", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for PrecommitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { error, .. } => Some(error),
+            Self::Format { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Top-level sections of a document, i.e., headings outside of containers
+//! with the blocks up to the next heading at the same or a shallower level.
+use super::*;
+
+/// A top-level heading and the section it starts.
+struct Section {
+    /// From the start of the heading's line to the next heading
+    /// at the same or a shallower level.
+    range: Range<usize>,
+    /// Normalized heading text.
+    title: String,
+}
+
+/// A top-level link reference or footnote definition
+/// and the normalized label it defines.
+struct Definition {
+    /// Whole lines of the definition, including the last newline.
+    range: Range<usize>,
+    label: String,
+    is_footnote: bool,
+}
+
+/// Normalize a heading title for matching: ignore case and collapse whitespace.
+fn normalize_title(title: &str) -> String {
+    links::normalize_label(title)
+}
+
+/// Extend `range` to whole lines, including the newline ending the last line.
+pub(crate) fn whole_lines(input: &str, range: Range<usize>) -> Range<usize> {
+    let start = input[..range.start]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let end = input[range.end..]
+        .find('\n')
+        .map_or(input.len(), |index| range.end + index + 1);
+    let end = match input[..range.end].ends_with('\n') {
+        true => range.end,
+        false => end,
+    };
+    start..end
+}
+
+/// The top-level headings of a document, and the definitions and references
+/// that move with sections.
+struct Outline {
+    /// (start of the line, level, text) of each top-level heading.
+    headings: Vec<(usize, HeadingLevel, String)>,
+    definitions: Vec<Definition>,
+    /// (is footnote, normalized label, source offset) of each reference.
+    references: Vec<(bool, String, usize)>,
+}
+
+fn outline(input: &str) -> Outline {
+    let parser = Parser::new_ext(input, parser_options());
+    let mut definitions = parser
+        .reference_definitions()
+        .iter()
+        .map(|(label, definition)| Definition {
+            range: whole_lines(input, definition.span.clone()),
+            label: links::normalize_label(label),
+            is_footnote: false,
+        })
+        // Only definitions outside of containers.
+        .filter(|definition| {
+            input[definition.range.start..]
+                .trim_start_matches(' ')
+                .starts_with('[')
+        })
+        .collect_vec();
+    let mut references = vec![];
+    let mut headings = vec![];
+    let mut heading = None;
+    let mut depth = 0_usize;
+    for (event, range) in parser.into_offset_iter() {
+        let depth_change = match event {
+            Event::Start(_) => 1,
+            Event::End(_) => -1,
+            _ => 0,
+        };
+        match event {
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                heading = Some((whole_lines(input, range).start, level, String::new()));
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) if depth == 0 => {
+                definitions.push(Definition {
+                    range: whole_lines(input, range),
+                    label: links::normalize_label(&label),
+                    is_footnote: true,
+                });
+            }
+            Event::Start(Tag::Link { link_type, id, .. } | Tag::Image { link_type, id, .. })
+                if matches!(
+                    link_type,
+                    LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+                ) =>
+            {
+                references.push((false, links::normalize_label(&id), range.start));
+            }
+            Event::FootnoteReference(label) => {
+                references.push((true, links::normalize_label(&label), range.start));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, title)) = heading.as_mut() {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) if depth == 1 => {
+                headings.extend(heading.take());
+            }
+            _ => {}
+        }
+        depth = depth.saturating_add_signed(depth_change);
+    }
+    Outline {
+        headings,
+        definitions,
+        references,
+    }
+}
+
+/// The level of the sections titled in `titles`, i.e.,
+/// the shallowest level of the top-level headings with those titles.
+fn section_level(
+    headings: &[(usize, HeadingLevel, String)],
+    titles: &[String],
+) -> Option<HeadingLevel> {
+    headings
+        .iter()
+        .filter(|(_, _, title)| titles.contains(&normalize_title(title)))
+        .map(|(_, level, _)| *level)
+        .min()
+}
+
+/// The sections started by the top-level headings at `level`.
+fn sections_at(
+    headings: &[(usize, HeadingLevel, String)],
+    level: HeadingLevel,
+    input_len: usize,
+) -> Vec<Section> {
+    headings
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, heading_level, _))| *heading_level == level)
+        .map(|(index, (start, _, title))| {
+            let end = headings[index + 1..]
+                .iter()
+                .find(|(_, next_level, _)| *next_level <= level)
+                .map_or(input_len, |(next_start, _, _)| *next_start);
+            Section {
+                range: *start..end,
+                title: normalize_title(title),
+            }
+        })
+        .collect()
+}
+
+/// A required section missing from a document.
+pub(crate) struct MissingSection {
+    /// Where to insert the section.
+    pub offset: usize,
+    pub level: HeadingLevel,
+    /// Index of the section's title in the required titles.
+    pub index: usize,
+}
+
+/// Find the sections titled in `required` that `input` lacks,
+/// each to be inserted after the section of the previous title in `required`,
+/// or before that of the next title if there is no previous one,
+/// or at the end.
+///
+/// Sections are at the level of the existing required sections,
+/// or nested in the document's title if it has a single shallowest heading.
+pub(crate) fn missing_sections(input: &str, required: &[&str]) -> Vec<MissingSection> {
+    let titles = required
+        .iter()
+        .map(|title| normalize_title(title))
+        .collect_vec();
+    let headings = outline(input).headings;
+    let level = section_level(&headings, &titles).unwrap_or_else(|| {
+        let Some(shallowest) = headings.iter().map(|(_, level, _)| *level).min() else {
+            return HeadingLevel::H1;
+        };
+        match headings
+            .iter()
+            .filter(|(_, level, _)| *level == shallowest)
+            .count()
+        {
+            1 => HeadingLevel::try_from(shallowest as usize + 1).unwrap_or(shallowest),
+            _ => shallowest,
+        }
+    });
+    let sections = sections_at(&headings, level, input.len());
+    let section = |title: &String| sections.iter().find(|section| section.title == *title);
+    titles
+        .iter()
+        .enumerate()
+        .filter(|(_, title)| section(title).is_none())
+        .map(|(index, _)| {
+            let offset = titles[..index]
+                .iter()
+                .rev()
+                .find_map(section)
+                .map(|section| section.range.end)
+                .or_else(|| {
+                    let next = titles[index + 1..].iter().find_map(section);
+                    next.map(|section| section.range.start)
+                })
+                .unwrap_or(input.len());
+            MissingSection {
+                offset,
+                level,
+                index,
+            }
+        })
+        .collect()
+}
+
+/// `input` with stub sections inserted for the titles in `templates`
+/// that it lacks, see [`MarkdownFormatter::insert_missing_sections`].
+pub(crate) fn insert_missing_sections(input: &str, templates: &[(&str, &str)]) -> String {
+    let titles = templates.iter().map(|(title, _)| *title).collect_vec();
+    // Sections inserted at the same offset go in one edit to keep their order.
+    let mut inserts: Vec<(usize, Vec<String>)> = vec![];
+    for missing in missing_sections(input, &titles) {
+        let (title, stub) = templates[missing.index];
+        let hashes = "#".repeat(missing.level as usize);
+        let section = match stub.trim() {
+            "" => format!("{hashes} {title}\n"),
+            stub => format!("{hashes} {title}\n\n{stub}\n"),
+        };
+        match inserts
+            .iter_mut()
+            .find(|(offset, _)| *offset == missing.offset)
+        {
+            Some((_, sections)) => sections.push(section),
+            None => inserts.push((missing.offset, vec![section])),
+        }
+    }
+    let edits = inserts
+        .into_iter()
+        .map(|(offset, sections)| {
+            let before = &input[..offset];
+            let separator = match () {
+                _ if before.is_empty() || before.ends_with("\n\n") => "",
+                _ if before.ends_with('\n') => "\n",
+                _ => "\n\n",
+            };
+            let mut text = format!("{separator}{}", sections.join("\n"));
+            if offset < input.len() {
+                text.push('\n');
+            }
+            (offset..offset, text)
+        })
+        .collect();
+    apply_edits(input, edits)
+}
+
+/// Rearrange the sections of `input` whose titles are in `order` into that order,
+/// see [`MarkdownFormatter::reorder_sections`].
+pub(crate) fn reorder_sections(input: &str, order: &[&str]) -> String {
+    let order = order
+        .iter()
+        .map(|title| normalize_title(title))
+        .collect_vec();
+    let Outline {
+        headings,
+        definitions,
+        references,
+    } = outline(input);
+    let Some(level) = section_level(&headings, &order) else {
+        return input.to_owned();
+    };
+    let sections = sections_at(&headings, level, input.len());
+    let section_of = |offset: usize| {
+        sections
+            .iter()
+            .position(|section| section.range.contains(&offset))
+    };
+
+    // Move each definition in a section to the first section using it.
+    let mut removed = vec![vec![]; sections.len()];
+    let mut appended = vec![String::new(); sections.len()];
+    for definition in &definitions {
+        let Some(containing) = section_of(definition.range.start) else {
+            continue;
+        };
+        let owner = references
+            .iter()
+            .find(|(is_footnote, label, _)| {
+                *is_footnote == definition.is_footnote && *label == definition.label
+            })
+            .and_then(|(_, _, offset)| section_of(*offset));
+        match owner {
+            Some(owner) if owner != containing => {
+                removed[containing].push(definition.range.clone());
+                appended[owner].push_str(input[definition.range.clone()].trim_end());
+                appended[owner].push_str("\n\n");
+            }
+            _ => {}
+        }
+    }
+    let section_texts = sections
+        .iter()
+        .zip(removed)
+        .zip(appended)
+        .map(|((section, removed), appended)| {
+            let edits = removed
+                .into_iter()
+                .map(|range| {
+                    (
+                        range.start - section.range.start..range.end - section.range.start,
+                        String::new(),
+                    )
+                })
+                .collect();
+            let mut text = apply_edits(&input[section.range.clone()], edits);
+            let trimmed_len = text.trim_end().len();
+            text.truncate(trimmed_len);
+            text.push_str("\n\n");
+            text.push_str(&appended);
+            text
+        })
+        .collect_vec();
+
+    // Sections separated by shallower headings are reordered separately,
+    // and sections not in `order` keep their positions.
+    let rank = |index: usize| {
+        order
+            .iter()
+            .position(|title| *title == sections[index].title)
+    };
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for index in 0..sections.len() {
+        match groups.last_mut() {
+            Some(group) if sections[index - 1].range.end == sections[index].range.start => {
+                group.push(index)
+            }
+            _ => groups.push(vec![index]),
+        }
+    }
+    let mut arrangement = (0..sections.len()).collect_vec();
+    for group in groups {
+        let slots = group
+            .iter()
+            .copied()
+            .filter(|&index| rank(index).is_some())
+            .collect_vec();
+        let mut ranked = slots.clone();
+        ranked.sort_by_key(|&index| rank(index));
+        for (slot, index) in slots.into_iter().zip(ranked) {
+            arrangement[slot] = index;
+        }
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut written = 0;
+    for (section, &index) in sections.iter().zip(&arrangement) {
+        output.push_str(&input[written..section.range.start]);
+        output.push_str(&section_texts[index]);
+        written = section.range.end;
+    }
+    output.push_str(&input[written..]);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reorder_within_shallower_headings() {
+        let input = "\
+# A
+
+## Two
+
+2[^note]
+
+## Other
+
+## One
+
+1
+
+# B
+
+## Two
+
+## One
+
+[^note]: Moves to the first section using it.
+";
+        assert_eq!(
+            reorder_sections(input, &["one", "TWO"]),
+            "\
+# A
+
+## One
+
+1
+
+## Other
+
+## Two
+
+2[^note]
+
+[^note]: Moves to the first section using it.
+
+# B
+
+## One
+
+## Two
+
+"
+        );
+        assert_eq!(reorder_sections(input, &["Missing"]), input);
+    }
+
+    #[test]
+    fn insert_missing_at_section_level() {
+        let input = "\
+# A
+
+## Two
+
+2
+";
+        let templates = [("One", "1"), ("Two", ""), ("Three", ""), ("Four", "4")];
+        assert_eq!(
+            insert_missing_sections(input, &templates),
+            "\
+# A
+
+## One
+
+1
+
+## Two
+
+2
+
+## Three
+
+## Four
+
+4
+"
+        );
+        assert_eq!(insert_missing_sections("", &[("Title", "")]), "# Title\n");
+        assert_eq!(insert_missing_sections(input, &[("two", "")]), input);
+    }
+}
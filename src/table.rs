@@ -1,5 +1,49 @@
 use super::*;
 
+/// A parsed table given to [`Config::table_hook`] before it is written,
+/// to transform its cells without parsing the formatted output.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter, Table};
+/// fn link_issues(table: &mut Table) {
+///     for row in table.rows.iter_mut().skip(1) {
+///         if let Some(cell) = row.first_mut() {
+///             cell.text = format!("[#{0}](https://example.com/issues/{0})", cell.text);
+///         }
+///     }
+/// }
+/// let config = Config {
+///     table_hook: Some(link_issues),
+///     ..Config::default()
+/// };
+/// let input = "| Issue | Status |\n|---|---|\n| 1 | open |\n";
+/// let output = <MarkdownFormatter>::with_config(config).format(input).unwrap();
+/// assert_eq!(
+///     output,
+///     "| Issue                              | Status |
+/// | ---------------------------------- | ------ |
+/// | [#1](https://example.com/issues/1) | open   |
+/// "
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    /// Alignment of each column, which sets the number of columns.
+    pub alignments: Vec<Alignment>,
+    /// Rows of cells, the first being the header.
+    /// Body rows may have fewer cells than there are columns.
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// A table cell.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cell {
+    /// Formatted Markdown of the cell.
+    pub text: String,
+    /// Source range of the cell, or an empty range if it has none.
+    pub range: Range<usize>,
+}
+
 pub(super) struct TableState<'a> {
     /// Alignment markers for HTML rendering
     /// * :-: center alignment
@@ -14,10 +58,19 @@ pub(super) struct TableState<'a> {
     max_column_width: Vec<usize>,
     /// Table rows
     body: Vec<Vec<Cow<'a, str>>>,
+    /// Source ranges of the header cells.
+    header_ranges: Vec<Range<usize>>,
+    /// Source ranges of the cells of each row.
+    body_ranges: Vec<Vec<Range<usize>>>,
     /// Keep track of whether or not we're writing to the headers
     write_to_body: bool,
     /// Keep track of Which cell we're currently operating on.
     col_index: usize,
+    /// Delimiter row cells as typed in the source, e.g., `:---`,
+    /// if cells should not be padded to align columns.
+    compact_delimiters: Option<Vec<String>>,
+    /// Lines of the Pandoc table caption right after the table, e.g., `: Caption`.
+    caption: Vec<&'a str>,
 }
 
 impl<'a> Write for TableState<'a> {
@@ -44,18 +97,36 @@ impl<'a> TableState<'a> {
             headers: Vec::with_capacity(capacity),
             max_column_width: vec![3; capacity],
             body: vec![],
+            header_ranges: Vec::with_capacity(capacity),
+            body_ranges: vec![],
             write_to_body: false,
             col_index: 0,
+            compact_delimiters: None,
+            caption: vec![],
         }
     }
 
+    /// Do not pad cells, and keep the delimiter row of the `source` table
+    /// so its dash counts do not change with the cell widths.
+    pub(super) fn compact(mut self, source: &str) -> Self {
+        let delimiters = source_delimiters(source)
+            .filter(|delimiters| delimiters.len() == self.alignment.len())
+            .unwrap_or_else(|| self.alignment.iter().map(minimal_delimiter).collect());
+        self.compact_delimiters = Some(delimiters);
+        self
+    }
+
     /// Check if we've written anything to the table state yet
     pub(super) fn is_empty(&self) -> bool {
         self.headers.is_empty() && self.body.is_empty()
     }
 
     /// Write some values to the table state.
+    ///
+    /// Pipes that would end the current cell, e.g., from parsed text, are escaped.
     pub(super) fn write(&mut self, value: Cow<'a, str>) {
+        let after_backslash = self.current_cell().is_some_and(|cell| cell.ends_with('\\'));
+        let value = escape_pipes(value, after_backslash);
         if self.write_to_body {
             // push or update the body
             self.write_cell(value)
@@ -65,6 +136,16 @@ impl<'a> TableState<'a> {
         }
     }
 
+    /// Check if the table has a caption, so following rows continue it.
+    pub(super) fn has_caption(&self) -> bool {
+        !self.caption.is_empty()
+    }
+
+    /// Add a line of the table caption, to write as is after the table.
+    pub(super) fn push_caption_line(&mut self, line: &'a str) {
+        self.caption.push(line);
+    }
+
     /// Allows the caller to advance the internal col_index.
     /// All subsequent calls to `write` will affect the next cell
     pub(super) fn increment_col_index(&mut self) {
@@ -74,12 +155,33 @@ impl<'a> TableState<'a> {
     /// Update the table state to start writing to the table body
     pub(super) fn push_row(&mut self) {
         self.body.push(Vec::with_capacity(self.alignment.len()));
+        self.body_ranges.push(vec![]);
         self.write_to_body = true;
         self.col_index = 0;
     }
 
+    /// Record the source range of the cell about to be written.
+    pub(super) fn set_cell_range(&mut self, range: Range<usize>) {
+        let ranges = match self.write_to_body {
+            true => self.body_ranges.last_mut(),
+            false => Some(&mut self.header_ranges),
+        };
+        if let Some(ranges) = ranges {
+            ranges.resize(self.col_index, 0..0);
+            ranges.push(range);
+        }
+    }
+
+    fn current_cell(&self) -> Option<&str> {
+        let cells = match self.write_to_body {
+            true => self.body.last()?,
+            false => &self.headers,
+        };
+        cells.get(self.col_index).map(AsRef::as_ref)
+    }
+
     fn write_header(&mut self, text: Cow<'a, str>) {
-        let header_width = unicode_str_width(&text);
+        let header_width = text_width::width(&text);
         if let Some(column_header) = self.headers.get_mut(self.col_index) {
             *column_header += text;
 
@@ -101,16 +203,137 @@ impl<'a> TableState<'a> {
             .expect("can only write cells after push_row called");
 
         if let Some(cell_value) = row.get_mut(self.col_index) {
-            let current_width = unicode_str_width(cell_value) + unicode_str_width(&text);
+            let current_width = text_width::width(cell_value) + text_width::width(&text);
             *cell_value += text;
             self.update_column_width(self.col_index, current_width);
         } else {
-            let cell_width = unicode_str_width(&text);
+            let cell_width = text_width::width(&text);
             row.push(text);
             self.update_column_width(self.col_index, cell_width);
         }
     }
 
+    /// Stably sort the body rows by their cells in the column with `header`,
+    /// if there is one, comparing cells with `compare`.
+    /// Missing cells are empty.
+    pub(super) fn sort_rows<F>(&mut self, header: &str, mut compare: F)
+    where
+        F: FnMut(&str, &str) -> std::cmp::Ordering,
+    {
+        let Some(column) = self.column(header) else {
+            return;
+        };
+        fn cell<'r>(row: &'r [Cow<str>], column: usize) -> &'r str {
+            row.get(column).map_or("", |cell| cell.as_ref())
+        }
+        let mut ranges = std::mem::take(&mut self.body_ranges);
+        ranges.resize(self.body.len(), vec![]);
+        let mut rows = std::mem::take(&mut self.body)
+            .into_iter()
+            .zip(ranges)
+            .collect_vec();
+        rows.sort_by(|(a, _), (b, _)| compare(cell(a, column), cell(b, column)));
+        (self.body, self.body_ranges) = rows.into_iter().unzip();
+    }
+
+    /// Keep only the columns with `headers`, in that order,
+    /// unless the table has none of them.
+    pub(super) fn select_columns(&mut self, headers: &[String]) {
+        let columns = headers.iter().filter_map(|header| self.column(header));
+        self.retain_columns(columns.unique().collect());
+    }
+
+    /// Remove the columns with `headers`, unless that would remove all columns.
+    pub(super) fn drop_columns(&mut self, headers: &[String]) {
+        let dropped = headers
+            .iter()
+            .filter_map(|header| self.column(header))
+            .collect_vec();
+        let columns = (0..self.alignment.len()).filter(|column| !dropped.contains(column));
+        self.retain_columns(columns.collect());
+    }
+
+    /// Index of the column with `header`, ignoring surrounding whitespace.
+    fn column(&self, header: &str) -> Option<usize> {
+        self.headers.iter().position(|h| h.trim() == header.trim())
+    }
+
+    /// Rebuild the table from its `columns` at these indexes, in this order.
+    fn retain_columns(&mut self, columns: Vec<usize>) {
+        if columns.is_empty() {
+            return;
+        }
+        fn select<T: Clone + Default>(cells: &[T], columns: &[usize]) -> Vec<T> {
+            let cell = |&column: &usize| cells.get(column).cloned().unwrap_or_default();
+            columns.iter().map(cell).collect()
+        }
+        self.alignment = columns
+            .iter()
+            .map(|&column| self.alignment[column])
+            .collect();
+        self.headers = select(&self.headers, &columns);
+        self.header_ranges = select(&self.header_ranges, &columns);
+        self.max_column_width = select(&self.max_column_width, &columns);
+        for row in &mut self.body {
+            *row = select(row, &columns);
+        }
+        for row in &mut self.body_ranges {
+            *row = select(row, &columns);
+        }
+        if let Some(delimiters) = &mut self.compact_delimiters {
+            *delimiters = select(delimiters, &columns);
+        }
+    }
+
+    /// Let `hook` transform the cells and alignments of the table,
+    /// then rebuild the table from them, keeping its caption.
+    /// Line breaks in cells are replaced with spaces,
+    /// and cells past the last column are dropped.
+    pub(super) fn apply_hook(&mut self, hook: fn(&mut Table)) {
+        fn cells(texts: &[Cow<str>], ranges: &[Range<usize>]) -> Vec<Cell> {
+            let cell = |(column, text): (usize, &Cow<str>)| Cell {
+                text: text.to_string(),
+                range: ranges.get(column).cloned().unwrap_or_default(),
+            };
+            texts.iter().enumerate().map(cell).collect()
+        }
+        let mut rows = vec![cells(&self.headers, &self.header_ranges)];
+        for (index, row) in self.body.iter().enumerate() {
+            let ranges = self.body_ranges.get(index).map_or(&[][..], Vec::as_slice);
+            rows.push(cells(row, ranges));
+        }
+        let mut table = Table {
+            alignments: std::mem::take(&mut self.alignment),
+            rows,
+        };
+        hook(&mut table);
+
+        let mut state = TableState::new(table.alignments);
+        let columns = state.alignment.len();
+        state.compact_delimiters =
+            self.compact_delimiters
+                .take()
+                .map(|delimiters| match delimiters.len() == columns {
+                    true => delimiters,
+                    false => state.alignment.iter().map(minimal_delimiter).collect(),
+                });
+        state.caption = std::mem::take(&mut self.caption);
+        let mut rows = table.rows.into_iter();
+        let mut header = rows.next().unwrap_or_default();
+        header.resize(columns, Cell::default());
+        for (index, row) in std::iter::once(header).chain(rows).enumerate() {
+            if index > 0 {
+                state.push_row();
+            }
+            for cell in row.into_iter().take(columns) {
+                state.set_cell_range(cell.range);
+                state.write(cell.text.replace(['\n', '\r'], " ").into());
+                state.increment_col_index();
+            }
+        }
+        *self = state;
+    }
+
     fn update_column_width(&mut self, index: usize, column_width: usize) {
         if let Some(old_column_width) = self.max_column_width.get_mut(index) {
             if *old_column_width < column_width {
@@ -119,31 +342,48 @@ impl<'a> TableState<'a> {
         }
     }
 
+    /// Write out the table, each row on its own line starting with `|`,
+    /// so callers only need to prefix rows with their container's indentation.
     pub(super) fn format(self) -> Result<String, std::fmt::Error> {
         let mut result = String::new();
         self.rewrite_header(&mut result)?;
         self.rewrite_alignment(&mut result)?;
         self.rewrite_body(&mut result)?;
+        for line in &self.caption {
+            write!(result, "\n{line}")?;
+        }
         Ok(result)
     }
 
-    fn write_wth_padding(buffer: &mut String, value: &str, mut size: usize) -> std::fmt::Result {
-        let offset = UnicodeSegmentation::graphemes(value, true)
-            .map(|grapheme| unicode_str_width(grapheme).saturating_sub(1))
-            .sum();
-        size = size.saturating_sub(offset);
-        write!(buffer, " {value:<0$} |", size)
+    fn write_wth_padding(&self, buffer: &mut String, value: &str, size: usize) -> std::fmt::Result {
+        match self.compact_delimiters {
+            Some(_) => write!(buffer, " {value} |"),
+            None => Self::write_padded(buffer, value, size),
+        }
+    }
+
+    /// Write `value` padded to `size` columns, then the closing `|`.
+    /// The space before the `|` keeps a literal `\` ending `value` from escaping it.
+    fn write_padded(buffer: &mut String, value: &str, size: usize) -> std::fmt::Result {
+        write!(buffer, " {} |", text_width::pad(value, size))
     }
 
     fn rewrite_header(&self, buffer: &mut String) -> std::fmt::Result {
+        buffer.push('|');
         for (header, width) in self.headers.iter().zip(self.max_column_width.iter()) {
-            Self::write_wth_padding(buffer, header, *width)?;
+            self.write_wth_padding(buffer, header, *width)?;
         }
         Ok(())
     }
 
     fn rewrite_alignment(&self, buffer: &mut String) -> std::fmt::Result {
-        writeln!(buffer)?;
+        buffer.push_str("\n|");
+        if let Some(delimiters) = &self.compact_delimiters {
+            for delimiter in delimiters {
+                self.write_wth_padding(buffer, delimiter, 0)?;
+            }
+            return Ok(());
+        }
         for (alignment, width) in self.alignment.iter().zip(self.max_column_width.iter()) {
             let alignment = match alignment {
                 Alignment::Center => {
@@ -164,21 +404,21 @@ impl<'a> TableState<'a> {
                     "-".repeat(*width)
                 }
             };
-            Self::write_wth_padding(buffer, &alignment, *width)?;
+            self.write_wth_padding(buffer, &alignment, *width)?;
         }
         Ok(())
     }
 
     fn rewrite_body(&self, buffer: &mut String) -> std::fmt::Result {
         for row in self.body.iter() {
-            writeln!(buffer)?;
+            buffer.push_str("\n|");
             for either_or_both in row.iter().zip_longest(self.max_column_width.iter()) {
                 match either_or_both {
                     EitherOrBoth::Both(cell, width) => {
-                        Self::write_wth_padding(buffer, cell, *width)?;
+                        self.write_wth_padding(buffer, cell, *width)?;
                     }
                     EitherOrBoth::Right(width) => {
-                        Self::write_wth_padding(buffer, "", *width)?;
+                        self.write_wth_padding(buffer, "", *width)?;
                     }
                     EitherOrBoth::Left(_) => {
                         // There may be fewer cells in a row, but there should never be more cells.
@@ -191,3 +431,148 @@ impl<'a> TableState<'a> {
         Ok(())
     }
 }
+
+/// Format a table from its `rows` of cells, the first being the header,
+/// with the same alignment and padding as tables in formatted Markdown.
+///
+/// The table has one column per alignment in `alignments`:
+/// extra cells are dropped and missing cells are left empty.
+/// Pipes in cells are escaped, and line breaks are replaced with spaces.
+/// Only [`Config::compact_tables`] of `config` applies.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{format_table, Alignment, Config};
+/// let rows = [
+///     vec!["Name".into(), "Size".into()],
+///     vec!["a|b".into(), "1".into()],
+///     vec!["c".into()],
+/// ];
+/// let table = format_table(&rows, &[Alignment::None, Alignment::Right], &Config::default());
+/// assert_eq!(
+///     table,
+///     "| Name | Size |\n| ---- | ---: |\n| a\\|b | 1    |\n| c    |      |"
+/// );
+/// ```
+pub fn format_table(rows: &[Vec<String>], alignments: &[Alignment], config: &Config) -> String {
+    let mut state = TableState::new(alignments.to_vec());
+    if config.compact_tables {
+        state = state.compact("");
+    }
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            state.push_row();
+        }
+        for column in 0..alignments.len() {
+            let cell = row.get(column).map_or("", String::as_str);
+            match cell.contains(['\n', '\r']) {
+                true => state.write(cell.replace(['\n', '\r'], " ").into()),
+                false => state.write(cell.into()),
+            }
+            state.increment_col_index();
+        }
+    }
+    state.format().expect("writing to a `String` does not fail")
+}
+
+/// Escape the pipes in `value` not already preceded by a backslash,
+/// including one ending the text before `value` if `after_backslash`.
+///
+/// In GFM tables, `\\|` is an escaped pipe even if the backslash itself is escaped,
+/// so a pipe only ends a cell if no backslash precedes it.
+fn escape_pipes(value: Cow<'_, str>, after_backslash: bool) -> Cow<'_, str> {
+    let is_unescaped_pipe = |(index, char): (usize, char)| {
+        char == '|'
+            && match index {
+                0 => !after_backslash,
+                _ => !value[..index].ends_with('\\'),
+            }
+    };
+    if !value.char_indices().any(is_unescaped_pipe) {
+        return value;
+    }
+    let mut result = String::with_capacity(value.len() + 1);
+    for (index, char) in value.char_indices() {
+        if is_unescaped_pipe((index, char)) {
+            result.push('\\');
+        }
+        result.push(char);
+    }
+    result.into()
+}
+
+/// The shortest delimiter row cell for `alignment`.
+fn minimal_delimiter(alignment: &Alignment) -> String {
+    match alignment {
+        Alignment::Center => ":-:",
+        Alignment::Left => ":--",
+        Alignment::Right => "--:",
+        Alignment::None => "---",
+    }
+    .into()
+}
+
+/// The delimiter row cells of the `source` table, e.g., `["---", ":-:"]`
+/// for `| a | b |\n|---|:-:|`.
+/// Prefixes such as block quote markers are skipped.
+fn source_delimiters(source: &str) -> Option<Vec<String>> {
+    let row = source.lines().nth(1)?;
+    let row = &row[row.find(['|', '-', ':'])?..];
+    let row = row.trim_end();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|')
+        .map(|cell| {
+            let cell = cell.trim();
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            (!dashes.is_empty() && dashes.bytes().all(|b| b == b'-')).then(|| cell.into())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_hook_gets_cell_ranges() {
+        fn ranges(table: &mut Table) {
+            for cell in table.rows.iter_mut().flatten() {
+                cell.text = format!("{}@{:?}", cell.text, cell.range);
+            }
+            table.alignments.pop();
+        }
+        let mut config = Config {
+            table_hook: Some(ranges),
+            ..Config::default()
+        };
+        config.set("transforms", "sort_table_rows:B").unwrap();
+        let input = "| A | B |\n|---|:-:|\n| x | 2 |\n| y | 1 |\n";
+        let output = <MarkdownFormatter>::with_config(config)
+            .format(input)
+            .unwrap();
+        let expected = "\
+| A@1..4   |
+| -------- |
+| y@31..34 |
+| x@21..24 |
+";
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn reads_source_delimiters() {
+        let source = "| a | b | c |\n|-----|:-:| --: |\n| 1 | 2 | 3 |";
+        assert_eq!(source_delimiters(source).unwrap(), ["-----", ":-:", "--:"]);
+        let source = "> a | b\n> ---|:---\n";
+        assert_eq!(source_delimiters(source).unwrap(), ["---", ":---"]);
+        assert_eq!(source_delimiters("| a |\n| b |"), None);
+    }
+
+    #[test]
+    fn escapes_cell_pipes() {
+        assert_eq!(escape_pipes("a|b".into(), false), r"a\|b");
+        assert_eq!(escape_pipes(r"a\|b\\|c".into(), false), r"a\|b\\|c");
+        assert_eq!(escape_pipes("|b".into(), true), "|b");
+        assert_eq!(escape_pipes("|b".into(), false), r"\|b");
+    }
+}
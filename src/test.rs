@@ -1,14 +1,16 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
-use insta::{assert_snapshot, glob, Settings};
+use insta::{Settings, assert_snapshot, glob};
 use rust_search::SearchBuilder;
 
 use super::*;
 
 impl MarkdownFormatter<DefaultFormatterCombination> {
+    /// Build a formatter from `<!-- :option: value -->` comments at the top of the input.
     pub fn from_leading_config_comments(input: &str) -> Self {
         let mut config = Config {
             max_width: None,
@@ -27,10 +29,10 @@ impl MarkdownFormatter<DefaultFormatterCombination> {
             else {
                 continue;
             };
-            config.set(config_option, value.trim());
+            config.set(config_option, value.trim()).unwrap();
         }
 
-        MarkdownFormatter::with_config(config)
+        <MarkdownFormatter>::with_config(config)
     }
 }
 
@@ -41,6 +43,52 @@ fn init_tracing() {
         .try_init();
 }
 
+/// Run `f` with a subscriber that logs to the returned string,
+/// filtered by `filter`, e.g., `"debug"`, regardless of `RUST_LOG`.
+pub(crate) fn capture_tracing<T>(filter: &str, f: impl FnOnce() -> T) -> (T, String) {
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(filter))
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let output = {
+        let _guard = tracing_subscriber::util::SubscriberInitExt::set_default(subscriber);
+        f()
+    };
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    (output, logs)
+}
+
+#[test]
+fn block_spans() {
+    let (_, logs) = capture_tracing("trace", || {
+        <MarkdownFormatter>::default()
+            .format(
+                "> Quoted
+",
+            )
+            .unwrap()
+    });
+    assert!(
+        logs.contains(r#"block{kind="block_quote" range=0..9 depth=0}:block{kind="paragraph" range=2..9 depth=1}"#),
+        "{logs}"
+    );
+}
+
 #[test]
 fn reformat() {
     init_tracing();
@@ -57,7 +105,7 @@ fn main() {}
     there!
     ]: htts://example.com "Yoooo"
 "##;
-    let mut formatter = MarkdownFormatter::default();
+    let mut formatter = <MarkdownFormatter>::default();
     formatter.sichanghe_config();
     let rewrite = formatter.format(input).unwrap();
     assert_snapshot!(rewrite)
@@ -69,7 +117,7 @@ fn reformat_emoji() {
     let input = "Congratulations, that's really good news 🙂
 
 I have a couple of good firends there.";
-    let mut formatter = MarkdownFormatter::default();
+    let mut formatter = <MarkdownFormatter>::default();
     formatter.sichanghe_config();
     let rewrite = formatter.format(input).unwrap();
     assert_snapshot!(rewrite)
@@ -83,7 +131,7 @@ fn reformat_display_math_in_list() {
     $$
     a
     $$";
-    let mut formatter = MarkdownFormatter::default();
+    let mut formatter = <MarkdownFormatter>::default();
     formatter.sichanghe_config();
     let rewrite = formatter.format(input).unwrap();
     assert_snapshot!(rewrite)
@@ -100,6 +148,24 @@ pub(crate) fn get_test_files<P: AsRef<Path>>(
         .map(PathBuf::from)
 }
 
+#[test]
+fn reformat_zero_padded_lists() {
+    init_tracing();
+    let input = "8. a\n9. b\n10. c\n\n- x\n\n010) keep width\n11) padded\n\n000. zero\n";
+    let mut formatter = <MarkdownFormatter>::default();
+    formatter.config_mut().zero_pad_to_widest_number = true;
+    let rewrite = formatter.format(input).unwrap();
+    assert_eq!(
+        rewrite,
+        "08. a\n09. b\n10. c\n\n- x\n\n010) keep width\n011) padded\n\n000. zero\n"
+    );
+
+    let mut formatter = <MarkdownFormatter>::default();
+    formatter.config_mut().fixed_number = Some(1);
+    let rewrite = formatter.format("010. a\n").unwrap();
+    assert_eq!(rewrite, "001. a\n");
+}
+
 #[test]
 fn check_markdown_formatting() {
     init_tracing();
@@ -145,3 +211,115 @@ fn idempotence_test() {
         }
     });
 }
+
+#[test]
+fn trailing_backslashes_round_trip() {
+    init_tracing();
+    // Hard breaks are invalid in ATX headings and table cells,
+    // where a trailing `\` is literal, and a literal `\` must never end a wrapped line.
+    let input = fs::read_to_string("src/source/trailing_backslashes.md").unwrap();
+    fn events(markdown: &str) -> Vec<Event<'_>> {
+        let events = Parser::new_ext(markdown, parser_options()).filter_map(|event| match event {
+            Event::SoftBreak => Some(Event::Text(" ".into())),
+            Event::Html(_) => None,
+            event => Some(event),
+        });
+        pulldown_cmark::TextMergeStream::new(events).collect()
+    }
+    for (option, value) in [
+        ("max_width", "10"),
+        ("max_width", "20"),
+        ("compact_tables", "true"),
+    ] {
+        let mut config = Config::default();
+        config.set(option, value).unwrap();
+        let rewrite = <MarkdownFormatter>::with_config(config)
+            .format(&input)
+            .unwrap();
+        assert_eq!(events(&rewrite), events(&input), "{option}: {value}");
+    }
+}
+
+#[test]
+fn tab_indentation_keeps_events() {
+    init_tracing();
+    let input = fs::read_to_string("src/source/tab_indentation.md").unwrap();
+    fn events(markdown: &str) -> Vec<Event<'_>> {
+        let events = Parser::new_ext(markdown, parser_options()).map(|event| match event {
+            Event::SoftBreak => Event::Text(" ".into()),
+            event => event,
+        });
+        pulldown_cmark::TextMergeStream::new(events).collect()
+    }
+    let expanded = utils::expand_indentation_tabs(&input, 4).unwrap();
+    assert!(!expanded.contains("\n\t-"));
+    assert_eq!(events(&expanded), events(&input));
+
+    let mut config = Config::default();
+    config.set("tab_width", "4").unwrap();
+    let rewrite = <MarkdownFormatter>::with_config(config)
+        .format(&input)
+        .unwrap();
+    assert_eq!(events(&rewrite), events(&input));
+}
+
+#[test]
+fn format_many_matches_format() {
+    init_tracing();
+    // The shared configuration must not carry anything over between documents.
+    let inputs = get_test_files("src/source", "md")
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>();
+    let formatter = <MarkdownFormatter>::with_config(Config::sichanghe_opinion());
+    let rewrites = formatter
+        .clone()
+        .format_many(inputs.iter().map(String::as_str))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    for (input, rewrite) in inputs.iter().zip(rewrites) {
+        assert_eq!(rewrite, formatter.clone().format(input).unwrap());
+    }
+}
+
+#[test]
+fn format_inline_escapes_block_starts() {
+    init_tracing();
+    for (text, expected) in [
+        ("# not a heading", "\\# not a heading"),
+        ("- not\n  a list", "\\- not a list"),
+        ("2) not a list", "2\\) not a list"),
+        ("> not a quote", "\\> not a quote"),
+        ("    not code", "not code"),
+        ("[not]: https://example.com", "\\[not]: https://example.com"),
+        ("*already* inline", "*already* inline"),
+        ("<span>", "<span>"),
+        ("<span>inline</span> HTML", "<span>inline</span> HTML"),
+        ("<!-- comment -->", "<!-- comment -->"),
+        ("<https://example.com>", "<https://example.com>"),
+        (
+            "<https://example.com>\nautolink",
+            "<https://example.com> autolink",
+        ),
+    ] {
+        let formatter = <MarkdownFormatter>::default();
+        assert_eq!(formatter.format_inline(text).unwrap(), expected, "{text:?}");
+    }
+}
+
+#[test]
+fn html_blocks_keep_events() {
+    init_tracing();
+    // Markdown-looking lines in HTML blocks must stay in their blocks.
+    let input = fs::read_to_string("src/source/html_block_markdown_content.md").unwrap();
+    fn events(markdown: &str) -> Vec<Event<'_>> {
+        let events = Parser::new_ext(markdown, parser_options()).map(|event| match event {
+            Event::Html(html) => Event::Html(html.trim_start().to_owned().into()),
+            event => event,
+        });
+        pulldown_cmark::TextMergeStream::new(events).collect()
+    }
+    let rewrite = <MarkdownFormatter>::default().format(&input).unwrap();
+    assert_eq!(events(&rewrite), events(&input));
+    let reformatted = <MarkdownFormatter>::default().format(&rewrite).unwrap();
+    assert_eq!(reformatted, rewrite);
+}
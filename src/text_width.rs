@@ -0,0 +1,54 @@
+//! Display width of text, as the formatter measures it to wrap lines and align tables,
+//! e.g., for [`ExternalFormatter`](crate::ExternalFormatter) implementations
+//! to make the same width decisions.
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The number of columns `text` takes up in a monospace font,
+/// e.g., 2 for most CJK characters and emoji.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::text_width::width;
+/// assert_eq!(width("abc"), 3);
+/// assert_eq!(width("日本"), 4);
+/// ```
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// The longest prefix of `text` at most `max_width` columns wide
+/// that does not split grapheme clusters, e.g., a letter and its accents.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::text_width::truncate;
+/// assert_eq!(truncate("abc", 2), "ab");
+/// assert_eq!(truncate("日本語", 5), "日本");
+/// assert_eq!(truncate("e\u{301}e\u{301}", 1), "e\u{301}");
+/// ```
+pub fn truncate(text: &str, max_width: usize) -> &str {
+    let mut total_width = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        total_width += width(grapheme);
+        if total_width > max_width {
+            return &text[..index];
+        }
+    }
+    text
+}
+
+/// `text` padded with trailing spaces to be at least `min_width` columns wide.
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::text_width::pad;
+/// assert_eq!(pad("ab", 4), "ab  ");
+/// assert_eq!(pad("日本", 5), "日本 ");
+/// assert_eq!(pad("abc", 2), "abc");
+/// ```
+pub fn pad(text: &str, min_width: usize) -> Cow<'_, str> {
+    match min_width.checked_sub(width(text)) {
+        Some(padding) if padding > 0 => format!("{text}{:padding$}", "").into(),
+        _ => text.into(),
+    }
+}
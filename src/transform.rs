@@ -0,0 +1,840 @@
+//! Opt-in transforms that change the content of a document while formatting,
+//! e.g., to keep maintained data tables sorted.
+//! Unlike formatting options, they are never part of the default formatting.
+//!
+//! Transforms apply to the whole document when listed in [`Config::transforms`].
+//! Table transforms can also apply to the next table only
+//! when listed in a [`MARKER`] HTML comment, e.g.:
+//!
+//! ```markdown
+//! <!-- markdown-fmt: drop_table_columns:Internal notes; sort_table_rows:Name -->
+//! | Name | Internal notes |
+//! | ---- | -------------- |
+//! ```
+use std::cmp::Ordering;
+
+use super::*;
+
+/// Opening of the HTML comments listing transforms for the next table,
+/// separated by `;` as in [`Config::set`].
+pub const MARKER: &str = "<!-- markdown-fmt:";
+
+/// A transform applied while formatting, listed in [`Config::transforms`].
+///
+/// ```rust
+/// # use fmtm_ytmimi_markdown_fmt::{Config, MarkdownFormatter, transform::{CellOrder, Transform}};
+/// let input = "| Crate | Version |\n| - | -: |\n| regex | 1 |\n| itertools | 0.13 |\n";
+/// let config = Config {
+///     transforms: vec![Transform::SortTableRows {
+///         column: "Crate".into(),
+///         order: CellOrder::Text,
+///         descending: false,
+///     }],
+///     ..Default::default()
+/// };
+/// let rewrite = <MarkdownFormatter>::with_config(config).format(input).unwrap();
+/// assert_eq!(
+///     rewrite,
+///     "| Crate     | Version |\n| --------- | ------: |\n| itertools | 0.13    |\n| regex     | 1       |\n"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transform {
+    /// Sort the body rows of the tables with a `column` header by their cells
+    /// in that column, keeping the header and alignment.
+    /// Rows with equal cells keep their order.
+    SortTableRows {
+        /// Header of the column to sort by, ignoring surrounding whitespace.
+        column: String,
+        /// How to compare the cells.
+        order: CellOrder,
+        /// Sort from the largest cell to the smallest.
+        descending: bool,
+    },
+    /// Keep only the table columns with these headers, in this order,
+    /// e.g., to reorder columns.
+    /// Tables with none of the headers are left as is.
+    SelectTableColumns {
+        /// Headers of the columns to keep, ignoring surrounding whitespace.
+        columns: Vec<String>,
+    },
+    /// Remove the table columns with these headers,
+    /// e.g., columns of generated tables that should not be published.
+    /// Tables would be left with no columns are left as is.
+    DropTableColumns {
+        /// Headers of the columns to remove, ignoring surrounding whitespace.
+        columns: Vec<String>,
+    },
+    /// Replace whole-word terms in prose with their canonical forms,
+    /// e.g., `master branch` with `main branch`,
+    /// ignoring ASCII case and keeping a capitalized first letter.
+    /// Code, link destinations, HTML, and bare URLs are left as is,
+    /// and terms are only found within a line of text.
+    ReplaceTerms {
+        /// Pairs of terms and their replacements, tried in order.
+        terms: Vec<(String, String)>,
+    },
+    /// Turn paragraph lines starting with `•` or `–` bullets,
+    /// e.g., pasted from word processors, into `-` list items before formatting,
+    /// and lines starting with `◦` into items nested under them.
+    NormalizeBullets,
+    /// Remove trailing periods and colons from headings,
+    /// as many style guides require,
+    /// and report each heading changed as a
+    /// [`DiagnosticKind::HeadingTrailingPunctuation`].
+    StripHeadingPunctuation {
+        /// Endings of headings to keep as is, e.g., `etc.` or `...`.
+        exceptions: Vec<String>,
+    },
+    /// Move the clause after the first ` — ` of ATX headings wider than
+    /// `max_width` columns into a paragraph right after the heading,
+    /// e.g., `# Setup — installing and configuring the tool`
+    /// becomes `# Setup` with the paragraph `installing and configuring the tool`.
+    /// See [`lint::long_headings`] to report long headings instead.
+    SplitLongHeadings {
+        /// Widest heading text in columns, without the `#` markers.
+        max_width: usize,
+    },
+    /// Update the word count and reading time of the document's prose,
+    /// excluding code blocks, HTML, and metadata,
+    /// in the `words` and `reading_time` front matter fields
+    /// and the `<!-- words: 1234 -->` and `<!-- reading-time: 7 min -->` comments
+    /// already in the document.
+    WordCount {
+        /// Reading speed to compute the reading time in whole minutes with.
+        words_per_minute: usize,
+    },
+}
+
+/// How [`Transform::SortTableRows`] compares cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellOrder {
+    /// As text, ignoring case.
+    Text,
+    /// As numbers, e.g., `9` before `10`.
+    /// Cells that are not numbers come after all numbers, in their original order.
+    Numeric,
+}
+
+impl Transform {
+    /// Apply the transform to `table` if it applies to tables.
+    pub(crate) fn apply_to_table(&self, table: &mut TableState) {
+        match self {
+            Self::SortTableRows {
+                column,
+                order,
+                descending,
+            } => table.sort_rows(column, |a, b| {
+                let ordering = compare_cells(a, b, *order);
+                match (descending, order) {
+                    // Keep cells that are not numbers last.
+                    (true, CellOrder::Numeric) if number(a).is_none() || number(b).is_none() => {
+                        ordering
+                    }
+                    (true, _) => ordering.reverse(),
+                    (false, _) => ordering,
+                }
+            }),
+            Self::SelectTableColumns { columns } => table.select_columns(columns),
+            Self::DropTableColumns { columns } => table.drop_columns(columns),
+            Self::ReplaceTerms { .. }
+            | Self::NormalizeBullets
+            | Self::StripHeadingPunctuation { .. }
+            | Self::SplitLongHeadings { .. }
+            | Self::WordCount { .. } => {}
+        }
+    }
+
+    /// Length of the `heading` text written so far without the trailing
+    /// punctuation to strip, or `None` if the transform keeps it as is.
+    pub(crate) fn stripped_heading_len(&self, heading: &str) -> Option<usize> {
+        let Self::StripHeadingPunctuation { exceptions } = self else {
+            return None;
+        };
+        if exceptions
+            .iter()
+            .any(|exception| heading.ends_with(exception.as_str()))
+        {
+            return None;
+        }
+        let stripped = heading.trim_end_matches(['.', ':']);
+        // Keep escaped punctuation and headings of only punctuation.
+        let keeps_text =
+            !stripped.ends_with(|c: char| c.is_whitespace() || matches!(c, '\\' | '#'));
+        (stripped.len() < heading.len() && keeps_text).then_some(stripped.len())
+    }
+
+    /// Apply the transform to the source `input` before formatting,
+    /// returning `None` if it does not apply or changes nothing.
+    pub(crate) fn apply_to_source(&self, input: &str) -> Option<String> {
+        match self {
+            Self::NormalizeBullets => normalize_bullets(input),
+            Self::SplitLongHeadings { max_width } => split_long_headings(input, *max_width),
+            Self::WordCount { words_per_minute } => update_word_count(input, *words_per_minute),
+            _ => None,
+        }
+    }
+
+    /// Check if the transform only changes tables,
+    /// so it can be listed in [`MARKER`] comments.
+    fn applies_to_tables(&self) -> bool {
+        matches!(
+            self,
+            Self::SortTableRows { .. }
+                | Self::SelectTableColumns { .. }
+                | Self::DropTableColumns { .. }
+        )
+    }
+
+    /// Apply the transform to prose `text` if it applies to text.
+    pub(crate) fn apply_to_text<'t>(&self, text: Cow<'t, str>) -> Cow<'t, str> {
+        match self {
+            Self::ReplaceTerms { terms } => replace_terms(text, terms),
+            _ => text,
+        }
+    }
+}
+
+fn replace_terms<'t>(text: Cow<'t, str>, terms: &[(String, String)]) -> Cow<'t, str> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let urls = text
+        .split_whitespace()
+        .filter(|token| token.contains("://") || token.trim_start_matches('<').starts_with("www."))
+        .map(|token| {
+            let start = token.as_ptr() as usize - text.as_ptr() as usize;
+            start..start + token.len()
+        })
+        .collect_vec();
+
+    let mut result = String::new();
+    let mut written = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        let starts_word = !text[..index].ends_with(is_word_char);
+        let matched = terms.iter().find(|(term, _)| {
+            let end = index + term.len();
+            starts_word
+                && !term.is_empty()
+                && rest
+                    .get(..term.len())
+                    .is_some_and(|s| s.eq_ignore_ascii_case(term))
+                && !text[end..].starts_with(is_word_char)
+                && !urls.iter().any(|url| url.start < end && index < url.end)
+        });
+        let Some((term, replacement)) = matched else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        result.push_str(&text[written..index]);
+        let mut chars = replacement.chars();
+        match (rest.chars().next(), chars.next()) {
+            (Some(first), Some(replaced)) if first.is_uppercase() && replaced.is_lowercase() => {
+                result.extend(replaced.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+            _ => result.push_str(replacement),
+        }
+        index += term.len();
+        written = index;
+    }
+    if written == 0 {
+        return text;
+    }
+    result.push_str(&text[written..]);
+    result.into()
+}
+
+/// Parse the transforms of the [`MARKER`] comment `html`,
+/// or return `None` if `html` is not one.
+pub(crate) fn parse_marker(html: &str) -> Option<Result<Vec<Transform>, String>> {
+    let transforms = html.trim().strip_prefix(MARKER)?.strip_suffix("-->")?;
+    let parse = |transform: &str| {
+        let transform = transform.trim();
+        match transform.parse::<Transform>() {
+            Ok(parsed) if !parsed.applies_to_tables() => {
+                let name = transform.split(':').next().unwrap_or_default();
+                Err(format!("`{name}` only applies to whole documents"))
+            }
+            parsed => parsed,
+        }
+    };
+    Some(transforms.split(';').map(parse).collect())
+}
+
+/// `input` with the bullets starting paragraph lines replaced with list markers,
+/// or `None` if there are none.
+fn normalize_bullets(input: &str) -> Option<String> {
+    let mut edits = vec![];
+    let mut at_line_start = false;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Paragraph) | Event::SoftBreak | Event::HardBreak => {
+                at_line_start = true;
+                continue;
+            }
+            Event::Text(_) if at_line_start => {
+                let text = &input[range.clone()];
+                let mut chars = text.chars();
+                let marker = match chars.next() {
+                    Some('•' | '–') => "- ",
+                    Some('◦') => "  - ",
+                    _ => "",
+                };
+                let rest = chars.as_str();
+                let content = rest.trim_start_matches([' ', '\t']);
+                if !marker.is_empty() && content.len() < rest.len() && !content.is_empty() {
+                    let end = range.start + text.len() - content.len();
+                    edits.push((range.start..end, marker.to_owned()));
+                }
+            }
+            _ => {}
+        }
+        at_line_start = false;
+    }
+    (!edits.is_empty()).then(|| apply_edits(input, edits))
+}
+
+/// `input` with the clauses after the em dashes of headings wider than
+/// `max_width` moved into paragraphs after them, or `None` if there are none.
+fn split_long_headings(input: &str, max_width: usize) -> Option<String> {
+    let mut edits = vec![];
+    for heading in lint::heading_texts(input) {
+        let Some(em_dash) = heading.em_dash else {
+            continue;
+        };
+        let clause = input[em_dash + " — ".len()..heading.text.end].trim();
+        let title = input[heading.text.start..em_dash].trim();
+        if text_width::width(&input[heading.text.clone()]) <= max_width
+            || clause.is_empty()
+            || title.is_empty()
+        {
+            continue;
+        }
+        // Continue the heading's containers, e.g., `> ` for block quotes.
+        let line_start = input[..heading.range.start]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let prefix: String = input[line_start..heading.range.start]
+            .chars()
+            .map(|c| {
+                if c == '>' || c.is_whitespace() {
+                    c
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        let blank_line = prefix.trim_end();
+        let heading_end = heading.range.start + input[heading.range].trim_end().len();
+        let next_line = input[heading_end..].split('\n').nth(1);
+        let next_is_blank = next_line.map_or(true, |line| {
+            line.trim().trim_start_matches('>').trim().is_empty()
+        });
+        let mut paragraph = format!("\n{blank_line}\n{prefix}{clause}");
+        if !next_is_blank {
+            paragraph = format!("{paragraph}\n{blank_line}");
+        }
+        edits.push((em_dash..heading.text.end, String::new()));
+        edits.push((heading_end..heading_end, paragraph));
+    }
+    (!edits.is_empty()).then(|| apply_edits(input, edits))
+}
+
+/// `input` with its word count and reading time fields and comments updated,
+/// or `None` if there are none.
+fn update_word_count(input: &str, words_per_minute: usize) -> Option<String> {
+    let mut prose = String::new();
+    let mut fields = vec![];
+    let mut skip_depth = 0;
+    for (event, range) in Parser::new_ext(input, parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock) => skip_depth += 1,
+            Event::Start(Tag::MetadataBlock(_)) => {
+                skip_depth += 1;
+                fields.push(range);
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                skip_depth -= 1
+            }
+            Event::Html(_) | Event::InlineHtml(_) => fields.push(range),
+            Event::Text(text) | Event::Code(text) if skip_depth == 0 => prose.push_str(&text),
+            // Separate the words of different lines and blocks,
+            // but not those split by inline markup, e.g., `*wor*ld`.
+            Event::End(
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Link
+                | TagEnd::Image,
+            ) => {}
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => prose.push(' '),
+            _ => {}
+        }
+    }
+    let words = prose.split_whitespace().count();
+    let minutes = words.div_ceil(words_per_minute.max(1));
+
+    let mut edits = vec![];
+    for range in fields {
+        let mut line_start = range.start;
+        for line in input[range].split_inclusive('\n') {
+            let number = word_count_field(line).map(|(field, _)| match field {
+                "words" => words,
+                _ => minutes,
+            });
+            if let (Some(number), Some(digits)) = (number, word_count_digits(line)) {
+                let digits = line_start + digits.start..line_start + digits.end;
+                if input[digits.clone()] != number.to_string() {
+                    edits.push((digits, number.to_string()));
+                }
+            }
+            line_start += line.len();
+        }
+    }
+    (!edits.is_empty()).then(|| apply_edits(input, edits))
+}
+
+/// The word count field `line` sets, and its number,
+/// e.g., `words: 12` in YAML, `reading_time = 1` in TOML, or `<!-- words: 12 -->`.
+fn word_count_field(line: &str) -> Option<(&str, usize)> {
+    let line = line.trim();
+    let line = match line.strip_prefix("<!--") {
+        Some(comment) => comment.strip_suffix("-->")?.trim(),
+        None => line,
+    };
+    let (field, value) = line.split_once([':', '='])?;
+    let field = match field.trim() {
+        "words" => "words",
+        "reading_time" | "reading-time" => "reading_time",
+        _ => return None,
+    };
+    let value = value.trim();
+    let value = value.strip_suffix("min").unwrap_or(value).trim();
+    Some((field, value.parse().ok()?))
+}
+
+/// Byte range of the number in the word count field `line`.
+fn word_count_digits(line: &str) -> Option<Range<usize>> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let len = line[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(line.len() - start);
+    Some(start..start + len)
+}
+
+fn compare_cells(a: &str, b: &str, order: CellOrder) -> Ordering {
+    match order {
+        CellOrder::Text => a.trim().to_lowercase().cmp(&b.trim().to_lowercase()),
+        CellOrder::Numeric => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    }
+}
+
+fn number(cell: &str) -> Option<f64> {
+    cell.trim().parse().ok()
+}
+
+/// Parse `sort_table_rows:<column>`,
+/// optionally followed by `:numeric` and `:descending`,
+/// e.g., `sort_table_rows:Downloads:numeric:descending`,
+/// or `select_table_columns:<columns>` or `drop_table_columns:<columns>`
+/// with `,`-separated headers, e.g., `drop_table_columns:Owner,Notes`,
+/// or `replace_terms:<terms>` with `,`-separated `<term> -> <replacement>` pairs,
+/// e.g., `replace_terms:master branch -> main branch, Github -> GitHub`,
+/// `normalize_bullets`, or `strip_heading_punctuation`,
+/// optionally followed by `:` and `,`-separated exceptions,
+/// e.g., `strip_heading_punctuation:etc.,...`,
+/// `split_long_headings:<max_width>`, e.g., `split_long_headings:60`,
+/// or `word_count`, optionally followed by `:<words_per_minute>`,
+/// 200 by default, e.g., `word_count:250`.
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "normalize_bullets" {
+            return Ok(Self::NormalizeBullets);
+        }
+        if let Some(words_per_minute) = s.strip_prefix("word_count") {
+            let words_per_minute = match words_per_minute.strip_prefix(':') {
+                Some(words_per_minute) => words_per_minute.trim().parse().ok(),
+                None if words_per_minute.is_empty() => Some(200),
+                None => None,
+            };
+            return match words_per_minute {
+                Some(words_per_minute) if words_per_minute > 0 => {
+                    Ok(Self::WordCount { words_per_minute })
+                }
+                _ => Err(format!("invalid transform `{s}`")),
+            };
+        }
+        if let Some(max_width) = s.strip_prefix("split_long_headings:") {
+            return match max_width.trim().parse() {
+                Ok(max_width) => Ok(Self::SplitLongHeadings { max_width }),
+                Err(_) => Err(format!("invalid `split_long_headings` width in `{s}`")),
+            };
+        }
+        if let Some(exceptions) = s.strip_prefix("strip_heading_punctuation") {
+            let exceptions = match exceptions.strip_prefix(':') {
+                Some(exceptions) => exceptions.split(',').map(|e| e.trim().into()).collect(),
+                None if exceptions.is_empty() => vec![],
+                None => return Err(format!("invalid transform `{s}`")),
+            };
+            return Ok(Self::StripHeadingPunctuation { exceptions });
+        }
+        if let Some(terms) = s.strip_prefix("replace_terms:") {
+            let term = |pair: &str| {
+                let (term, replacement) = pair.split_once("->")?;
+                let term = term.trim();
+                (!term.is_empty()).then(|| (term.into(), replacement.trim().into()))
+            };
+            let terms = terms.split(',').map(term).collect::<Option<Vec<_>>>();
+            return match terms {
+                Some(terms) if !terms.is_empty() => Ok(Self::ReplaceTerms { terms }),
+                _ => Err(format!("invalid `replace_terms` pairs in `{s}`")),
+            };
+        }
+        let headers = |columns: &str| -> Vec<String> {
+            let columns = columns.split(',').map(str::trim);
+            columns.filter(|c| !c.is_empty()).map(Into::into).collect()
+        };
+        if let Some((name, columns)) = s.split_once(':') {
+            let columns = headers(columns);
+            match name {
+                _ if columns.is_empty() => {}
+                "select_table_columns" => return Ok(Self::SelectTableColumns { columns }),
+                "drop_table_columns" => return Ok(Self::DropTableColumns { columns }),
+                _ => {}
+            }
+        }
+        let mut parts = s.split(':');
+        match (parts.next(), parts.next()) {
+            (Some("sort_table_rows"), Some(column)) if !column.trim().is_empty() => {
+                let (mut order, mut descending) = (CellOrder::Text, false);
+                for flag in parts {
+                    match flag {
+                        "numeric" => order = CellOrder::Numeric,
+                        "descending" => descending = true,
+                        _ => return Err(format!("unknown `sort_table_rows` flag `{flag}`")),
+                    }
+                }
+                Ok(Self::SortTableRows {
+                    column: column.trim().into(),
+                    order,
+                    descending,
+                })
+            }
+            _ => Err(format!("invalid transform `{s}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sort_table_rows_numerically() {
+        let input = "\
+| Name | Downloads |
+|:-----|----------:|
+| a    | 10        |
+| b    | n/a       |
+| c    | 9         |
+| d    | 10        |
+
+| Other | Table |
+|-------|-------|
+| z     | 1     |
+| y     | 2     |
+";
+        let mut config = Config::default();
+        config
+            .set("transforms", "sort_table_rows:Downloads:numeric:descending")
+            .unwrap();
+        let rewrite = <MarkdownFormatter>::with_config(config)
+            .format(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "\
+| Name | Downloads |
+| :--- | --------: |
+| a    | 10        |
+| d    | 10        |
+| c    | 9         |
+| b    | n/a       |
+
+| Other | Table |
+| ----- | ----- |
+| z     | 1     |
+| y     | 2     |
+"
+        );
+        assert!(
+            "sort_table_rows:Name:backwards"
+                .parse::<Transform>()
+                .is_err()
+        );
+    }
+    #[test]
+    fn select_and_drop_table_columns() {
+        let input = "\
+<!-- markdown-fmt: select_table_columns:Version, Crate -->
+| Crate | Owner | Version |
+|-------|-------|--------:|
+| regex | ? | 1.10 |
+| itertools |
+
+<!-- markdown-fmt: drop_table_columns:Owner -->
+| Crate | Owner | Version |
+|-------|-------|--------:|
+| regex | ? | 1.10 |
+
+| Crate | Owner |
+|-------|-------|
+| regex | ? |
+";
+        let (rewrite, diagnostics) = <MarkdownFormatter>::default()
+            .format_with_diagnostics(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "\
+<!-- markdown-fmt: select_table_columns:Version, Crate -->
+| Version | Crate     |
+| ------: | --------- |
+| 1.10    | regex     |
+|         | itertools |
+
+<!-- markdown-fmt: drop_table_columns:Owner -->
+| Crate | Version |
+| ----- | ------: |
+| regex | 1.10    |
+
+| Crate | Owner |
+| ----- | ----- |
+| regex | ?     |
+"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn invalid_marker() {
+        let input = "<!-- markdown-fmt: drop_table_columns -->\n| a |\n|---|\n| b |\n";
+        let (rewrite, diagnostics) = <MarkdownFormatter>::default()
+            .format_with_diagnostics(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            input.replace("| a |\n|---|\n| b |", "| a   |\n| --- |\n| b   |")
+        );
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                range: 0..41,
+                kind: DiagnosticKind::InvalidTransformMarker {
+                    message: "invalid transform `drop_table_columns`".into(),
+                },
+            }]
+        );
+    }
+    #[test]
+    fn replace_terms_in_prose_only() {
+        let input = "\
+# Push to Master Branch
+
+Merge into the master branch, not `master branch`,
+per <https://example.com/master branch> and [master branch](https://example.com/master-branch \"master branch\").
+See https://example.com/master branch, <span title=\"master branch\">master branch</span>,
+or themaster branches.
+
+```sh
+git push origin master branch
+```
+
+| Branch |
+| ------ |
+| master branch |
+";
+        let mut config = Config::default();
+        config
+            .set(
+                "transforms",
+                "replace_terms:master branch -> main branch, Github -> GitHub",
+            )
+            .unwrap();
+        let rewrite = <MarkdownFormatter>::with_config(config)
+            .format(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "\
+# Push to Main branch
+
+Merge into the main branch, not `master branch`,
+per <https://example.com/master branch> and [main branch](https://example.com/master-branch \"master branch\").
+See https://example.com/master branch, <span title=\"master branch\">main branch</span>,
+or themaster branches.
+
+```sh
+git push origin master branch
+```
+
+| Branch      |
+| ----------- |
+| main branch |
+"
+        );
+        assert!("replace_terms:master".parse::<Transform>().is_err());
+    }
+    #[test]
+    fn normalize_bullets() {
+        let input = "\
+Shopping list:
+• eggs
+◦ free-range
+– bread
+
+Not –a bullet, and • mid-line.
+
+> • quoted
+
+```
+• code
+```
+";
+        let mut config = Config::default();
+        config.set("transforms", "normalize_bullets").unwrap();
+        let rewrite = <MarkdownFormatter>::with_config(config)
+            .format(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "\
+Shopping list:
+- eggs
+  - free-range
+- bread
+
+Not –a bullet, and • mid-line.
+
+> - quoted
+
+```
+• code
+```
+"
+        );
+        assert_eq!(
+            parse_marker("<!-- markdown-fmt: normalize_bullets -->"),
+            Some(Err(
+                "`normalize_bullets` only applies to whole documents".into()
+            ))
+        );
+    }
+    #[test]
+    fn strip_heading_punctuation() {
+        let input = "# Introduction:\n\n## Install. ##\n\n### And so on etc.\n\n### Escaped\\.\n";
+        let mut config = Config::default();
+        config
+            .set("transforms", "strip_heading_punctuation:etc.")
+            .unwrap();
+        let (rewrite, diagnostics) = <MarkdownFormatter>::with_config(config)
+            .format_with_diagnostics(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "# Introduction\n\n## Install\n\n### And so on etc.\n\n### Escaped\\.\n"
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic {
+                    range: 0..15,
+                    kind: DiagnosticKind::HeadingTrailingPunctuation {
+                        punctuation: ":".into()
+                    },
+                },
+                Diagnostic {
+                    range: 17..31,
+                    kind: DiagnosticKind::HeadingTrailingPunctuation {
+                        punctuation: ".".into()
+                    },
+                },
+            ]
+        );
+    }
+    #[test]
+    fn split_long_headings() {
+        let input = "\
+# Setup — installing and configuring the tool
+Right after.
+
+## Short — fine
+
+> ### Quoted heading — with a long trailing clause
+";
+        let mut config = Config::default();
+        config.set("transforms", "split_long_headings:20").unwrap();
+        let rewrite = <MarkdownFormatter>::with_config(config)
+            .format(input)
+            .unwrap();
+        assert_eq!(
+            rewrite,
+            "\
+# Setup
+
+installing and configuring the tool
+
+Right after.
+
+## Short — fine
+
+> ### Quoted heading
+>
+> with a long trailing clause
+"
+        );
+    }
+
+    #[test]
+    fn update_word_count_and_reading_time() {
+        let input = "\
+---
+title: Post
+words: 0
+reading_time: 0
+---
+
+<!-- words: 1 -->
+<!-- reading-time: 9 min -->
+
+# Hello *wor*ld
+
+Some `code` text
+across lines.
+
+```
+not counted here
+```
+";
+        let rewrite = update_word_count(input, 3).unwrap();
+        assert_eq!(
+            rewrite,
+            input
+                .replace("words: 0", "words: 7")
+                .replace("reading_time: 0", "reading_time: 3")
+                .replace("words: 1", "words: 7")
+                .replace("9 min", "3 min")
+        );
+        assert_eq!(update_word_count(&rewrite, 3), None);
+        assert_eq!(update_word_count("No fields here.", 200), None);
+    }
+}
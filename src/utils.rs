@@ -1,6 +1,159 @@
-use unicode_width::UnicodeWidthStr;
+use super::*;
 
-// Duplicated from the rustfmt::util module
-pub(crate) fn unicode_str_width(s: &str) -> usize {
-    s.width()
+/// Options used to parse Markdown throughout the crate.
+pub(crate) fn parser_options() -> Options {
+    let mut options = Options::all();
+    options.remove(Options::ENABLE_SMART_PUNCTUATION);
+    options
+}
+
+/// Replace each range in `input` with its replacement.
+/// The ranges must not overlap.
+pub(crate) fn apply_edits(input: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut output = String::with_capacity(input.len());
+    let mut last_end = 0;
+    for (range, replacement) in edits {
+        debug_assert!(last_end <= range.start, "overlapping edits");
+        output.push_str(&input[last_end..range.start]);
+        output.push_str(&replacement);
+        last_end = range.end;
+    }
+    output.push_str(&input[last_end..]);
+    output
+}
+
+/// `input` with the trailing whitespace of its lines trimmed,
+/// except where it is content or a hard break,
+/// e.g., in code blocks, code spans, HTML, and link titles.
+pub(crate) fn trim_trailing_whitespace(input: &str) -> String {
+    let parser = Parser::new_ext(input, parser_options());
+    let mut protected = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, definition)| definition.span.clone())
+        .collect::<Vec<_>>();
+    protected.extend(
+        parser
+            .into_offset_iter()
+            .filter_map(|(event, range)| match event {
+                Event::Start(
+                    Tag::CodeBlock(_)
+                    | Tag::HtmlBlock
+                    | Tag::MetadataBlock(_)
+                    | Tag::Link { .. }
+                    | Tag::Image { .. },
+                )
+                | Event::Code(_)
+                | Event::InlineMath(_)
+                | Event::DisplayMath(_)
+                | Event::Html(_)
+                | Event::InlineHtml(_)
+                | Event::HardBreak => Some(range),
+                _ => None,
+            }),
+    );
+
+    let mut output = String::with_capacity(input.len());
+    let mut line_start = 0;
+    for line in input.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_end_matches([' ', '\t']);
+        let whitespace = line_start + trimmed.len()..line_start + content.len();
+        let is_protected = protected
+            .iter()
+            .any(|range| range.start < whitespace.end && whitespace.start < range.end);
+        match whitespace.is_empty() || is_protected {
+            true => output.push_str(line),
+            false => {
+                output.push_str(trimmed);
+                output.push_str(&line[content.len()..]);
+            }
+        }
+        line_start += line.len();
+    }
+    output
+}
+
+/// `input` with the tabs in the indentation of its lines expanded to spaces
+/// at stops every `tab_width` columns, or `None` if there are none.
+/// The indentation includes block quote and list markers,
+/// but not the lines of code blocks, HTML blocks, and metadata blocks,
+/// whose tabs may be content.
+/// With CommonMark's tab stop of 4 columns, the document parses the same.
+pub(crate) fn expand_indentation_tabs(input: &str, tab_width: usize) -> Option<String> {
+    if !input.contains('\t') {
+        return None;
+    }
+    let tab_width = tab_width.max(1);
+    let blocks = Parser::new_ext(input, parser_options())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut edits = vec![];
+    let mut line_start = 0;
+    for line in input.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        if blocks.iter().any(|block| block.contains(&start)) {
+            continue;
+        }
+        // The indentation ends at the first block that starts on the line.
+        let line = blocks
+            .iter()
+            .filter(|block| (start..line_start).contains(&block.start))
+            .map(|block| &line[..block.start - start])
+            .min_by_key(|prefix| prefix.len())
+            .unwrap_or(line);
+        let indentation = indentation_len(line);
+        if !line[..indentation].contains('\t') {
+            continue;
+        }
+        let mut expanded = String::with_capacity(indentation + tab_width);
+        for char in line[..indentation].chars() {
+            match char {
+                '\t' => {
+                    let spaces = tab_width - expanded.chars().count() % tab_width;
+                    expanded.extend(std::iter::repeat(' ').take(spaces));
+                }
+                _ => expanded.push(char),
+            }
+        }
+        edits.push((start..start + indentation, expanded));
+    }
+    (!edits.is_empty()).then(|| apply_edits(input, edits))
+}
+
+/// Length of the leading whitespace, block quote markers, and list markers
+/// followed by whitespace of `line`.
+fn indentation_len(line: &str) -> usize {
+    let bytes = line.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        let marker_len = match bytes[index] {
+            b' ' | b'\t' | b'>' => 1,
+            b'-' | b'*' | b'+' => 1,
+            b'0'..=b'9' => {
+                let digits = bytes[index..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                match bytes.get(index + digits) {
+                    Some(b'.' | b')') if digits <= 9 => digits + 1,
+                    _ => break,
+                }
+            }
+            _ => break,
+        };
+        let is_list_marker = !matches!(bytes[index], b' ' | b'\t' | b'>');
+        if is_list_marker && !matches!(bytes.get(index + marker_len), Some(b' ' | b'\t')) {
+            break;
+        }
+        index += marker_len;
+    }
+    index
 }
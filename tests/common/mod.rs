@@ -1,11 +1,11 @@
-/// Collection of common functions / macros used for generating tests
+//! Collection of common functions / macros used for generating tests
 
 #[allow(dead_code)]
 pub fn check_formatted_markdown<'a>(
     input: &'a str,
     expected_output: &str,
 ) -> std::borrow::Cow<'a, str> {
-    let formatted = fmtm_ytmimi_markdown_fmt::MarkdownFormatter::default()
+    let formatted = <fmtm_ytmimi_markdown_fmt::MarkdownFormatter>::default()
         .format(input)
         .expect("formatting won't fail");
 
@@ -13,6 +13,31 @@ pub fn check_formatted_markdown<'a>(
     formatted.into()
 }
 
+/// Check that the [`Config::preserve`] profile only trims trailing whitespace,
+/// and that the input and output parse to exactly the same Markdown events.
+///
+/// [`Config::preserve`]: fmtm_ytmimi_markdown_fmt::Config::preserve
+pub fn check_preserved_markdown(input: &str) {
+    let preserved = <fmtm_ytmimi_markdown_fmt::MarkdownFormatter>::with_config(
+        fmtm_ytmimi_markdown_fmt::Config::preserve(),
+    )
+    .format(input)
+    .expect("formatting won't fail");
+
+    let trimmed_lines = |markdown: &str| {
+        markdown
+            .split('\n')
+            .map(|line| line.trim_end().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(trimmed_lines(&preserved), trimmed_lines(input));
+
+    let mut options = pulldown_cmark::Options::all();
+    options.remove(pulldown_cmark::Options::ENABLE_SMART_PUNCTUATION);
+    let events = |markdown| pulldown_cmark::Parser::new_ext(markdown, options).collect::<Vec<_>>();
+    assert_eq!(events(&preserved), events(input));
+}
+
 pub fn init_tracing() {
     _ = tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
@@ -20,6 +45,7 @@ pub fn init_tracing() {
         .try_init();
 }
 
+/// Check that the input formats to the expected output, and that the output is idempotent.
 #[macro_export]
 macro_rules! test {
     ($input:expr) => {
@@ -27,6 +53,7 @@ macro_rules! test {
     };
     ($input:expr, $output:expr) => {{
         $crate::common::init_tracing();
+        $crate::common::check_preserved_markdown($input);
         let formatted = $crate::common::check_formatted_markdown($input, $output);
         if $input != $output {
             // Perform an idempotency check on the formatted markdown
@@ -36,6 +63,7 @@ macro_rules! test {
     }};
 }
 
+/// Like [`test!`], but also check that the input and output parse to the same Markdown events.
 #[macro_export]
 macro_rules! test_identical_markdown_events {
     ($input:expr) => {
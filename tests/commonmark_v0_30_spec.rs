@@ -2122,7 +2122,7 @@ fn markdown_link_reference_definitions_202() {
         r##"[foo]: /url\bar\*baz "foo\"bar\baz"
 
 [foo]"##,
-        r##"[foo]: /url\bar\*baz "foo\"bar\baz"
+        r##"[foo]: /url\bar\*baz 'foo"bar\baz'
 
 [foo]"##
     );
@@ -2305,8 +2305,7 @@ fn markdown_link_reference_definitions_218() {
 > [foo]: /url"##,
         r##"[foo]
 
->
-[foo]: /url"##
+> [foo]: /url"##
     );
 }
 
@@ -3487,7 +3486,7 @@ fn markdown_lists_317() {
         r##"- a
 - b
 
-[ref]: /url
+  [ref]: /url
 - d"##
     );
 }
@@ -4713,7 +4712,10 @@ fn markdown_links_504() {
 #[test]
 fn markdown_links_505() {
     // https://spec.commonmark.org/0.30/#example-505
-    test_identical_markdown_events!(r##"[link](/url "title \"&quot;")"##);
+    test_identical_markdown_events!(
+        r##"[link](/url "title \"&quot;")"##,
+        r##"[link](/url 'title "&quot;')"##
+    );
 }
 
 #[test]
@@ -1513,7 +1513,7 @@ fn gfm_markdown_link_reference_definitions_171() {
     // https://github.github.com/gfm/#example-171
     test_identical_markdown_events!(r##"[foo]: /url\bar\*baz "foo\"bar\baz"
 
-[foo]"##,r##"[foo]: /url\bar\*baz "foo\"bar\baz"
+[foo]"##,r##"[foo]: /url\bar\*baz 'foo"bar\baz'
 
 [foo]"##);
 }
@@ -1668,8 +1668,7 @@ fn gfm_markdown_link_reference_definitions_187() {
 
 > [foo]: /url"##,r##"[foo]
 
->
-[foo]: /url"##);
+> [foo]: /url"##);
 }
 
 #[test]
@@ -2726,7 +2725,7 @@ fn gfm_markdown_lists_297() {
 - d"##,r##"- a
 - b
 
-[ref]: /url
+  [ref]: /url
 - d"##);
 }
 
@@ -4107,7 +4106,7 @@ fn gfm_markdown_links_513() {
 #[test]
 fn gfm_markdown_links_514() {
     // https://github.github.com/gfm/#example-514
-    test_identical_markdown_events!(r##"[link](/url "title \"&quot;")"##);
+    test_identical_markdown_events!(r##"[link](/url "title \"&quot;")"##,r##"[link](/url 'title "&quot;')"##);
 }
 
 #[test]